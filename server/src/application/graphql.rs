@@ -0,0 +1,545 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::{Arc, Mutex, RwLock};
+
+use actix_web::dev::HttpServiceFactory;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use async_graphql::dynamic::{
+    Field, FieldFuture, FieldValue, InputObject, InputValue, Object, ObjectAccessor, Scalar, Schema, TypeRef
+};
+use async_graphql::{Error as GraphQLError, ErrorExtensions, Value};
+use futures::future::{BoxFuture, FutureExt, Shared};
+
+use crate::application::{ApplicationState, v1query};
+use crate::audit::{AuditAction, AuditEvent, AuditLog};
+use crate::backend::{Backend, Cancellation};
+use crate::metainfo::{Column, ColumnType, Entity, MetaInfo};
+use crate::metrics::Metrics;
+use crate::security::SecurityContext;
+use crate::server::ApiError;
+use crate::telemetry::Trace;
+
+// row of query result, by column name
+type Row = serde_json::Map<String, serde_json::Value>;
+
+const QUERY_TYPE: &str = "Query";
+const LONG_SCALAR: &str = "Long";
+const DATETIME_SCALAR: &str = "DateTime";
+// nesting of relations in one request
+const MAX_DEPTH: usize = 10;
+// count of fields in one request
+const MAX_COMPLEXITY: usize = 500;
+// keys of relation in one query, oracle allows up to 1000 expressions in list
+const MAX_KEYS: usize = 500;
+
+pub fn graphql_resource() -> impl HttpServiceFactory {
+    web::resource("/graphql")
+        .route(web::post().to(graphql))
+}
+
+async fn graphql(req: HttpRequest, body: web::Json<async_graphql::Request>, data: web::Data<Arc<ApplicationState>>) -> impl Responder {
+    let schema = match data.graphql.schema(&data.metainfo()) {
+        Ok(schema) => schema,
+        Err(e) => return data.error_response(&req, e)
+    };
+
+    let user_id = req.extensions().get::<SecurityContext>().map(|ctx| ctx.user_id()).unwrap_or(0);
+    // statements of all resolvers are limited by timeout of request and cancelled, when client is gone
+    let cancellation = data.cancellation(&req);
+    let _guard = cancellation.guard();
+    let context = RequestContext {
+        user_id, endpoint: req.path().to_string(), audit: data.audit.clone(), backend: data.backend(), metrics: data.metrics(),
+        trace: Trace::of(&req), cancellation, max_scan_rows: data.max_scan_rows()
+    };
+
+    let response = schema.execute(body.into_inner().data(context)).await;
+    HttpResponse::Ok().json(response)
+}
+
+/// Data of request, available for resolvers
+#[derive(Clone)]
+struct RequestContext {
+    user_id:  u32,
+    endpoint: String,
+    audit:    AuditLog,
+    backend:  Arc<dyn Backend>,
+    metrics:  Arc<Metrics>,
+    trace:    Trace,
+    cancellation: Cancellation,
+    max_scan_rows: u32,
+}
+
+/// GraphQL schema for current metainfo, rebuilt after reload of metainfo
+#[derive(Default)]
+pub struct GraphQLSchema {
+    cached: RwLock<Option<(Arc<MetaInfo>, Schema)>>,
+}
+
+impl GraphQLSchema {
+    fn schema(&self, metainfo: &Arc<MetaInfo>) -> Result<Schema, ApiError> {
+        if let Some((cached_metainfo, schema)) = &*self.cached.read().unwrap() {
+            if Arc::ptr_eq(cached_metainfo, metainfo) {
+                return Ok(schema.clone());
+            }
+        }
+
+        let schema = build_schema(metainfo)
+            .map_err(|err| ApiError::Internal(format!("Can not build GraphQL schema: {}", err)))?
+            .ok_or_else(|| ApiError::NotFound("No entities are exposed for GraphQL".to_string()))?;
+        *self.cached.write().unwrap() = Some((metainfo.clone(), schema.clone()));
+        Ok(schema)
+    }
+}
+
+/// Entity with names of GraphQL type and fields
+struct Target {
+    datasource:  String,
+    schema_name: String,
+    entity_name: String,
+    entity:      Arc<Entity>,
+    type_name:   String,
+    columns:     Vec<(String, usize)>, // field name and position of column
+}
+
+impl Target {
+    fn column_name(&self, field_name: &str) -> Option<&str> {
+        self.columns
+            .iter()
+            .find(|(name, _)| name == field_name)
+            .map(|(_, i)| self.entity.columns[*i].name.as_str())
+    }
+}
+
+/// Field of object type for foreign key
+struct Relation {
+    field:   String,
+    target:  usize,
+    columns: Vec<(String, String)>, // column of this entity and column of target entity
+    many:    bool, // list of referencing entities, otherwise referenced entity
+}
+
+/// names of GraphQL types and fields: `[_A-Za-z][_0-9A-Za-z]*`
+fn graphql_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_digit()) || name.starts_with("__") {
+        format!("t{}", name)
+    } else {
+        name
+    }
+}
+
+fn unique_name(used: &mut HashSet<String>, name: String, alternative: impl FnOnce() -> String) -> String {
+    let name = if used.contains(&name) { alternative() } else { name };
+    let mut unique = name.clone();
+    let mut suffix = 2;
+    while used.contains(&unique) {
+        unique = format!("{}_{}", name, suffix);
+        suffix += 1;
+    }
+    used.insert(unique.clone());
+    unique
+}
+
+fn column_type(column: &Column) -> TypeRef {
+    let name = match column.col_type {
+        ColumnType::Integer if column.col_size <= 4 => TypeRef::INT,
+        ColumnType::Integer  => LONG_SCALAR,
+        ColumnType::Number   => TypeRef::FLOAT,
+        ColumnType::DateTime => DATETIME_SCALAR,
+        ColumnType::String | ColumnType::Unsupported => TypeRef::STRING,
+    };
+    if column.nullable { TypeRef::named(name) } else { TypeRef::named_nn(name) }
+}
+
+/// Rows of one list: relations are loaded for all rows of list together, with one query per relation field
+struct Batch {
+    rows:  Vec<Row>,
+    loads: Mutex<HashMap<String, SharedLoad>>, // by response key of relation field
+}
+
+/// Rows of relation for all rows of batch, by values of key
+struct Loaded {
+    batch:  Arc<Batch>,
+    by_key: HashMap<Vec<String>, Vec<usize>>,
+}
+
+type SharedLoad = Shared<BoxFuture<'static, Result<Arc<Loaded>, GraphQLError>>>;
+
+/// Row of list, parent value of column and relation fields
+struct Node {
+    batch: Arc<Batch>,
+    index: usize,
+}
+
+impl Batch {
+    fn nodes(rows: Vec<Row>) -> impl Iterator<Item = FieldValue<'static>> {
+        let batch = Arc::new(Batch { rows, loads: Mutex::new(HashMap::new()) });
+        (0..batch.rows.len()).map(move |index| FieldValue::owned_any(Node { batch: batch.clone(), index }))
+    }
+
+    /// load of relation field started by first row, awaited by all rows of batch
+    fn load(&self, field: &str, start: impl FnOnce() -> BoxFuture<'static, Result<Arc<Loaded>, GraphQLError>>) -> SharedLoad {
+        self.loads
+            .lock()
+            .unwrap()
+            .entry(field.to_string())
+            .or_insert_with(|| start().shared())
+            .clone()
+    }
+}
+
+impl Node {
+    fn row(&self) -> &Row {
+        &self.batch.rows[self.index]
+    }
+}
+
+/// tables become object types with columns and relations by foreign keys, none without exposed tables
+fn build_schema(metainfo: &MetaInfo) -> Result<Option<Schema>, async_graphql::dynamic::SchemaError> {
+    let mut targets = Vec::new();
+    let mut type_names = HashSet::new();
+
+    let mut schema_names: Vec<&String> = metainfo.schema_names().collect();
+    schema_names.sort();
+    for schema_name in schema_names {
+        let schema = match metainfo.find_schema(schema_name) {
+            Some(schema) => schema,
+            None => continue
+        };
+        let mut entities: Vec<_> = schema.entities_iter().collect();
+        entities.sort_by(|a, b| a.0.cmp(b.0));
+
+        for (entity_name, entity) in entities {
+            // object types without fields are not allowed
+            if entity.columns.is_empty() {
+                continue;
+            }
+            let type_name = graphql_name(&format!("{}_{}", schema_name, entity_name));
+            let type_name = unique_name(&mut type_names, type_name.clone(), || type_name);
+
+            let mut field_names = HashSet::new();
+            let columns = entity.columns
+                .iter()
+                .enumerate()
+                .map(|(i, c)| {
+                    let name = graphql_name(&c.name);
+                    (unique_name(&mut field_names, name.clone(), || name), i)
+                })
+                .collect();
+
+            targets.push(Target {
+                datasource: schema.datasource().to_string(),
+                schema_name: schema_name.clone(),
+                entity_name: entity_name.clone(),
+                entity: entity.clone(),
+                type_name,
+                columns
+            });
+        }
+    }
+
+    if targets.is_empty() {
+        return Ok(None);
+    }
+
+    let relations = find_relations(&targets);
+    let targets: Vec<Arc<Target>> = targets.into_iter().map(Arc::new).collect();
+
+    let mut builder = Schema::build(QUERY_TYPE, None, None)
+        .register(Scalar::new(LONG_SCALAR).description("64-bit integer"))
+        .register(Scalar::new(DATETIME_SCALAR).description("date and time in RFC 3339 format"))
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY);
+
+    let mut query = Object::new(QUERY_TYPE);
+
+    for (index, target) in targets.iter().enumerate() {
+        let mut object = Object::new(target.type_name.as_str());
+        if let Some(comment) = &target.entity.comment {
+            object = object.description(comment);
+        }
+
+        let mut filter = InputObject::new(filter_type(target));
+        for (field_name, i) in target.columns.iter() {
+            let column = &target.entity.columns[*i];
+            let column_name = column.name.clone();
+            let mut field = Field::new(field_name.as_str(), column_type(column), move |ctx| {
+                let value = ctx.parent_value
+                    .downcast_ref::<Node>()
+                    .and_then(|node| node.row().get(&column_name))
+                    .filter(|v| !v.is_null())
+                    .and_then(|v| Value::from_json(v.clone()).ok());
+                FieldFuture::from_value(value)
+            });
+            if let Some(comment) = &column.comment {
+                field = field.description(comment);
+            }
+            object = object.field(field);
+            filter = filter.field(InputValue::new(field_name.as_str(), TypeRef::named(TypeRef::STRING)));
+        }
+
+        for relation in relations[index].iter() {
+            object = object.field(relation_field(relation, targets[relation.target].clone()));
+        }
+
+        let root_target = target.clone();
+        let root_field = Field::new(target.type_name.as_str(), TypeRef::named_nn_list_nn(target.type_name.as_str()), move |ctx| {
+            let target = root_target.clone();
+            FieldFuture::new(async move {
+                let (filter, order, limit, offset) = list_arguments(&target, &ctx.args)?;
+                let context = ctx.data::<RequestContext>()?;
+                let query = v1query::DynamicQuery::create_from_params(
+                    &target.schema_name, &target.entity_name, target.entity.clone(), filter.clone(), order, limit, offset,
+                    context.max_scan_rows
+                ).map_err(graphql_error)?;
+                let rows = fetch(context, &target, query, filter).await?;
+                Ok(Some(FieldValue::list(Batch::nodes(rows))))
+            })
+        });
+        query = query.field(list_arguments_of(root_field, target));
+
+        builder = builder.register(object).register(filter);
+    }
+
+    builder.register(query).finish().map(Some)
+}
+
+fn filter_type(target: &Target) -> String {
+    format!("{}_filter", target.type_name)
+}
+
+fn list_arguments_of(field: Field, target: &Target) -> Field {
+    field
+        .argument(InputValue::new("filter", TypeRef::named(filter_type(target))))
+        .argument(InputValue::new("order", TypeRef::named_nn_list(TypeRef::STRING)))
+        .argument(InputValue::new("limit", TypeRef::named(TypeRef::INT)))
+        .argument(InputValue::new("offset", TypeRef::named(TypeRef::INT)))
+}
+
+/// relations in both directions for every foreign key, when referenced entity is available
+fn find_relations(targets: &[Target]) -> Vec<Vec<Relation>> {
+    let positions: HashMap<(&str, &str), usize> = targets
+        .iter()
+        .enumerate()
+        .map(|(i, t)| ((t.schema_name.as_str(), t.entity_name.as_str()), i))
+        .collect();
+
+    let mut used: Vec<HashSet<String>> = targets
+        .iter()
+        .map(|t| t.columns.iter().map(|(name, _)| name.clone()).collect())
+        .collect();
+    let mut relations: Vec<Vec<Relation>> = targets.iter().map(|_| Vec::new()).collect();
+
+    for (index, target) in targets.iter().enumerate() {
+        for fk in target.entity.foreign_keys.iter() {
+            let parent = match positions.get(&(fk.ref_schema.as_str(), fk.ref_entity.as_str())) {
+                Some(parent) => *parent,
+                None => continue
+            };
+            // referenced columns could be hidden by expose rules
+            let parent_columns = &targets[parent].entity.columns;
+            if !fk.ref_columns.iter().all(|name| parent_columns.iter().any(|c| &c.name == name)) {
+                continue;
+            }
+            let fk_columns: Vec<String> = fk.columns.iter().map(|i| target.entity.columns[*i].name.clone()).collect();
+
+            // referenced entity: consumer.client
+            let name = relation_name(&targets[parent], &target.schema_name);
+            let field = unique_name(&mut used[index], name.clone(), || format!("{}_by_{}", name, graphql_name(&fk_columns[0])));
+            let columns = fk_columns.iter().cloned().zip(fk.ref_columns.iter().cloned()).collect();
+            relations[index].push(Relation { field, target: parent, columns, many: false });
+
+            // referencing entities: client.consumer
+            let name = relation_name(target, &fk.ref_schema);
+            let field = unique_name(&mut used[parent], name.clone(), || format!("{}_by_{}", name, graphql_name(&fk_columns[0])));
+            let columns = fk.ref_columns.iter().cloned().zip(fk_columns.iter().cloned()).collect();
+            relations[parent].push(Relation { field, target: index, columns, many: true });
+        }
+    }
+    relations
+}
+
+/// name of related entity, qualified by schema for other schemas
+fn relation_name(target: &Target, schema_name: &str) -> String {
+    if target.schema_name == schema_name {
+        graphql_name(&target.entity_name)
+    } else {
+        target.type_name.clone()
+    }
+}
+
+fn relation_field(relation: &Relation, target: Arc<Target>) -> Field {
+    let columns = relation.columns.clone();
+    let many = relation.many;
+    let ty = if many {
+        TypeRef::named_nn_list_nn(target.type_name.as_str())
+    } else {
+        TypeRef::named(target.type_name.as_str())
+    };
+
+    let field_target = target.clone();
+    let field = Field::new(relation.field.as_str(), ty, move |ctx| {
+        let target = field_target.clone();
+        let columns = columns.clone();
+        FieldFuture::new(async move {
+            let node = ctx.parent_value.try_downcast_ref::<Node>()?;
+            let key = match row_key(node.row(), columns.iter().map(|(column, _)| column)) {
+                Some(key) => key,
+                None if many => return Ok(Some(FieldValue::list(Vec::<FieldValue>::new()))),
+                None => return Ok(None)
+            };
+
+            let (filter, order, limit, offset) = if many {
+                list_arguments(&target, &ctx.args)?
+            } else {
+                (HashMap::new(), Vec::new(), Some(1), None)
+            };
+            let context = ctx.data::<RequestContext>()?.clone();
+            let batch = node.batch.clone();
+            let field = ctx.ctx.item.node.response_key().node.to_string();
+
+            let load = node.batch.load(&field, move || async move {
+                // keys of all rows in batch, rows without key have no related entities
+                let keys: Vec<Vec<String>> = batch.rows
+                    .iter()
+                    .filter_map(|row| row_key(row, columns.iter().map(|(column, _)| column)))
+                    .collect::<BTreeSet<_>>()
+                    .into_iter()
+                    .collect();
+                let key_columns: Vec<String> = columns.iter().map(|(_, target_column)| target_column.clone()).collect();
+
+                let mut rows = Vec::new();
+                for keys in keys.chunks(MAX_KEYS) {
+                    let mut event_filter = filter.clone();
+                    for (i, column) in key_columns.iter().enumerate() {
+                        event_filter.insert(column.clone(), keys.iter().map(|key| key[i].as_str()).collect::<Vec<_>>().join(","));
+                    }
+                    let query = v1query::DynamicQuery::create_from_keys(
+                        &target.schema_name, &target.entity_name, target.entity.clone(), filter.clone(),
+                        key_columns.clone(), keys.to_vec(), order.clone(), limit, offset, context.max_scan_rows
+                    ).map_err(graphql_error)?;
+                    rows.extend(fetch(&context, &target, query, event_filter).await?);
+                }
+
+                let mut by_key: HashMap<Vec<String>, Vec<usize>> = HashMap::new();
+                for (index, row) in rows.iter().enumerate() {
+                    if let Some(key) = row_key(row, key_columns.iter()) {
+                        by_key.entry(key).or_default().push(index);
+                    }
+                }
+                let batch = Arc::new(Batch { rows, loads: Mutex::new(HashMap::new()) });
+                Ok(Arc::new(Loaded { batch, by_key }))
+            }.boxed());
+
+            let loaded = load.await?;
+            let mut nodes = loaded.by_key
+                .get(&key)
+                .into_iter()
+                .flatten()
+                .map(|index| FieldValue::owned_any(Node { batch: loaded.batch.clone(), index: *index }));
+            if many {
+                Ok(Some(FieldValue::list(nodes)))
+            } else {
+                Ok(nodes.next())
+            }
+        })
+    });
+
+    if many { list_arguments_of(field, &target) } else { field }
+}
+
+/// values of key columns in row, in order of key; null keys have no related entities
+fn row_key<'a>(row: &Row, columns: impl Iterator<Item = &'a String>) -> Option<Vec<String>> {
+    columns
+        .map(|column| row.get(column).and_then(parameter_value))
+        .collect()
+}
+
+/// value of key column as parameter of query, null keys have no related entities
+fn parameter_value(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        _ => None
+    }
+}
+
+type ListArguments = (HashMap<String,String>, Vec<String>, Option<u32>, Option<u32>);
+
+/// filter, order, limit and offset of list field, with names of columns
+fn list_arguments(target: &Target, args: &ObjectAccessor) -> Result<ListArguments, GraphQLError> {
+    let mut filter = HashMap::new();
+    if let Some(value) = args.get("filter") {
+        if !value.is_null() {
+            for (field_name, value) in value.object()?.iter() {
+                if value.is_null() {
+                    continue;
+                }
+                let column = target.column_name(field_name.as_str())
+                    .ok_or_else(|| GraphQLError::new(format!("Not found column {}", field_name)))?;
+                filter.insert(column.to_string(), value.string()?.to_string());
+            }
+        }
+    }
+
+    let mut order = Vec::new();
+    if let Some(value) = args.get("order") {
+        if !value.is_null() {
+            for field_name in value.list()?.iter() {
+                let field_name = field_name.string()?;
+                let column = target.column_name(field_name)
+                    .ok_or_else(|| GraphQLError::new(format!("Order column {} not found", field_name)))?;
+                order.push(column.to_string());
+            }
+        }
+    }
+
+    let number = |name: &str| -> Result<Option<u32>, GraphQLError> {
+        match args.get(name) {
+            Some(value) if !value.is_null() => {
+                let value = value.i64()?;
+                if value < 0 {
+                    return Err(GraphQLError::new(format!("{} must be >= 0", name)));
+                }
+                Ok(Some(value as u32))
+            },
+            _ => Ok(None)
+        }
+    };
+
+    Ok((filter, order, number("limit")?, number("offset")?))
+}
+
+/// error of resolver with stable code of error in extensions, as in problem details of v1 api
+fn graphql_error(err: ApiError) -> GraphQLError {
+    GraphQLError::new(err.to_string()).extend_with(|_, extensions| extensions.set("code", err.code()))
+}
+
+/// query rows through the same sql generation as v1 api
+async fn fetch(
+    context: &RequestContext,
+    target: &Target,
+    query: v1query::DynamicQuery,
+    filter: HashMap<String,String>
+) -> Result<Vec<Row>, GraphQLError> {
+    let mut event = AuditEvent::new(context.user_id, &context.endpoint, AuditAction::Read, &target.schema_name, &target.entity_name);
+    event.filter = Some(filter);
+
+    let backend = context.backend.clone();
+    let datasource = target.datasource.clone();
+    let trace = context.trace.clone();
+    let cancellation = context.cancellation.clone();
+    let result = web::block(move || query.fetch_many(backend.as_ref(), &datasource, &trace, &cancellation))
+        .await
+        .map_err(|err| graphql_error(err.into()))?;
+
+    let rows: Vec<Row> = serde_json::from_str(&result.json)
+        .map_err(|err| GraphQLError::new(format!("Can not parse query result: {}", err)))?;
+
+    event.rows = result.rows;
+    context.audit.record(event).await.map_err(graphql_error)?;
+    context.metrics.record_result(&target.schema_name, &target.entity_name, result.rows, result.json.len());
+    Ok(rows)
+}
@@ -0,0 +1,157 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use actix_web::{get, web, HttpResponse, Responder, ResponseError};
+use openssl::asn1::Asn1Time;
+use openssl::x509::X509;
+use serde::Serialize;
+
+use crate::server::ApiError;
+use super::ApplicationState;
+
+// probe should not wait for unavailable database longer than this
+const PING_TIMEOUT: Duration = Duration::from_secs(2);
+// certificate must be renewed before this count of days until expiry
+const CERT_EXPIRY_DAYS: i32 = 14;
+
+/// Result of readiness checks
+#[derive(Serialize)]
+struct Readiness {
+    status: &'static str,
+    checks: Vec<Check>,
+}
+
+/// Result of one check, message describes state or failure
+#[derive(Serialize)]
+struct Check {
+    name:       String,
+    ok:         bool,
+    latency_ms: f64,
+    message:    String,
+}
+
+/// measure latency of check
+fn check<F: FnOnce() -> Result<String, String>>(name: String, check: F) -> Check {
+    let started = Instant::now();
+    let result = check();
+    let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+    match result {
+        Ok(message) => Check { name, ok: true, latency_ms, message },
+        Err(message) => Check { name, ok: false, latency_ms, message },
+    }
+}
+
+/// pools and round trips of all datasources, metainfo and certificate of listener
+fn readiness(state: &ApplicationState) -> Readiness {
+    let backend = state.backend();
+    let mut checks = Vec::new();
+
+    for datasource in backend.datasources() {
+        checks.push(check(format!("pool:{}", datasource), || {
+            let pool = backend.pool_state(&datasource).map_err(|err| err.to_string())?;
+            if pool.is_exhausted() {
+                return Err(format!("All {} connections are in use", pool.max_size));
+            }
+            Ok(format!("{} of {} connections, {} idle", pool.connections, pool.max_size, pool.idle))
+        }));
+        checks.push(check(format!("database:{}", datasource), || {
+            backend.ping(&datasource, PING_TIMEOUT)
+                .map(|_| "SELECT 1 FROM DUAL".to_string())
+                .map_err(|err| err.to_string())
+        }));
+    }
+
+    checks.push(check("metainfo".to_string(), || metainfo_status(state)));
+
+    if let Some(path) = &state.tls_cert {
+        checks.push(check("tls".to_string(), || certificate_expiry(path)));
+    }
+
+    let status = if checks.iter().all(|c| c.ok) { "ready" } else { "degraded" };
+    Readiness { status, checks }
+}
+
+/// metainfo from snapshot is ready only after revalidation against database
+fn metainfo_status(state: &ApplicationState) -> Result<String, String> {
+    if let Some(err) = state.reload_error.read().unwrap().as_ref() {
+        return Err(format!("Last reload failed: {}", err));
+    }
+    if !state.validated.load(Ordering::Acquire) {
+        return Err("Loaded from snapshot, not revalidated against database".to_string());
+    }
+    let summary = state.metainfo().summary();
+    Ok(format!("{} schemas, {} entities", summary.schemas, summary.entities))
+}
+
+/// first certificate of chain must not expire soon
+fn certificate_expiry(path: &Path) -> Result<String, String> {
+    let pem = fs::read(path)
+        .map_err(|err| format!("Can not read certificate {:?}: {}", path, err))?;
+    let cert = X509::from_pem(&pem)
+        .map_err(|err| format!("Can not parse certificate {:?}: {}", path, err))?;
+    expiry_status(&cert)
+}
+
+fn expiry_status(cert: &X509) -> Result<String, String> {
+    let days = Asn1Time::days_from_now(0)
+        .and_then(|now| now.diff(cert.not_after()))
+        .map_err(|err| format!("Can not compare expiry of certificate: {}", err))?
+        .days;
+
+    match days {
+        d if d < 0 => Err(format!("Certificate expired {} days ago", -d)),
+        d if d < CERT_EXPIRY_DAYS => Err(format!("Certificate expires in {} days", d)),
+        d => Ok(format!("Certificate expires in {} days", d)),
+    }
+}
+
+#[get("/health")]
+async fn health() -> impl Responder {
+    "OK".to_string()
+}
+
+/// process is alive, without checks of dependencies
+#[get("/health/live")]
+async fn live() -> impl Responder {
+    "OK".to_string()
+}
+
+/// 503 when any check fails, breakdown of checks in both cases
+#[get("/health/ready")]
+async fn ready(data: web::Data<Arc<ApplicationState>>) -> HttpResponse {
+    let state = data.get_ref().clone();
+    match web::block(move || Ok::<_, ()>(readiness(&state))).await {
+        Ok(readiness) if readiness.status == "ready" => HttpResponse::Ok().json(readiness),
+        Ok(readiness) => HttpResponse::ServiceUnavailable().json(readiness),
+        Err(err) => ApiError::Internal(format!("Can not check readiness: {}", err)).error_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::PKey;
+    use openssl::rsa::Rsa;
+
+    use super::*;
+
+    fn certificate(days: u32) -> X509 {
+        let key = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let mut builder = X509::builder().unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        builder.set_not_after(&Asn1Time::days_from_now(days).unwrap()).unwrap();
+        builder.sign(&key, MessageDigest::sha256()).unwrap();
+        builder.build()
+    }
+
+    #[test]
+    fn certificate_near_expiry_is_not_ready() {
+        assert!(expiry_status(&certificate(365)).is_ok());
+        assert!(expiry_status(&certificate(3)).unwrap_err().starts_with("Certificate expires in"));
+        assert!(certificate_expiry(Path::new("/nonexistent/cert.pem")).is_err());
+    }
+}
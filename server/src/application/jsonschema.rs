@@ -0,0 +1,225 @@
+use serde_json::{json, Map, Value};
+
+use crate::metainfo::{Column, ColumnType, Entity, Schema};
+
+const JSON_SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+// larger numbers can not be represented exactly in JSON
+const MAX_EXACT_PRECISION: u8 = 15;
+
+/// JSON Schema of entity for form builders: constraints of columns, required are not null columns
+pub fn entity_json_schema(schema_name: &str, entity_name: &str, entity: &Entity) -> Value {
+    let properties: Map<String, Value> = entity.columns
+        .iter()
+        .map(|c| (c.name.clone(), column_schema(c)))
+        .collect();
+    let required: Vec<&str> = entity.columns
+        .iter()
+        .filter(|c| !c.nullable)
+        .map(|c| c.name.as_str())
+        .collect();
+
+    let mut schema = Map::new();
+    schema.insert("$schema".to_string(), json!(JSON_SCHEMA_DIALECT));
+    schema.insert("title".to_string(), json!(format!("{}.{}", schema_name, entity_name)));
+    if let Some(comment) = &entity.comment {
+        schema.insert("description".to_string(), json!(comment));
+    }
+    schema.insert("type".to_string(), json!("object"));
+    schema.insert("properties".to_string(), Value::Object(properties));
+    schema.insert("required".to_string(), json!(required));
+    Value::Object(schema)
+}
+
+/// JSON Schema of column value with size and precision of oracle type
+pub fn column_schema(column: &Column) -> Value {
+    let json_type = match column.col_type {
+        ColumnType::Integer  => "integer",
+        ColumnType::Number   => "number",
+        // values of unsupported columns are rendered as "not-implemented"
+        ColumnType::String | ColumnType::DateTime | ColumnType::Unsupported => "string",
+    };
+
+    let mut schema = Map::new();
+    if column.nullable {
+        schema.insert("type".to_string(), json!([json_type, "null"]));
+    } else {
+        schema.insert("type".to_string(), json!(json_type));
+    }
+
+    match column.sql_type {
+        oracle::sql_type::OracleType::Varchar2(size) => {
+            schema.insert("maxLength".to_string(), json!(size));
+        },
+        oracle::sql_type::OracleType::Date => {
+            schema.insert("format".to_string(), json!("date-time"));
+        },
+        oracle::sql_type::OracleType::Number(precision, scale) if precision > 0 && precision <= MAX_EXACT_PRECISION => {
+            let scale = scale.max(0) as i32;
+            let step = 10f64.powi(-scale);
+            let maximum = 10f64.powi(precision as i32 - scale) - step;
+            if scale > 0 {
+                schema.insert("multipleOf".to_string(), json!(step));
+            }
+            schema.insert("minimum".to_string(), json!(-maximum));
+            schema.insert("maximum".to_string(), json!(maximum));
+        },
+        _ => {}
+    }
+
+    if let Some(comment) = &column.comment {
+        schema.insert("description".to_string(), json!(comment));
+    }
+    Value::Object(schema)
+}
+
+/// TypeScript declarations (`.d.ts`) with interface for every entity of schema
+pub fn typescript_definitions(schema_name: &str, schema: &Schema) -> String {
+    let mut entities: Vec<_> = schema.entities_iter().collect();
+    entities.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut result = format!("// generated from metainfo of schema {}\n", schema_name);
+    for (entity_name, entity) in entities {
+        result.push('\n');
+        if let Some(comment) = &entity.comment {
+            result.push_str(&format!("/** {} */\n", doc_comment(comment)));
+        }
+        result.push_str(&format!("export interface {} {{\n", interface_name(entity_name)));
+        for column in entity.columns.iter() {
+            if let Some(comment) = &column.comment {
+                result.push_str(&format!("    /** {} */\n", doc_comment(comment)));
+            }
+            let ts_type = match column.col_type {
+                ColumnType::Integer | ColumnType::Number => "number",
+                ColumnType::String | ColumnType::DateTime | ColumnType::Unsupported => "string",
+            };
+            let nullable = if column.nullable { " | null" } else { "" };
+            result.push_str(&format!("    {}: {}{};\n", property_name(&column.name), ts_type, nullable));
+        }
+        result.push_str("}\n");
+    }
+    result
+}
+
+/// `client_consumers` => `ClientConsumers`
+fn interface_name(entity_name: &str) -> String {
+    let name: String = entity_name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new()
+            }
+        })
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("T{}", name)
+    } else {
+        name
+    }
+}
+
+/// column names with `#` are not valid identifiers
+fn property_name(column_name: &str) -> String {
+    let valid = column_name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && column_name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+    if valid {
+        column_name.to_string()
+    } else {
+        format!("\"{}\"", column_name)
+    }
+}
+
+fn doc_comment(comment: &str) -> String {
+    comment.replace("*/", "* /").replace('\n', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use oracle::sql_type::OracleType;
+
+    use super::*;
+    use crate::backend::{Backend, MemoryBackend};
+    use crate::metainfo::Rules;
+
+    fn column(name: &str, col_type: ColumnType, sql_type: OracleType, nullable: bool) -> Column {
+        Column { name: name.to_string(), col_type, sql_type, col_size: 0, nullable, comment: None, statistics: None }
+    }
+
+    #[test]
+    fn nullable_varchar_has_max_length() {
+        let mut name = column("name", ColumnType::String, OracleType::Varchar2(40), true);
+        name.comment = Some("Name of person".to_string());
+        assert_eq!(
+            column_schema(&name),
+            json!({ "type": ["string", "null"], "maxLength": 40, "description": "Name of person" })
+        );
+    }
+
+    #[test]
+    fn number_has_range_of_precision_and_scale() {
+        let salary = column("salary", ColumnType::Number, OracleType::Number(8, 2), false);
+        let schema = column_schema(&salary);
+        assert_eq!(schema["type"], json!("number"));
+        assert_eq!(schema["multipleOf"], json!(0.01));
+        assert!((schema["maximum"].as_f64().unwrap() - 999_999.99).abs() < 1e-6);
+        assert!((schema["minimum"].as_f64().unwrap() + 999_999.99).abs() < 1e-6);
+
+        let id = column("id", ColumnType::Integer, OracleType::Number(4, 0), false);
+        assert_eq!(column_schema(&id), json!({ "type": "integer", "minimum": -9999.0, "maximum": 9999.0 }));
+
+        // precision of NUMBER without precision is unknown, large precision is not exact in JSON
+        let any = column("any", ColumnType::Number, OracleType::Number(0, 0), false);
+        assert_eq!(column_schema(&any), json!({ "type": "number" }));
+        let large = column("large", ColumnType::Number, OracleType::Number(20, 0), false);
+        assert_eq!(column_schema(&large), json!({ "type": "number" }));
+    }
+
+    #[test]
+    fn date_is_date_time_string() {
+        let hired = column("hired", ColumnType::DateTime, OracleType::Date, true);
+        assert_eq!(column_schema(&hired), json!({ "type": ["string", "null"], "format": "date-time" }));
+    }
+
+    #[test]
+    fn entity_requires_not_null_columns() {
+        let metainfo = MemoryBackend::fixture().load_metainfo(&Rules::default()).unwrap();
+        let employees = metainfo.find_schema("hr").and_then(|s| s.find_entity("employees")).unwrap();
+        let schema = entity_json_schema("hr", "employees", employees);
+        assert_eq!(schema["title"], json!("hr.employees"));
+        assert_eq!(schema["description"], json!("Employees"));
+        assert_eq!(schema["required"], json!(["id", "name"]));
+        assert_eq!(schema["properties"]["hired"]["format"], json!("date-time"));
+    }
+
+    #[test]
+    fn typescript_interfaces_of_entities() {
+        let metainfo = MemoryBackend::fixture().load_metainfo(&Rules::default()).unwrap();
+        let definitions = typescript_definitions("hr", metainfo.find_schema("hr").unwrap());
+        let expected = "\
+/** Employees */
+export interface Employees {
+    id: number;
+    name: string;
+    /** Monthly salary */
+    salary: number | null;
+    department_id: number | null;
+    hired: string | null;
+}
+";
+        assert!(definitions.starts_with("// generated from metainfo of schema hr\n"));
+        assert!(definitions.contains(expected), "{}", definitions);
+        assert!(definitions.contains("export interface EmployeesV {"));
+    }
+
+    #[test]
+    fn names_are_valid_identifiers() {
+        assert_eq!(interface_name("client_consumers"), "ClientConsumers");
+        assert_eq!(interface_name("2020_sales"), "T2020Sales");
+        assert_eq!(property_name("order#"), "\"order#\"");
+        assert_eq!(property_name("amount$"), "amount$");
+        assert_eq!(doc_comment("end */ of\ncomment"), "end * / of comment");
+    }
+}
@@ -3,12 +3,15 @@ use std::iter::FromIterator;
 use std::sync::Arc;
 
 use actix_web::dev::HttpServiceFactory;
-use actix_web::{get, web, HttpResponse, Responder};
+use actix_web::{get, post, web, HttpRequest, HttpResponse, Responder};
+use actix_web::error::BlockingError;
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-use crate::metainfo::{ColumnType,EntityType};
+use crate::metainfo::{self, ColumnStatistics, ColumnType, EntityType, TableStatistics};
+use crate::server::ApiError;
 use super::ApplicationState;
+use super::jsonschema;
 
 // https://github.com/foundation-rs/backend/blob/master/server/src/application/mgmt_scope.rs
 
@@ -19,6 +22,23 @@ pub fn metainfo_scope() -> impl HttpServiceFactory {
         .service(schemas_metainfo)
         .service(tables_metainfo)
         .service(table_metainfo)
+        .service(table_json_schema)
+}
+
+// search in names and comments of entities and columns
+pub fn search_resource() -> impl HttpServiceFactory {
+    web::resource("/search")
+        .wrap(crate::security::Authorized::developers())
+        .route(web::get().to(search_metainfo))
+}
+
+// group of endpoints for management of metainfo
+pub fn metainfo_mgmt_scope() -> impl HttpServiceFactory {
+    web::scope("/metainfo")
+        .wrap(crate::security::Authorized::developers())
+        .service(reload_metainfo)
+        .service(metainfo_changes)
+        .service(metainfo_diff)
 }
 
 #[derive(Serialize)]
@@ -45,6 +65,9 @@ struct TableMetaInfo<'a> {
     #[serde(alias="type")]
     entity_type: EntityType,
     has_pk:    bool,
+    num_rows:  Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    statistics: Option<&'a TableStatistics>,
     columns:   Vec<ColumnMetaInfo<'a>>
 }
 
@@ -54,12 +77,14 @@ pub struct ColumnMetaInfo<'a> {
     #[serde(alias="type")]
     pub col_type: ColumnType,
     pub is_pk:    bool,
-    pub nullable: bool
+    pub nullable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub statistics: Option<&'a ColumnStatistics>,
 }
 
 #[get("/")]
 async fn schemas_metainfo(data: web::Data<Arc<ApplicationState>>) -> impl Responder {
-    let metainfo = data.metainfo.read().unwrap();
+    let metainfo = data.metainfo();
     let mut schemas: Vec<&str> = metainfo.schema_names().map(|s|s.as_str()).collect();
     schemas.sort();
     let response = DatabaseMetainfo { schemas };
@@ -67,9 +92,9 @@ async fn schemas_metainfo(data: web::Data<Arc<ApplicationState>>) -> impl Respon
 }
 
 #[get("/{schema}")]
-async fn tables_metainfo(path: web::Path<(String,)>, data: web::Data<Arc<ApplicationState>>) -> impl Responder {
+async fn tables_metainfo(req: HttpRequest, path: web::Path<(String,)>, data: web::Data<Arc<ApplicationState>>) -> impl Responder {
     let schema_name = path.into_inner().0;
-    let metainfo = data.metainfo.read().unwrap();
+    let metainfo = data.metainfo();
 
     // match metainfo.schemas.get(schema_name.as_str()) {
     match metainfo.find_schema(&schema_name) {
@@ -84,17 +109,18 @@ async fn tables_metainfo(path: web::Path<(String,)>, data: web::Data<Arc<Applica
 
             HttpResponse::Ok().json(SchemaMetainfo { tables })
         },
-        None => HttpResponse::NotFound().finish()
+        None => ApiError::NotFound(format!("Schema {} is not found", schema_name)).response(&req)
     }
 }
 
 #[get("/{schema}/{table}")]
-async fn table_metainfo(path: web::Path<(String,String)>, data: web::Data<Arc<ApplicationState>>) -> impl Responder {
+async fn table_metainfo(req: HttpRequest, path: web::Path<(String,String)>, data: web::Data<Arc<ApplicationState>>) -> impl Responder {
     let (schema_name,table_name) = path.into_inner();
-    let metainfo = data.metainfo.read().unwrap();
+    let metainfo = data.metainfo();
 
     if let Some(info) = metainfo.find_schema(&schema_name) {
         if let Some(info) = info.find_entity(&table_name) {
+            crate::metrics::label_entity(&req, &schema_name, &table_name);
             let pk_indices = match &info.primary_key {
                 Some(pk) => {
                     HashSet::from_iter(pk)
@@ -113,18 +139,110 @@ async fn table_metainfo(path: web::Path<(String,String)>, data: web::Data<Arc<Ap
                         name: c.name.as_str(), 
                         col_type: c.col_type, 
                         is_pk, 
-                        nullable: c.nullable}
+                        nullable: c.nullable,
+                        statistics: c.statistics.as_ref()}
                 }).collect();
 
             let response = TableMetaInfo {
                 name: &table_name,
                 entity_type: info.entity_type,
                 has_pk: pk_indices.len() > 0,
+                num_rows: info.num_rows,
+                statistics: info.statistics.as_ref(),
                 columns
             };
             return HttpResponse::Ok().json(response)
         }
     };
 
-    HttpResponse::NotFound().finish()
-}
\ No newline at end of file
+    ApiError::NotFound(format!("Table {}.{} is not found", schema_name, table_name)).response(&req)
+}
+
+#[get("/{schema}/{table}/jsonschema")]
+async fn table_json_schema(req: HttpRequest, path: web::Path<(String,String)>, data: web::Data<Arc<ApplicationState>>) -> impl Responder {
+    let (schema_name, table_name) = path.into_inner();
+    let metainfo = data.metainfo();
+
+    match metainfo.find_schema(&schema_name).and_then(|s| s.find_entity(&table_name)) {
+        Some(info) => {
+            crate::metrics::label_entity(&req, &schema_name, &table_name);
+            HttpResponse::Ok()
+                .content_type("application/schema+json")
+                .json(jsonschema::entity_json_schema(&schema_name, &table_name, info))
+        },
+        None => ApiError::NotFound(format!("Table {}.{} is not found", schema_name, table_name)).response(&req)
+    }
+}
+
+#[derive(Deserialize)]
+struct ReloadParams {
+    full: Option<bool>, // reload all objects, not only changed
+}
+
+#[post("/reload")]
+async fn reload_metainfo(req: HttpRequest, params: web::Query<ReloadParams>, data: web::Data<Arc<ApplicationState>>) -> impl Responder {
+    let state = data.get_ref().clone();
+    let full = params.full.unwrap_or(false);
+    let result = web::block(move || state.reload_metainfo(full)).await;
+    match result {
+        Ok(Some(reload)) => HttpResponse::Ok().json(reload),
+        Ok(None) => {
+            let err = ApiError::Conflict { code: "reload_in_progress", detail: "Reload of metainfo already in progress".to_string() };
+            err.response(&req)
+        },
+        Err(BlockingError::Error(e)) => data.error_response(&req, e.into()),
+        Err(BlockingError::Canceled) => data.error_response(&req, ApiError::Internal("Reload of metainfo was canceled".to_string()))
+    }
+}
+
+#[get("/changes")]
+async fn metainfo_changes(data: web::Data<Arc<ApplicationState>>) -> impl Responder {
+    HttpResponse::Ok().json(data.metainfo_changes())
+}
+
+#[derive(Deserialize)]
+struct DiffParams {
+    against: String,        // name of snapshot in snapshots directory
+    format:  Option<String>, // "json" (default) or "text"
+}
+
+#[get("/diff")]
+async fn metainfo_diff(req: HttpRequest, params: web::Query<DiffParams>, data: web::Data<Arc<ApplicationState>>) -> impl Responder {
+    let state = data.get_ref().clone();
+    let params = params.into_inner();
+    let against = params.against.clone();
+    let result = web::block(move || state.diff_metainfo(&against)).await;
+    match result {
+        Ok(diff) => {
+            if params.format.as_deref() == Some("text") {
+                HttpResponse::Ok().content_type("text/plain; charset=utf-8").body(diff.to_string())
+            } else {
+                HttpResponse::Ok().json(diff)
+            }
+        },
+        Err(BlockingError::Error(e)) => data.error_response(&req, e),
+        Err(BlockingError::Canceled) => data.error_response(&req, ApiError::Internal("Diff of metainfo was canceled".to_string()))
+    }
+}
+
+const DEFAULT_SEARCH_LIMIT: usize = 50;
+const MAX_SEARCH_LIMIT: usize = 500;
+
+#[derive(Deserialize)]
+struct SearchParams {
+    q:     String,
+    limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct SearchResponse<'a> {
+    query:   &'a str,
+    results: Vec<metainfo::SearchResult>,
+}
+
+async fn search_metainfo(params: web::Query<SearchParams>, data: web::Data<Arc<ApplicationState>>) -> impl Responder {
+    let metainfo = data.metainfo();
+    let limit = params.limit.unwrap_or(DEFAULT_SEARCH_LIMIT).min(MAX_SEARCH_LIMIT);
+    let results = metainfo::search(&metainfo, &params.q, limit);
+    HttpResponse::Ok().json(SearchResponse { query: &params.q, results })
+}
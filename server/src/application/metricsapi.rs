@@ -0,0 +1,27 @@
+use std::sync::Arc;
+
+use actix_web::dev::HttpServiceFactory;
+use actix_web::{web, HttpResponse};
+
+use super::ApplicationState;
+
+// text format of prometheus exposition
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+// metrics for prometheus, scraper authenticates as developer
+pub fn metrics_resource() -> impl HttpServiceFactory {
+    web::resource("/metrics")
+        .wrap(crate::security::Authorized::developers())
+        .route(web::get().to(metrics))
+}
+
+async fn metrics(data: web::Data<Arc<ApplicationState>>) -> HttpResponse {
+    let backend = data.backend();
+    let pools: Vec<_> = backend.datasources()
+        .into_iter()
+        .filter_map(|name| backend.pool_state(&name).ok().map(|state| (name, state)))
+        .collect();
+    HttpResponse::Ok()
+        .content_type(CONTENT_TYPE)
+        .body(data.metrics().render(&pools))
+}
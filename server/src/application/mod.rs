@@ -1,34 +1,355 @@
+mod graphql;
+mod health;
+mod jsonschema;
 mod metaapi;
+mod metricsapi;
+mod openapi;
 mod v1api;
 mod v1query;
 
+use std::collections::VecDeque;
+use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
 
-use actix_web::{get, web, Responder, Scope};
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web::dev::HttpServiceFactory;
 
-use crate::metainfo::{self, MetaInfo};
-use crate::server::{self, config};
+use crate::audit::AuditLog;
+use crate::backend::{Backend, BackendError, Cancellation, OracleBackend};
+use serde::Serialize;
+use slog::{error, info, o, Logger};
 
+use crate::metrics::Metrics;
+use crate::metainfo::{self, Entity, MetaInfo, MetaInfoChanges, MetaInfoDiff, MetaInfoSummary};
+use crate::security::{RateLimit, SecurityContext};
+use crate::server::{self, config, ApiError};
+use crate::telemetry::Trace;
+
+pub use jsonschema::typescript_definitions;
 pub use v1api::v1_api_scope;
 
+// count of refreshes with changes, kept for changes feed
+const MAX_CHANGES: usize = 100;
+// pause between attempts to revalidate metainfo from snapshot
+const REVALIDATE_RETRY: Duration = Duration::from_secs(30);
+
 // This struct represents state
 pub struct ApplicationState {
-    metainfo: RwLock<MetaInfo>
+    metainfo:  RwLock<Arc<MetaInfo>>,
+    changes:   RwLock<VecDeque<MetaInfoChanges>>,
+    others:    Option<config::OthersConfig>,
+    rules:     metainfo::Rules,
+    snapshot:  Option<PathBuf>,
+    reloading: AtomicBool,
+    validated: AtomicBool, // metainfo is loaded from database, not only from snapshot
+    reload_error: RwLock<Option<String>>, // error of last reload, cleared by successful reload
+    tls_cert:  Option<PathBuf>, // certificate of listener, checked for expiry
+    audit:     AuditLog,
+    graphql:   graphql::GraphQLSchema,
+    backend:   Arc<dyn Backend>,
+    metrics:   Arc<Metrics>,
+    timeouts:  config::TimeoutsConfig,
+    log:       Logger,
+}
+
+/// Result of metainfo reload
+#[derive(Serialize)]
+pub struct MetaInfoReload {
+    pub summary: MetaInfoSummary,
+    pub changes: MetaInfoChanges,
+}
+
+/// Flag of reload in progress, cleared also on errors
+struct ReloadGuard<'a>(&'a AtomicBool);
+
+impl Drop for ReloadGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
 }
 
 impl ApplicationState {
-    pub fn load(config: &config::ServerConfig) -> server::SimpleResult<Arc<ApplicationState>> {
-        let metainfo = metainfo::load(&config.others)?;
-        let metainfo = RwLock::new(metainfo);
-        Ok( Arc::new(ApplicationState{metainfo}) )
+    pub fn load(config: &config::ServerConfig, datasources: Arc<server::Datasources>, log: Logger) -> server::SimpleResult<Arc<ApplicationState>> {
+        let metrics = Arc::new(Metrics::new(&config.metrics));
+        let backend: Arc<dyn Backend> = Arc::new(OracleBackend::new(datasources.clone(), metrics.clone(), log.new(o!("component" => "backend"))));
+        let others = config.others.clone();
+        let rules = metainfo::Rules::new(&others)?;
+        let snapshot = others.as_ref().and_then(|o| o.snapshot.as_ref()).map(PathBuf::from);
+
+        // start from snapshot immediately, if it exists
+        let from_snapshot = match &snapshot {
+            Some(path) if path.exists() => {
+                match metainfo::load_snapshot(path) {
+                    Ok(snapshot) => {
+                        info!(log, "metainfo loaded from snapshot"; "path" => ?path, "created" => snapshot.created.to_rfc3339());
+                        // rules could be changed after snapshot was saved
+                        let mut metainfo = snapshot.metainfo;
+                        metainfo.apply_rules(&rules);
+                        Some(metainfo)
+                    },
+                    Err(err) => {
+                        error!(log, "can not load snapshot of metainfo"; "error" => err);
+                        None
+                    }
+                }
+            },
+            _ => None
+        };
+
+        let revalidate = from_snapshot.is_some();
+        let metainfo = match from_snapshot {
+            Some(metainfo) => metainfo,
+            None => backend.load_metainfo(&rules).map_err(|err| err.to_string())?
+        };
+
+        let audit = AuditLog::start(&config.audit, &datasources, metrics.clone(), log.new(o!("component" => "audit")))?;
+        let tls_cert = Some(PathBuf::from(&config.http.tls_cert));
+        let state = ApplicationState::new(metainfo, others, rules, snapshot, tls_cert, audit, backend, metrics, config.timeouts.clone(), log);
+
+        if revalidate {
+            state.validated.store(false, Ordering::Release);
+            start_revalidation(state.clone())?;
+        } else {
+            state.save_snapshot();
+        }
+
+        let refresh_interval = config.others.as_ref().and_then(|o| o.refresh_interval);
+        if let Some(seconds) = refresh_interval {
+            start_refresh(state.clone(), Duration::from_secs(seconds))?;
+        }
+
+        Ok(state)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        metainfo: MetaInfo,
+        others:   Option<config::OthersConfig>,
+        rules:    metainfo::Rules,
+        snapshot: Option<PathBuf>,
+        tls_cert: Option<PathBuf>,
+        audit:    AuditLog,
+        backend:  Arc<dyn Backend>,
+        metrics:  Arc<Metrics>,
+        timeouts: config::TimeoutsConfig,
+        log:      Logger,
+    ) -> Arc<ApplicationState> {
+        let metainfo = RwLock::new(Arc::new(metainfo));
+        let changes = RwLock::new(VecDeque::with_capacity(MAX_CHANGES));
+        Arc::new(ApplicationState{
+            metainfo, changes, others, rules, snapshot,
+            reloading: AtomicBool::new(false),
+            validated: AtomicBool::new(true),
+            reload_error: RwLock::new(None),
+            tls_cert, audit, graphql: Default::default(), backend, metrics, timeouts, log
+        })
+    }
+
+    /// state with metainfo of backend, without snapshot, audit and background refresh
+    #[cfg(test)]
+    pub fn with_backend(backend: Arc<dyn Backend>, others: Option<config::OthersConfig>, timeouts: config::TimeoutsConfig) -> server::SimpleResult<Arc<ApplicationState>> {
+        let rules = metainfo::Rules::new(&others)?;
+        let metainfo = backend.load_metainfo(&rules).map_err(|err| err.to_string())?;
+        Ok(ApplicationState::new(metainfo, others, rules, None, None, AuditLog::disabled(), backend, Default::default(), timeouts, server::discard_logging()))
+    }
+
+    /// current snapshot of metainfo; requests in progress keep their snapshot while metainfo is reloaded
+    pub fn metainfo(&self) -> Arc<MetaInfo> {
+        self.metainfo.read().unwrap().clone()
+    }
+
+    /// load new metainfo from database and swap it in: completely or only changed objects;
+    /// returns `None` if reload is already in progress
+    pub fn reload_metainfo(&self, full: bool) -> Result<Option<MetaInfoReload>, BackendError> {
+        if self.reloading.compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire).is_err() {
+            return Ok(None);
+        }
+        // next reload starts from metainfo of this reload, after it was swapped in and changes were published
+        let _reloading = ReloadGuard(&self.reloading);
+
+        let previous = self.metainfo();
+        let result = if full {
+            self.backend.load_metainfo(&self.rules).map(|metainfo| {
+                let mut changes = MetaInfoChanges::between(&previous, &metainfo.ddl_times());
+                changes.full = true;
+                (metainfo, changes)
+            })
+        } else {
+            self.backend.refresh_metainfo(&previous, &self.rules)
+        };
+
+        *self.reload_error.write().unwrap() = result.as_ref().err().map(|err| err.to_string());
+        let (metainfo, changes) = result?;
+        self.validated.store(true, Ordering::Release);
+        let summary = metainfo.summary();
+        *self.metainfo.write().unwrap() = Arc::new(metainfo);
+
+        if full || !changes.is_empty() {
+            self.save_snapshot();
+        }
+
+        if !changes.is_empty() {
+            info!(self.log, "metainfo changed";
+                "full" => changes.full, "added" => changes.added.len(), "changed" => changes.changed.len(), "dropped" => changes.dropped.len());
+            let mut feed = self.changes.write().unwrap();
+            if feed.len() == MAX_CHANGES {
+                feed.pop_front();
+            }
+            feed.push_back(changes.clone());
+        }
+
+        Ok(Some(MetaInfoReload { summary, changes }))
+    }
+
+    /// database for queries of api
+    pub fn backend(&self) -> Arc<dyn Backend> {
+        self.backend.clone()
     }
+
+    /// logger of request with ids of request and trace, path and authenticated user
+    pub fn request_log(&self, req: &HttpRequest) -> Logger {
+        let user_id = req.extensions().get::<SecurityContext>().map(|ctx| ctx.user_id()).unwrap_or(0);
+        let trace = Trace::of(req);
+        self.log.new(o!(
+            "request_id" => trace.request_id().to_string(),
+            "trace_id" => trace.trace_id().to_string(),
+            "path" => req.path().to_string(),
+            "user_id" => user_id
+        ))
+    }
+
+    /// problem details of error for user of request, errors of server are logged
+    pub fn error_response(&self, req: &HttpRequest, err: ApiError) -> HttpResponse {
+        if err.status().is_server_error() {
+            error!(self.request_log(req), "request failed"; "code" => err.code(), "error" => err.detail(true));
+        }
+        err.response(req)
+    }
+
+    /// larger tables are not scanned without filter by indexed column
+    pub fn max_scan_rows(&self) -> u32 {
+        self.others.as_ref().and_then(|o| o.max_scan_rows).unwrap_or(v1query::DEFAULT_MAX_SCAN_ROWS)
+    }
+
+    /// timeout of statements of request: by user, by route or default
+    pub fn statement_timeout(&self, req: &HttpRequest) -> Duration {
+        let user_id = req.extensions().get::<SecurityContext>().map(|ctx| ctx.user_id().to_string());
+        let seconds = user_id.and_then(|id| self.timeouts.users.get(&id).copied())
+            .or_else(|| req.match_pattern().and_then(|route| self.timeouts.routes.get(&route).copied()))
+            .unwrap_or(self.timeouts.statement);
+        Duration::from_secs_f64(seconds.max(0.0))
+    }
+
+    /// cancellation of statements of request by timeout or by end of request
+    pub fn cancellation(&self, req: &HttpRequest) -> Cancellation {
+        Cancellation::new(self.statement_timeout(req))
+    }
+
+    /// metrics of requests, queries and authentication
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+
+    /// name of datasource with schema
+    pub fn datasource(&self, schema_name: &str) -> String {
+        self.metainfo().datasource(schema_name).to_string()
+    }
+
+    /// entity for api request, only if it is exposed by rules
+    pub fn find_entity(&self, schema_name: &str, entity_name: &str) -> Option<Arc<Entity>> {
+        if !self.rules.table_allowed(schema_name, entity_name) {
+            return None;
+        }
+        self.metainfo()
+            .find_schema(schema_name)
+            .and_then(|s| s.find_entity(entity_name))
+            .cloned()
+    }
+
+    /// write current metainfo to snapshot, if snapshot is configured
+    fn save_snapshot(&self) {
+        if let Some(path) = &self.snapshot {
+            if let Err(err) = metainfo::save_snapshot(&self.metainfo(), path) {
+                error!(self.log, "can not save snapshot of metainfo"; "error" => err);
+            }
+        }
+    }
+
+    /// changes of metainfo found by last refreshes, oldest first
+    pub fn metainfo_changes(&self) -> Vec<MetaInfoChanges> {
+        self.changes.read().unwrap().iter().cloned().collect()
+    }
+
+    /// differences between snapshot from snapshots directory and current metainfo
+    pub fn diff_metainfo(&self, against: &str) -> Result<MetaInfoDiff, ApiError> {
+        let dir = self.others
+            .as_ref()
+            .and_then(|o| o.snapshots_dir.as_ref())
+            .ok_or_else(|| ApiError::NotFound("Directory with snapshots is not configured".to_string()))?;
+
+        // only files from snapshots directory are allowed
+        if against.is_empty() || against.contains(['/', '\\']) || against.starts_with('.') {
+            return Err(ApiError::BadRequest(format!("Invalid snapshot name: {}", against)));
+        }
+
+        // snapshot, which exists, can not be read or parsed: error of server
+        let path = PathBuf::from(dir).join(against);
+        if !path.is_file() {
+            return Err(ApiError::NotFound(format!("Snapshot {} not found", against)));
+        }
+        let snapshot = metainfo::load_snapshot(&path).map_err(ApiError::Internal)?;
+        Ok(metainfo::diff(&snapshot.metainfo, &self.metainfo()))
+    }
+}
+
+/// revalidate metainfo from snapshot against database in background, until database is available
+fn start_revalidation(state: Arc<ApplicationState>) -> server::SimpleResult<()> {
+    thread::Builder::new()
+        .name("metainfo-revalidate".to_string())
+        .spawn(move || loop {
+            match state.reload_metainfo(false) {
+                Ok(Some(_)) => break,
+                // other reload is running, its result is not known here
+                Ok(None) => thread::sleep(REVALIDATE_RETRY),
+                Err(err) => {
+                    error!(state.log, "can not revalidate metainfo from snapshot"; "error" => %err);
+                    thread::sleep(REVALIDATE_RETRY);
+                }
+            }
+        })
+        .map(|_| ())
+        .map_err(|err| format!("Can not start metainfo revalidation: {}", err))
+}
+
+/// periodic refresh of metainfo in background
+fn start_refresh(state: Arc<ApplicationState>, interval: Duration) -> server::SimpleResult<()> {
+    thread::Builder::new()
+        .name("metainfo-refresh".to_string())
+        .spawn(move || loop {
+            thread::sleep(interval);
+            if let Err(err) = state.reload_metainfo(false) {
+                error!(state.log, "can not refresh metainfo"; "error" => %err);
+            }
+        })
+        .map(|_| ())
+        .map_err(|err| format!("Can not start metainfo refresh: {}", err))
 }
 
 // group of base endpoints
-pub fn base_scope() -> Scope {
+pub fn base_scope(limits: RateLimit) -> impl HttpServiceFactory {
     web::scope("/mgmt")
-        .service(health)
+        .wrap(limits)
+        .service(health::health)
+        .service(health::live)
+        .service(health::ready)
         .service(metaapi::metainfo_scope())
+        .service(metaapi::metainfo_mgmt_scope())
+        .service(openapi::openapi_resource())
+        .service(metaapi::search_resource())
+        .service(metricsapi::metrics_resource())
         /*
         .service(fs::Files::new("/", "./www")
             .show_files_listing()
@@ -42,7 +363,3 @@ async fn index() -> Result<NamedFile> {
     Ok(NamedFile::open(path)?)
 }
 */
-#[get("/health")]
-async fn health() -> impl Responder {
-    "OK".to_string()
-}
@@ -0,0 +1,200 @@
+use std::sync::Arc;
+
+use actix_web::dev::HttpServiceFactory;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use serde_json::{json, Map, Value};
+
+use crate::metainfo::{Entity, MetaInfo};
+use crate::security::{Authorized, SecurityContext, BASE_ACCESS};
+use super::ApplicationState;
+use super::jsonschema::column_schema;
+
+const OPENAPI_VERSION: &str = "3.1.0";
+// component of error responses, names of entity components always contain schema
+const PROBLEM: &str = "Problem";
+
+// document for generation of api clients
+pub fn openapi_resource() -> impl HttpServiceFactory {
+    web::resource("/openapi.json")
+        .wrap(Authorized::authenticated())
+        .route(web::get().to(openapi_document))
+}
+
+async fn openapi_document(req: HttpRequest, data: web::Data<Arc<ApplicationState>>) -> impl Responder {
+    // api of tables is available only for members of BASE_ACCESS group
+    let api_access = req.extensions()
+        .get::<SecurityContext>()
+        .map(|ctx| ctx.is_member(BASE_ACCESS))
+        .unwrap_or(false);
+
+    let metainfo = data.metainfo();
+    let document = if api_access {
+        generate(&metainfo)
+    } else {
+        generate(&MetaInfo::default())
+    };
+    HttpResponse::Ok().json(document)
+}
+
+/// OpenAPI 3.1 document with paths of v1 api for all entities of metainfo
+pub fn generate(metainfo: &MetaInfo) -> Value {
+    let mut paths = Map::new();
+    let mut schemas = Map::new();
+
+    let mut schema_names: Vec<&String> = metainfo.schema_names().collect();
+    schema_names.sort();
+
+    for schema_name in schema_names {
+        let schema = match metainfo.find_schema(schema_name) {
+            Some(schema) => schema,
+            None => continue
+        };
+        let mut entities: Vec<_> = schema.entities_iter().collect();
+        entities.sort_by(|a, b| a.0.cmp(b.0));
+
+        for (entity_name, entity) in entities {
+            let component = component_name(schema_name, entity_name);
+            let reference = json!({ "$ref": format!("#/components/schemas/{}", component) });
+            let tags = json!([schema_name]);
+
+            paths.insert(
+                format!("/api/v1/{}/{}/", schema_name, entity_name),
+                json!({
+                    "get": {
+                        "operationId": format!("{}_list", component),
+                        "summary": format!("Query {}.{} by column values", schema_name, entity_name),
+                        "tags": tags,
+                        "parameters": [
+                            {
+                                "name": "q", "in": "query", "required": true,
+                                "description": "JSON object with values of columns, e.g. {\"id\":\"1\"}",
+                                "schema": { "type": "string" }
+                            },
+                            {
+                                "name": "order", "in": "query", "required": false,
+                                "description": "comma-separated columns for ordering",
+                                "schema": { "type": "string" }
+                            },
+                            {
+                                "name": "limit", "in": "query", "required": false,
+                                "schema": { "type": "integer", "minimum": 0 }
+                            },
+                            {
+                                "name": "offset", "in": "query", "required": false,
+                                "schema": { "type": "integer", "minimum": 0 }
+                            }
+                        ],
+                        "responses": {
+                            "200": {
+                                "description": "rows of entity",
+                                "content": { "application/json": { "schema": { "type": "array", "items": reference } } }
+                            },
+                            "400": problem_response("invalid query or scan of large table without indexed filter"),
+                            "404": problem_response("entity not found"),
+                            "503": problem_response("no connection of datasource available"),
+                            "504": problem_response("statement timed out")
+                        }
+                    }
+                })
+            );
+
+            if let Some(pk_columns) = primary_key_columns(entity) {
+                paths.insert(
+                    format!("/api/v1/{}/{}/{{pk}}", schema_name, entity_name),
+                    json!({
+                        "get": {
+                            "operationId": format!("{}_by_pk", component),
+                            "summary": format!("Get {}.{} by primary key", schema_name, entity_name),
+                            "tags": tags,
+                            "parameters": [
+                                {
+                                    "name": "pk", "in": "path", "required": true,
+                                    "description": format!("comma-separated values of primary key: {}", pk_columns.join(",")),
+                                    "schema": { "type": "string" }
+                                }
+                            ],
+                            "responses": {
+                                "200": {
+                                    "description": "row of entity",
+                                    "content": { "application/json": { "schema": reference } }
+                                },
+                                "400": problem_response("invalid primary key"),
+                                "404": problem_response("entity or row not found"),
+                                "503": problem_response("no connection of datasource available"),
+                                "504": problem_response("statement timed out")
+                            }
+                        }
+                    })
+                );
+            }
+
+            schemas.insert(component, entity_schema(entity));
+        }
+    }
+
+    schemas.insert(PROBLEM.to_string(), problem_schema());
+
+    json!({
+        "openapi": OPENAPI_VERSION,
+        "info": {
+            "title": "Foundation API",
+            "version": env!("CARGO_PKG_VERSION")
+        },
+        "paths": paths,
+        "components": { "schemas": schemas }
+    })
+}
+
+/// name of component schema; names of oracle objects can contain `$` and `#`
+pub fn component_name(schema_name: &str, entity_name: &str) -> String {
+    format!("{}.{}", schema_name, entity_name)
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+fn primary_key_columns(entity: &Entity) -> Option<Vec<&str>> {
+    entity.primary_key.as_ref().map(|pk| pk
+        .iter()
+        .filter_map(|i| entity.columns.get(*i))
+        .map(|c| c.name.as_str())
+        .collect())
+}
+
+fn problem_response(description: &str) -> Value {
+    json!({
+        "description": description,
+        "content": { "application/problem+json": { "schema": { "$ref": format!("#/components/schemas/{}", PROBLEM) } } }
+    })
+}
+
+/// problem details of errors with stable code
+fn problem_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "type": { "type": "string" },
+            "title": { "type": "string" },
+            "status": { "type": "integer" },
+            "code": { "type": "string" },
+            "detail": { "type": "string" },
+            "request_id": { "type": "string" }
+        },
+        "required": ["type", "title", "status", "code", "detail"]
+    })
+}
+
+/// JSON schema of row: all columns are always present, nullable columns can be null
+pub fn entity_schema(entity: &Entity) -> Value {
+    let properties: Map<String, Value> = entity.columns
+        .iter()
+        .map(|c| (c.name.clone(), column_schema(c)))
+        .collect();
+    let required: Vec<&str> = entity.columns.iter().map(|c| c.name.as_str()).collect();
+
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required
+    })
+}
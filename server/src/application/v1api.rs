@@ -1,58 +1,58 @@
 use std::collections::HashMap;
 use std::sync::Arc;
-use actix_web::{get, web, Responder, HttpResponse};
+use actix_web::{get, web, HttpRequest, Responder, HttpResponse};
 use actix_web::http::header::ContentType;
 use actix_web::dev::HttpServiceFactory;
 use serde::Deserialize;
+use slog::debug;
 
 use crate::application::{ApplicationState, v1query};
-use crate::metainfo;
+use crate::audit::{AuditAction, AuditEvent};
+use crate::metainfo::Entity;
+use crate::security::SecurityContext;
+use crate::server::ApiError;
+use crate::telemetry::Trace;
 
 // group of endpoints for api
-pub fn v1_api_scope() -> impl HttpServiceFactory {
+pub fn v1_api_scope(limits: crate::security::RateLimit) -> impl HttpServiceFactory {
     web::scope("/api")
+        .wrap(limits)
         .wrap(crate::security::Authorized::all())
         .service(table_query_by_pk)
         .service(table_query_by_params)
+        .service(super::graphql::graphql_resource())
 }
 
 #[get("/v1/{schema}/{table}/{pk}")]
-async fn table_query_by_pk(path: web::Path<(String,String,String)>, data: web::Data<Arc<ApplicationState>>) -> impl Responder {
+async fn table_query_by_pk(req: HttpRequest, path: web::Path<(String,String,String)>, data: web::Data<Arc<ApplicationState>>) -> impl Responder {
     let (schema_name,table_name, pk_params) = path.into_inner();
+    query_by_pk(&req, &schema_name, &table_name, &pk_params, &data).await
+        .unwrap_or_else(|err| data.error_response(&req, err))
+}
 
-    println!("table_query_by_pk: {}.{}; pk: {}", &schema_name, &table_name, &pk_params);
-
-    let metainfo = data.metainfo.read().unwrap();
-
-    if let Some(info) = metainfo.find_schema(&schema_name) {
-        if let Some(info) = info.find_entity(&table_name) {
-            let pk_params: Vec<String> = pk_params.split(",").map(|s|s.to_string()).collect();
-
-            let info = unsafe { 
-                // hack: transmute lifetime
-                // because we load metainfo once in startup
-                let info: &'static metainfo::Entity = std::mem::transmute(info);
-                info
-            };
-
-            let query = v1query::DynamicQuery::create_from_pk(&schema_name, &table_name, info, pk_params);
-            return match query {
-                Ok(query) => {
-                    let result = web::block(move || query.fetch_one()).await;
-                    match result {
-                        Ok(result) => HttpResponse::Ok().set(ContentType::json()).body(result),
-                        Err(e) => {
-                            eprintln!("{:?}",e);
-                            HttpResponse::InternalServerError().finish()
-                        }
-                    }
-                },
-                Err(err) => HttpResponse::BadRequest().body(err)
-            };
-        }
-    };
-
-    HttpResponse::NotFound().finish()
+async fn query_by_pk(req: &HttpRequest, schema_name: &str, table_name: &str, pk_params: &str, data: &ApplicationState) -> Result<HttpResponse, ApiError> {
+    let log = data.request_log(req);
+    debug!(log, "query by primary key"; "schema" => schema_name, "table" => table_name, "pk" => pk_params);
+
+    let info = find_entity(req, data, schema_name, table_name)?;
+    let pk_params: Vec<String> = pk_params.split(",").map(|s|s.to_string()).collect();
+
+    let mut event = read_event(req, schema_name, table_name);
+    event.key = Some(pk_params.clone());
+
+    let query = v1query::DynamicQuery::create_from_pk(schema_name, table_name, info, pk_params)?;
+    let backend = data.backend();
+    let datasource = data.datasource(schema_name);
+    let trace = Trace::of(req);
+    let cancellation = data.cancellation(req);
+    // statement is cancelled, when client is gone and request is dropped
+    let _guard = cancellation.guard();
+    let result = web::block(move || query.fetch_one(backend.as_ref(), &datasource, &trace, &cancellation)).await?;
+
+    event.rows = result.rows;
+    data.audit.record(event).await?;
+    data.metrics.record_result(schema_name, table_name, result.rows, result.json.len());
+    Ok(HttpResponse::Ok().set(ContentType::json()).body(result.json))
 }
 
 // for limit, offset etc, see: https://oracletutorial.com/oracle-basics/oracle-fetch
@@ -66,48 +66,92 @@ struct QueryParams {
 }
 
 #[get("/v1/{schema}/{table}/")]
-async fn table_query_by_params(path: web::Path<(String,String)>, req: web::Query<QueryParams>, data: web::Data<Arc<ApplicationState>>) -> impl Responder {
+async fn table_query_by_params(http_req: HttpRequest, path: web::Path<(String,String)>, req: web::Query<QueryParams>, data: web::Data<Arc<ApplicationState>>) -> impl Responder {
     let (schema_name,table_name) = path.into_inner();
+    query_by_params(&http_req, &schema_name, &table_name, req.into_inner(), &data).await
+        .unwrap_or_else(|err| data.error_response(&http_req, err))
+}
+
+async fn query_by_params(http_req: &HttpRequest, schema_name: &str, table_name: &str, req: QueryParams, data: &ApplicationState) -> Result<HttpResponse, ApiError> {
+    let log = data.request_log(http_req);
+    debug!(log, "query by params"; "schema" => schema_name, "table" => table_name, "q" => &req.q);
+
+    let info = find_entity(http_req, data, schema_name, table_name)?;
+    let paremeters: HashMap<String,String> = serde_json::from_str(&req.q)
+        .map_err(|err| ApiError::BadRequest(format!("Invalid query format: {}", err)))?;
+    let order: Vec<String> = req.order.as_ref().map(|s|s.split(",").map(|s|s.to_string()).collect()).unwrap_or(vec![]);
+
+    let mut event = read_event(http_req, schema_name, table_name);
+    event.filter = Some(paremeters.clone());
+
+    let query = v1query::DynamicQuery::create_from_params(
+        schema_name, table_name, info, paremeters, order, req.limit, req.offset, data.max_scan_rows()
+    )?;
+    let backend = data.backend();
+    let datasource = data.datasource(schema_name);
+    let trace = Trace::of(http_req);
+    let cancellation = data.cancellation(http_req);
+    // statement is cancelled, when client is gone and request is dropped
+    let _guard = cancellation.guard();
+    let result = web::block(move || query.fetch_many(backend.as_ref(), &datasource, &trace, &cancellation)).await?;
+
+    event.rows = result.rows;
+    data.audit.record(event).await?;
+    data.metrics.record_result(schema_name, table_name, result.rows, result.json.len());
+    Ok(HttpResponse::Ok().set(ContentType::json()).body(result.json))
+}
+
+/// exposed entity, which labels metrics of request
+fn find_entity(req: &HttpRequest, data: &ApplicationState, schema_name: &str, table_name: &str) -> Result<Arc<Entity>, ApiError> {
+    let entity = data.find_entity(schema_name, table_name)
+        .ok_or_else(|| ApiError::NotFound(format!("Table {}.{} is not found", schema_name, table_name)))?;
+    crate::metrics::label_entity(req, schema_name, table_name);
+    Ok(entity)
+}
+
+/// audit event for reading of table by current user
+fn read_event(req: &HttpRequest, schema_name: &str, table_name: &str) -> AuditEvent {
+    let user_id = req.extensions().get::<SecurityContext>().map(|ctx| ctx.user_id()).unwrap_or(0);
+    AuditEvent::new(user_id, req.path(), AuditAction::Read, schema_name, table_name)
+}
 
-    println!("table_query_by_params: {}.{}", &schema_name, &table_name);
-
-    let metainfo = data.metainfo.read().unwrap();
-
-    if let Some(info) = metainfo.find_schema(&schema_name) {
-        if let Some(info) = info.find_entity(&table_name) {
-            println!("table_query_by_params, q: {}", req.q);
-
-            let q: serde_json::error::Result<HashMap<String,String>> = serde_json::from_str(&req.q);
-            return match q {
-                Ok(paremeters) => {
-                    let order: Vec<String> = req.order.as_ref().map(|s|s.split(",").map(|s|s.to_string()).collect()).unwrap_or(vec![]);
-
-                    let info = unsafe { 
-                        // hack: transmute lifetime
-                        // because we load metainfo once in startup
-                        let info: &'static metainfo::Entity = std::mem::transmute(info);
-                        info
-                    };
-        
-                    let query = v1query::DynamicQuery::create_from_params(&schema_name, &table_name, info, paremeters, order, req.limit, req.offset);
-                    return match query {
-                        Ok(query) => {
-                            let result = web::block(move || query.fetch_many()).await;
-                            match result {
-                                Ok(result) => HttpResponse::Ok().set(ContentType::json()).body(result),
-                                Err(e) => {
-                                    eprintln!("{:?}",e);
-                                    HttpResponse::InternalServerError().finish()
-                                }
-                            }
-                        },
-                        Err(err) => HttpResponse::BadRequest().body(err)
-                    };
-                },
-                Err(err) => HttpResponse::BadRequest().body(format!("Invalid query format: {}", err))
-            };
-        }
-    };
-
-    HttpResponse::NotFound().finish()
+#[cfg(test)]
+mod tests {
+    use actix_web::{test, App};
+    use actix_web::http::StatusCode;
+
+    use super::*;
+    use crate::backend::MemoryBackend;
+
+    fn state() -> Arc<ApplicationState> {
+        ApplicationState::with_backend(Arc::new(MemoryBackend::fixture()), None, Default::default()).unwrap()
+    }
+
+    #[actix_rt::test]
+    async fn query_by_pk() {
+        let mut app = test::init_service(App::new().data(state()).service(table_query_by_pk)).await;
+
+        let req = test::TestRequest::get().uri("/v1/hr/departments/30").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(test::read_body(resp).await, "{ \"id\":30,\"name\":\"Sales\" }");
+
+        let req = test::TestRequest::get().uri("/v1/hr/unknown/1").to_request();
+        assert_eq!(test::call_service(&mut app, req).await.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn query_by_params() {
+        let mut app = test::init_service(App::new().data(state()).service(table_query_by_params)).await;
+
+        let req = test::TestRequest::get()
+            .uri("/v1/hr/employees/?q=%7B%22department_id%22%3A%2210%22%7D&order=name")
+            .to_request();
+        let rows: Vec<serde_json::Value> = test::read_response_json(&mut app, req).await;
+        let names: Vec<&str> = rows.iter().filter_map(|r| r["name"].as_str()).collect();
+        assert_eq!(names, vec!["Clark \"Junior\"", "King"]);
+
+        let req = test::TestRequest::get().uri("/v1/hr/events/?q=%7B%7D").to_request();
+        assert_eq!(test::call_service(&mut app, req).await.status(), StatusCode::BAD_REQUEST);
+    }
 }
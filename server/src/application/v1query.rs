@@ -1,181 +1,218 @@
 use std::collections::HashMap;
-use itertools::Itertools;
-
-use oracle;
+use std::sync::Arc;
 
+use crate::backend::{Backend, Cancellation, Keys, Parameter, Row, Select, Value};
 use crate::metainfo;
-use crate::server::get_connection;
+use crate::server::ApiError;
+use crate::telemetry::Trace;
+
+// larger tables are not scanned without filter by indexed column, unless configured in `others`
+pub const DEFAULT_MAX_SCAN_ROWS: u32 = 1_000_000;
 
 pub struct DynamicQuery {
-    sql: String,
-    fetch_array_size: u32,
-    columns: &'static Vec<metainfo::Column>, // hack because we load metainfo once in startup
-    params: Vec<Parameter>,
+    select: Select,
 }
 
-struct Parameter {
-    pub column: &'static metainfo::Column, // hack because we load metainfo once in startup
-    pub value:  ParameterValue
-}
-enum ParameterValue {
-    Int16 (i16), Int32(i32), Int64(i64), Varchar(String)
+/// JSON result of query and count of fetched rows
+pub struct QueryResult {
+    pub json: String,
+    pub rows: usize,
 }
 
 impl DynamicQuery {
     pub fn create_from_pk(schema_name: &str, 
                           entity_name: &str, 
-                          entity:      &'static metainfo::Entity, 
-                          pk_params:   Vec<String>) -> Result<DynamicQuery, String> {
+                          entity:      Arc<metainfo::Entity>,
+                          pk_params:   Vec<String>) -> Result<DynamicQuery, ApiError> {
         match &entity.primary_key {
-            None => Err("Primary key not exists".to_string()),
+            None => Err(ApiError::BadRequest("Primary key not exists".to_string())),
             Some(ref pk_indices) => {
                 let param_columns_len = pk_params.len();
 
                 if param_columns_len != pk_indices.len() {
-                    return Err("Count of columns in primary key does not match with count of parameters in query".to_string())
+                    return Err(ApiError::BadRequest("Count of columns in primary key does not match with count of parameters in query".to_string()))
                 }
 
                 let mut params = Vec::with_capacity(param_columns_len);
 
                 for (pk_column_index, p) in pk_indices.iter().zip(pk_params) {
-                    let pk_column = unsafe { entity.columns.get_unchecked(*pk_column_index) };
+                    let pk_column = &entity.columns[*pk_column_index];
 
-                    let parsed = Parameter::parse(pk_column, p.to_string());
+                    let parsed = parse_parameter(*pk_column_index, pk_column, p.to_string());
                     match parsed {
-                        Err(err) => return Err(format!("Can not parse parameter value {} for column {}: {}", p, pk_column.name, err)),
+                        Err(err) => return Err(ApiError::BadRequest(format!("Can not parse parameter value {} for column {}: {}", p, pk_column.name, err))),
                         Ok(parsed) => {
                             params.push(parsed);
                         }
                     }
                 };
 
-                let sql = generate_sql(schema_name, entity_name, &entity.columns, &params, vec![], 1, Option::None);
-
-                Ok( DynamicQuery { sql, fetch_array_size: 1, columns: &entity.columns, params } )
+                let select = Select {
+                    schema_name: schema_name.to_string(),
+                    entity_name: entity_name.to_string(),
+                    entity: entity.clone(),
+                    params,
+                    order: vec![],
+                    limit: 1,
+                    offset: None,
+                    keys: None
+                };
+                Ok( DynamicQuery { select } )
             }
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn create_from_params(schema_name: &str,
                               entity_name: &str,
-                              entity:      &'static metainfo::Entity, 
+                              entity:      Arc<metainfo::Entity>,
                               parameters:  HashMap<String,String>,
                               order:       Vec<String>,
                               limit:       Option<u32>,
-                              offset:      Option<u32>
-    ) -> Result<DynamicQuery, String> {
+                              offset:      Option<u32>,
+                              max_scan_rows: u32
+    ) -> Result<DynamicQuery, ApiError> {
+        Self::create(schema_name, entity_name, entity, parameters, None, order, limit, offset, max_scan_rows)
+    }
+
+    /// rows of many keys in one query, limit and offset apply to rows of every key
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_from_keys(schema_name: &str,
+                            entity_name: &str,
+                            entity:      Arc<metainfo::Entity>,
+                            parameters:  HashMap<String,String>,
+                            key_columns: Vec<String>,
+                            keys:        Vec<Vec<String>>,
+                            order:       Vec<String>,
+                            limit:       Option<u32>,
+                            offset:      Option<u32>,
+                            max_scan_rows: u32
+    ) -> Result<DynamicQuery, ApiError> {
+        let mut columns = Vec::with_capacity(key_columns.len());
+        for col_name in &key_columns {
+            match entity.columns.iter().position(|c|&c.name == col_name) {
+                None => return Err(ApiError::BadRequest(format!("Not found column {}", col_name))),
+                Some(column_index) => columns.push(column_index)
+            }
+        }
+
+        let mut values = Vec::with_capacity(keys.len());
+        for key in keys {
+            let mut key_params = Vec::with_capacity(columns.len());
+            for (column_index, p) in columns.iter().zip(key) {
+                key_params.push(parse_column_parameter(*column_index, &entity.columns[*column_index], p)?);
+            }
+            values.push(key_params);
+        }
+
+        let keys = Keys { columns, values };
+        Self::create(schema_name, entity_name, entity, parameters, Some(keys), order, limit, offset, max_scan_rows)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn create(schema_name: &str,
+              entity_name: &str,
+              entity:      Arc<metainfo::Entity>,
+              parameters:  HashMap<String,String>,
+              keys:        Option<Keys>,
+              order:       Vec<String>,
+              limit:       Option<u32>,
+              offset:      Option<u32>,
+              max_scan_rows: u32
+    ) -> Result<DynamicQuery, ApiError> {
         let param_columns_len = parameters.len();
 
         let mut params = Vec::with_capacity(param_columns_len);
 
-        for (ref col_name,ref p) in parameters {
-            let column = entity.columns.iter().find(|c|&c.name == col_name);
+        for (col_name, p) in parameters {
+            let column = entity.columns.iter().position(|c|c.name == col_name);
 
             match column {
-                None => return Err(format!("Not found column {}", col_name)),
-                Some(column) => {
-                    let parsed = Parameter::parse(&column as &'static metainfo::Column, p.to_string());
-                    match parsed {
-                        Err(err) => return Err(format!("Can not parse parameter value {} for column {}: {}", p, col_name, err)),
-                        Ok(parsed) => {
-                            params.push(parsed);
-                        }
-                    }
-                }
+                None => return Err(ApiError::BadRequest(format!("Not found column {}", col_name))),
+                Some(column_index) => params.push(parse_column_parameter(column_index, &entity.columns[column_index], p)?)
             }
         }
 
+        let mut order_columns = Vec::with_capacity(order.len());
         for col_name in &order {
-            let column = entity.columns.iter().find(|c|&c.name == col_name);
-            if column.is_none() {
-                return Err(format!("Order column {} nof found in table {}", col_name, &entity_name))
+            let column = entity.columns.iter().position(|c|&c.name == col_name);
+            match column {
+                None => return Err(ApiError::BadRequest(format!("Order column {} nof found in table {}", col_name, &entity_name))),
+                Some(column_index) => order_columns.push(column_index)
             }
         };
 
+        if let Some(num_rows) = entity.num_rows {
+            let indexed = params.iter().any(|p| entity.is_indexed(p.column))
+                || keys.iter().flat_map(|k| k.columns.iter()).any(|c| entity.is_indexed(*c));
+            if num_rows > max_scan_rows && !indexed {
+                return Err(ApiError::BadRequest(format!(
+                    "Table {}.{} has about {} rows, query must filter by leading column of primary key or index",
+                    schema_name, entity_name, num_rows
+                )));
+            }
+        }
+
         let limit = limit.unwrap_or(25);
 
         if limit > 100  {
-            return Err("limit rows must be <= 100".to_string());
+            return Err(ApiError::BadRequest("limit rows must be <= 100".to_string()));
         }
 
         if let Some(offset) = offset {
             if offset < limit {
-                return Err("offset must be >= limit".to_string());
+                return Err(ApiError::BadRequest("offset must be >= limit".to_string()));
             }
             if offset % limit > 0 {
-                return Err("offset must be a multiple of the limit (remainder must be zero)".to_string());
+                return Err(ApiError::BadRequest("offset must be a multiple of the limit (remainder must be zero)".to_string()));
             }
         }
 
-        let sql = generate_sql(schema_name, entity_name, &entity.columns, &params, order, limit, offset);
-
-        Ok( DynamicQuery { sql, fetch_array_size: limit, columns: &entity.columns, params } )
+        let select = Select {
+            schema_name: schema_name.to_string(),
+            entity_name: entity_name.to_string(),
+            entity,
+            params,
+            order: order_columns,
+            limit,
+            offset,
+            keys
+        };
+        Ok( DynamicQuery { select } )
     }
 
     /// execute a query and generate JSON result
-    pub fn fetch_one(self) -> Result<String,String> {
-        let conn = get_connection()
-            .map_err(|err|format!("Can not connect to oracle: {}", err))?;
-
-        let mut stmt = conn.prepare(&self.sql, &[oracle::StmtParam::FetchArraySize(self.fetch_array_size)])
-            .map_err(|err| format!("can not prepare statement: {}", err))?;
-
-        let params_view: Vec<&dyn oracle::sql_type::ToSql> = 
-            self.params
-                .iter()
-                .map(|p| p as &dyn oracle::sql_type::ToSql)
-                .collect();
-
-        let row = stmt
-            .query_row(&params_view[..])
-            .map_err(|err| format!("can not dynamic query from statement: {:?}", err))?;
+    pub fn fetch_one(self, backend: &dyn Backend, datasource: &str, trace: &Trace, cancellation: &Cancellation) -> Result<QueryResult,ApiError> {
+        let rows = backend.select(datasource, &self.select, trace, cancellation)?;
+        let row = rows.first().ok_or_else(|| ApiError::NoData(format!(
+            "No row of {}.{} with primary key", self.select.schema_name, self.select.entity_name
+        )))?;
 
-        let result = self.gen_result(row);
+        let mut span = trace.span("serialize");
+        let json = self.gen_result(row);
+        span.attribute("json.bytes", json.len());
 
-        Ok(result)
+        Ok( QueryResult { json, rows: 1 } )
     }
 
     /// execute a query and generate JSON result
-    pub fn fetch_many(self) -> Result<String,String> {
-        let conn = get_connection()
-            .map_err(|err|format!("Can not connect to oracle: {}", err))?;
-
-        let mut stmt = conn.prepare(&self.sql, &[oracle::StmtParam::FetchArraySize(self.fetch_array_size)])
-            .map_err(|err| format!("can not prepare statement: {}", err))?;
-
-        let params_view: Vec<&dyn oracle::sql_type::ToSql> = 
-            self.params
-                .iter()
-                .map(|p| p as &dyn oracle::sql_type::ToSql)
-                .collect();
-
-        let rows = stmt
-            .query(&params_view[..])
-            .map_err(|err| format!("can not dynamic query from statement: {:?}", err))?;
-
-        let mut result = Vec::new();
-
-        for row in rows {
-            match row {
-                Ok(row) => {
-                    let r = self.gen_result(row);
-                    result.push(r);        
-                },
-                Err(err) => {
-                    return Err(format!("can not fetch query result: {:?}", err))
-                }
-            }
-        }
+    pub fn fetch_many(self, backend: &dyn Backend, datasource: &str, trace: &Trace, cancellation: &Cancellation) -> Result<QueryResult,ApiError> {
+        let rows = backend.select(datasource, &self.select, trace, cancellation)?;
 
-        Ok( format!("[{}]", result.join(",")) )
+        let mut span = trace.span("serialize");
+        let result: Vec<String> = rows.iter().map(|row| self.gen_result(row)).collect();
+        let json = format!("[{}]", result.join(","));
+        span.attribute("json.bytes", json.len());
+
+        Ok( QueryResult { json, rows: result.len() } )
     }
 
-    fn gen_result(&self, rs: oracle::Row) -> String {
-        let results: Vec<String> = self.columns
+    fn gen_result(&self, rs: &Row) -> String {
+        let results: Vec<String> = self.select.entity.columns
             .iter()
             .enumerate()
             .map(|(idx, col)|{
-                let result = col.try_to_string(&rs, idx);
+                let result = rs.get(idx).to_json();
                 format!("\"{}\":{}", col.name, result)
             }).collect();
 
@@ -184,87 +221,115 @@ impl DynamicQuery {
 
 }
 
-fn generate_sql(schema_name: &str, 
-    entity_name: &str, 
-    columns:     &Vec<metainfo::Column>, 
-    params:      &Vec<Parameter>,
-    order:       Vec<String>,
-    limit:       u32,
-    offset:      Option<u32>
-) -> String {
-    let joined_result_columns = columns.iter().map(|c|&c.name).join(",");
-
-    let enumerated_param_columns: Vec<String> =
-    params.iter().enumerate().map(|(idx,p)|format!("{} = :{}", p.column.name, idx+1)).collect();
-    let joined_param_columns = enumerated_param_columns.join(" AND ");
-
-    let mut sql = format!("SELECT {} FROM {}.{} WHERE {}", joined_result_columns, schema_name, entity_name, joined_param_columns);
-
-    if order.len() > 0 {
-        let joined_order_columns = order.join(",");
-        let order_clause = format!(" ORDER BY {}", joined_order_columns);
-        sql.push_str(&order_clause);
+/// value of column from request, with error of request
+fn parse_column_parameter(column_index: usize, column: &metainfo::Column, value: String) -> Result<Parameter, ApiError> {
+    parse_parameter(column_index, column, value.clone())
+        .map_err(|err| ApiError::BadRequest(format!("Can not parse parameter value {} for column {}: {}", value, column.name, err)))
+}
+
+/// value of column from request, typed by column
+fn parse_parameter(column_index: usize, column: &metainfo::Column, value: String) -> Result<Parameter, &'static str> {
+    let value = match column.col_type {
+        metainfo::ColumnType::Integer => {
+            let parsed = match column.col_size {
+                2 => value.parse::<i16>().map(i64::from),
+                4 => value.parse::<i32>().map(i64::from),
+                8 => value.parse::<i64>(),
+                _ => return Err("Not supported size for Number")
+            };
+            parsed.map(Value::Integer).map_err(|_| "Invalid integer")
+        },
+        metainfo::ColumnType::String => {
+            Ok(Value::String(value))
+        },
+        _ => Err("Not supported type for Primary key")
+    };
+    value.map(|v| Parameter{ column: column_index, sql_type: column.sql_type.clone(), value: v})
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    use chrono::{Local, TimeZone};
+
+    use super::*;
+    use crate::backend::MemoryBackend;
+    use crate::metainfo::Rules;
+    use crate::server::DEFAULT_DATASOURCE;
+
+    fn entity(backend: &MemoryBackend, name: &str) -> Arc<metainfo::Entity> {
+        let metainfo = backend.load_metainfo(&Rules::default()).unwrap();
+        metainfo.find_schema("hr").and_then(|s| s.find_entity(name)).cloned().unwrap()
     }
 
-    if limit > 1 {
-        if let Some(offset) = offset {
-            let offset_clause = format!(" OFFSET {} ROWS", offset);
-            sql.push_str(&offset_clause);
-        }
-        let fetch_clause = format!(" FETCH NEXT {} ROWS ONLY", limit);
-        sql.push_str(&fetch_clause);
+    fn query(backend: &MemoryBackend, name: &str, filter: &[(&str, &str)], order: &[&str]) -> Result<DynamicQuery, ApiError> {
+        let filter: HashMap<String, String> = filter.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        let order = order.iter().map(|c| c.to_string()).collect();
+        DynamicQuery::create_from_params("hr", name, entity(backend, name), filter, order, None, None, DEFAULT_MAX_SCAN_ROWS)
     }
-    sql        
-}
 
-impl Parameter {
-    fn parse(column: &'static metainfo::Column, value: String) -> Result<Self, &'static str> {
-        let value = match column.col_type {
-            metainfo::ColumnType::Integer => {
-                match column.col_size {
-                    2 => {
-                        let val: i16 = value.parse().unwrap();
-                        Ok(ParameterValue::Int16(val))
-                    },
-                    4 => {
-                        let val: i32 = value.parse().unwrap();
-                        Ok(ParameterValue::Int32(val))
-                    },
-                    8 => {
-                        let val: i64 = value.parse().unwrap();
-                        Ok(ParameterValue::Int64(val))
-                    },
-                    _ => Err("Not supported size for Number")
-                }
-            },
-            metainfo::ColumnType::String => {
-                Ok(ParameterValue::Varchar(value))
-            },
-            _ => Err("Not supported type for Primary key")
-        };
-        value.map(|v| Parameter{ column, value: v})
+    #[test]
+    fn fetch_by_primary_key() {
+        let backend = MemoryBackend::fixture();
+        let query = DynamicQuery::create_from_pk("hr", "departments", entity(&backend, "departments"), vec!["20".to_string()]).unwrap();
+        let result = query.fetch_one(&backend, DEFAULT_DATASOURCE, &Trace::disabled(), &Cancellation::new(Duration::from_secs(10))).unwrap();
+        assert_eq!(result.rows, 1);
+        assert_eq!(result.json, "{ \"id\":20,\"name\":\"Research\" }");
     }
-}
 
-impl oracle::sql_type::ToSql for Parameter {
-    fn oratype(&self, _conn: &oracle::Connection) -> oracle::Result<oracle::sql_type::OracleType> {
-        Ok(self.column.sql_type.clone())
+    #[test]
+    fn fetch_by_params_with_nulls_and_escapes() {
+        let backend = MemoryBackend::fixture();
+        let result = query(&backend, "employees", &[("department_id", "10")], &["id"]).unwrap()
+            .fetch_many(&backend, DEFAULT_DATASOURCE, &Trace::disabled(), &Cancellation::new(Duration::from_secs(10)))
+            .unwrap();
+        assert_eq!(result.rows, 2);
+
+        let hired = Local.ymd(2001, 6, 17).and_hms(9, 0, 0).to_rfc3339();
+        let expected = format!(
+            "[{{ \"id\":1,\"name\":\"King\",\"salary\":5000,\"department_id\":10,\"hired\":\"{}\" }},\
+            {{ \"id\":3,\"name\":\"Clark \\\"Junior\\\"\",\"salary\":null,\"department_id\":10,\"hired\":null }}]",
+            hired
+        );
+        assert_eq!(result.json, expected);
+        assert!(serde_json::from_str::<serde_json::Value>(&result.json).is_ok());
     }
 
-    fn to_sql(&self, p: &mut oracle::SqlValue) -> oracle::Result<()> {
-        match &self.value {
-            ParameterValue::Int16(val) => {
-                val.to_sql(p)
-            },
-            ParameterValue::Int32(val) => {
-                val.to_sql(p)
-            },
-            ParameterValue::Int64(val) => {
-                val.to_sql(p)
-            },
-            ParameterValue::Varchar(val) => {
-                val.to_sql(p)
-            },
-        }
+    #[test]
+    fn fetch_by_keys_with_limit_of_every_key() {
+        let backend = MemoryBackend::fixture();
+        let keys = vec![vec!["10".to_string()], vec!["20".to_string()]];
+        let result = DynamicQuery::create_from_keys(
+            "hr", "employees", entity(&backend, "employees"), HashMap::new(),
+            vec!["department_id".to_string()], keys, vec!["id".to_string()], Some(1), None, DEFAULT_MAX_SCAN_ROWS
+        ).unwrap()
+            .fetch_many(&backend, DEFAULT_DATASOURCE, &Trace::disabled(), &Cancellation::new(Duration::from_secs(10)))
+            .unwrap();
+        let rows: Vec<serde_json::Value> = serde_json::from_str(&result.json).unwrap();
+        let ids: Vec<i64> = rows.iter().filter_map(|r| r["id"].as_i64()).collect();
+        assert_eq!(ids, vec![1, 4]);
+    }
+
+    #[test]
+    fn invalid_params_are_rejected() {
+        let backend = MemoryBackend::fixture();
+        assert!(query(&backend, "employees", &[("unknown", "1")], &[]).is_err());
+        assert!(query(&backend, "employees", &[("id", "one")], &[]).is_err());
+        assert!(query(&backend, "employees", &[], &["unknown"]).is_err());
+    }
+
+    #[test]
+    fn unindexed_scan_of_large_table_is_refused() {
+        let backend = MemoryBackend::fixture();
+        assert!(query(&backend, "events", &[], &[]).is_err());
+        assert!(query(&backend, "events", &[("kind", "login")], &[]).is_err());
+        assert!(query(&backend, "events", &[("id", "1")], &[]).is_ok());
+        assert!(query(&backend, "employees", &[], &[]).is_ok());
+
+        let filter = HashMap::new();
+        let events = DynamicQuery::create_from_params("hr", "events", entity(&backend, "events"), filter, vec![], None, None, 10_000_000);
+        assert!(events.is_ok());
     }
 }
@@ -0,0 +1,239 @@
+mod sinks;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use actix_web::rt::time::delay_for;
+use chrono::{DateTime, Local};
+use serde::{Serialize, Deserialize};
+use slog::{error, Logger};
+
+use crate::metrics::Metrics;
+use crate::server::config::AuditConfig;
+use crate::server::{ApiError, Datasources, SimpleResult};
+
+const QUEUE_SIZE: usize = 10000;
+// wait for space in full queue, then request fails: data is not returned without record of access
+const QUEUE_WAIT: Duration = Duration::from_secs(2);
+const QUEUE_POLL: Duration = Duration::from_millis(10);
+
+/// Sensitivity of data in schema or table, events below configured minimum level are not recorded
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sensitivity {
+    Public,
+    #[default]
+    Internal,
+    Confidential,
+}
+
+impl Sensitivity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Sensitivity::Public       => "public",
+            Sensitivity::Internal     => "internal",
+            Sensitivity::Confidential => "confidential",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditAction {
+    Read,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Read => "read",
+        }
+    }
+}
+
+/// One access to data: who read or changed which records
+#[derive(Debug, Serialize)]
+pub struct AuditEvent {
+    pub timestamp:   DateTime<Local>,
+    pub user_id:     u32,
+    pub endpoint:    String,
+    pub action:      AuditAction,
+    pub schema:      String,
+    pub table:       String,
+    pub sensitivity: Sensitivity,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key:         Option<Vec<String>>, // primary key values
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter:      Option<HashMap<String,String>>,
+    pub rows:        usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before:      Option<serde_json::Value>, // values before modification
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after:       Option<serde_json::Value>, // values after modification
+}
+
+impl AuditEvent {
+    pub fn new(user_id: u32, endpoint: &str, action: AuditAction, schema: &str, table: &str) -> Self {
+        AuditEvent {
+            timestamp: Local::now(),
+            user_id,
+            endpoint: endpoint.to_string(),
+            action,
+            schema: schema.to_string(),
+            table: table.to_string(),
+            sensitivity: Sensitivity::default(),
+            key: None,
+            filter: None,
+            rows: 0,
+            before: None,
+            after: None
+        }
+    }
+}
+
+struct Inner {
+    sender:  Option<SyncSender<AuditEvent>>,
+    min_level:     Sensitivity,
+    default_level: Sensitivity,
+    levels:  HashMap<(String, Option<String>), Sensitivity>, // (schema, table) => level
+    wait:    Duration,
+    metrics: Arc<Metrics>,
+    log:     Logger,
+}
+
+/// Handler of audit log; events are written asynchronously by background writer
+#[derive(Clone)]
+pub struct AuditLog {
+    inner: Arc<Inner>,
+}
+
+impl AuditLog {
+    pub fn start(config: &Option<AuditConfig>, datasources: &Arc<Datasources>, metrics: Arc<Metrics>, log: Logger) -> SimpleResult<AuditLog> {
+        let config = match config {
+            Some(config) => config,
+            None => return Ok(AuditLog::disabled())
+        };
+
+        let mut writers = Vec::new();
+        if let Some(file) = &config.file {
+            writers.push(sinks::AuditSink::file(file, log.clone())?);
+        }
+        if let Some(table) = &config.table {
+            writers.push(sinks::AuditSink::table(table, datasources.clone()));
+        }
+
+        let sender = if writers.is_empty() {
+            None
+        } else {
+            let (sender, receiver) = mpsc::sync_channel(QUEUE_SIZE);
+            let writer_log = log.clone();
+            thread::Builder::new()
+                .name("audit-writer".to_string())
+                .spawn(move || sinks::write_events(receiver, writers, writer_log))
+                .map_err(|err| format!("Can not start audit writer: {}", err))?;
+            Some(sender)
+        };
+
+        let levels = config.levels
+            .iter()
+            .map(|l| ((l.schema.to_lowercase(), l.table.as_ref().map(|t| t.to_lowercase())), l.level))
+            .collect();
+
+        let inner = Inner {
+            sender,
+            min_level: config.min_level,
+            default_level: config.default_level,
+            levels,
+            wait: QUEUE_WAIT,
+            metrics,
+            log
+        };
+        Ok(AuditLog { inner: Arc::new(inner) })
+    }
+
+    pub fn disabled() -> AuditLog {
+        let inner = Inner {
+            sender: None,
+            min_level: Sensitivity::default(),
+            default_level: Sensitivity::default(),
+            levels: HashMap::new(),
+            wait: QUEUE_WAIT,
+            metrics: Default::default(),
+            log: crate::server::discard_logging()
+        };
+        AuditLog { inner: Arc::new(inner) }
+    }
+
+    /// sensitivity of table: configured for table, for schema or default
+    pub fn sensitivity(&self, schema: &str, table: &str) -> Sensitivity {
+        let levels = &self.inner.levels;
+        levels.get(&(schema.to_string(), Some(table.to_string())))
+            .or_else(|| levels.get(&(schema.to_string(), None)))
+            .copied()
+            .unwrap_or(self.inner.default_level)
+    }
+
+    /// enqueue event for writing, waits for space in full queue;
+    /// fails when event can not be recorded, then data must not be returned
+    pub async fn record(&self, mut event: AuditEvent) -> Result<(), ApiError> {
+        let sender = match &self.inner.sender {
+            Some(sender) => sender,
+            None => return Ok(())
+        };
+
+        event.sensitivity = self.sensitivity(&event.schema, &event.table);
+        if event.sensitivity < self.inner.min_level {
+            return Ok(());
+        }
+
+        let started = Instant::now();
+        loop {
+            event = match sender.try_send(event) {
+                Ok(_) => return Ok(()),
+                Err(TrySendError::Full(event)) if started.elapsed() < self.inner.wait => event,
+                Err(TrySendError::Full(_)) => return Err(self.dropped("audit queue is full")),
+                Err(TrySendError::Disconnected(_)) => return Err(self.dropped("audit writer is stopped")),
+            };
+            delay_for(QUEUE_POLL).await;
+        }
+    }
+
+    fn dropped(&self, reason: &str) -> ApiError {
+        self.inner.metrics.record_audit_dropped();
+        error!(self.inner.log, "audit event is not recorded, request fails"; "reason" => reason);
+        ApiError::AuditUnavailable(format!("Access can not be recorded in audit log: {}", reason))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_rt::test]
+    async fn requests_fail_when_events_can_not_be_queued() {
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let metrics: Arc<Metrics> = Default::default();
+        let inner = Inner {
+            sender: Some(sender),
+            min_level: Sensitivity::Public,
+            default_level: Sensitivity::Internal,
+            levels: HashMap::new(),
+            wait: Duration::from_millis(50),
+            metrics: metrics.clone(),
+            log: crate::server::discard_logging()
+        };
+        let audit = AuditLog { inner: Arc::new(inner) };
+        let event = || AuditEvent::new(42, "/api/v1/hr/employees/1", AuditAction::Read, "hr", "employees");
+
+        assert!(audit.record(event()).await.is_ok());
+        let err = audit.record(event()).await.unwrap_err();
+        assert_eq!(err.code(), "audit_unavailable");
+
+        drop(receiver);
+        assert!(audit.record(event()).await.is_err());
+        assert!(metrics.render(&[]).contains("foundation_audit_events_dropped_total 2\n"));
+    }
+}
@@ -0,0 +1,264 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use chrono::{Local, NaiveDate};
+use slog::{error, Logger};
+
+use super::AuditEvent;
+use crate::server::config::{AuditFileConfig, AuditTableConfig};
+use crate::server::{Datasources, SimpleResult};
+
+const FILE_PREFIX: &str = "audit-";
+const FILE_SUFFIX: &str = ".jsonl";
+const BATCH_SIZE: usize = 256;
+// pauses between attempts to write failed batch, queue fills up meanwhile and requests fail
+const RETRY_MIN: Duration = Duration::from_millis(100);
+const RETRY_MAX: Duration = Duration::from_secs(10);
+
+/// Writer of audit events
+pub enum AuditSink {
+    File(FileSink),
+    Table(TableSink),
+}
+
+/// Local JSONL files, rotated daily: `<dir>/audit-YYYY-MM-DD.jsonl`
+pub struct FileSink {
+    dir: PathBuf,
+    retention_days: Option<u32>,
+    current: Option<(NaiveDate, BufWriter<File>)>,
+    log: Logger,
+}
+
+/// Oracle audit table, expected structure:
+/// ```sql
+/// CREATE TABLE AUDIT_LOG (
+///     EVENT_TIME  DATE NOT NULL,
+///     USER_ID     NUMBER(10) NOT NULL,
+///     ENDPOINT    VARCHAR2(512) NOT NULL,
+///     ACTION      VARCHAR2(16) NOT NULL,
+///     SCHEMA_NAME VARCHAR2(128) NOT NULL,
+///     TABLE_NAME  VARCHAR2(128) NOT NULL,
+///     SENSITIVITY VARCHAR2(16) NOT NULL,
+///     ROW_COUNT   NUMBER(10) NOT NULL,
+///     DETAILS     CLOB
+/// )
+/// ```
+pub struct TableSink {
+    datasources: Arc<Datasources>,
+    name: String,
+    retention_days: Option<u32>,
+    cleaned: Option<NaiveDate>,
+}
+
+impl AuditSink {
+    pub fn file(config: &AuditFileConfig, log: Logger) -> SimpleResult<AuditSink> {
+        let dir = PathBuf::from(&config.dir);
+        fs::create_dir_all(&dir)
+            .map_err(|err| format!("Can not create audit directory {}: {}", &config.dir, err))?;
+        Ok(AuditSink::File(FileSink { dir, retention_days: config.retention_days, current: None, log }))
+    }
+
+    pub fn table(config: &AuditTableConfig, datasources: Arc<Datasources>) -> AuditSink {
+        AuditSink::Table(TableSink { datasources, name: config.name.clone(), retention_days: config.retention_days, cleaned: None })
+    }
+
+    fn write(&mut self, events: &[AuditEvent]) -> SimpleResult<()> {
+        match self {
+            AuditSink::File(sink) => sink.write(events),
+            AuditSink::Table(sink) => sink.write(events),
+        }
+    }
+
+    fn flush(&mut self) {
+        if let AuditSink::File(FileSink { current: Some((_, writer)), .. }) = self {
+            let _ = writer.flush();
+        }
+    }
+}
+
+/// loop of background writer: collect events in batches and pass them to all sinks;
+/// failed batch is kept and written again, events are never discarded
+pub fn write_events(receiver: Receiver<AuditEvent>, mut sinks: Vec<AuditSink>, log: Logger) {
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    loop {
+        match receiver.recv_timeout(Duration::from_secs(1)) {
+            Ok(event) => {
+                batch.push(event);
+                while batch.len() < BATCH_SIZE {
+                    match receiver.try_recv() {
+                        Ok(event) => batch.push(event),
+                        Err(_) => break
+                    }
+                }
+            },
+            Err(RecvTimeoutError::Timeout) => {
+                sinks.iter_mut().for_each(|s| s.flush());
+                continue;
+            },
+            Err(RecvTimeoutError::Disconnected) => {
+                sinks.iter_mut().for_each(|s| s.flush());
+                return;
+            }
+        }
+
+        let mut pending: Vec<&mut AuditSink> = sinks.iter_mut().collect();
+        let mut retry = RETRY_MIN;
+        loop {
+            pending.retain_mut(|sink| match sink.write(&batch) {
+                Ok(_) => false,
+                Err(err) => {
+                    error!(log, "can not write audit events, retry"; "events" => batch.len(), "retry_ms" => retry.as_millis() as u64, "error" => err);
+                    true
+                }
+            });
+            if pending.is_empty() {
+                break;
+            }
+            thread::sleep(retry);
+            retry = (retry * 2).min(RETRY_MAX);
+        }
+        batch.clear();
+    }
+}
+
+impl FileSink {
+    /// append events, file is opened again for next attempt after error
+    fn write(&mut self, events: &[AuditEvent]) -> SimpleResult<()> {
+        let result = self.append(events);
+        if result.is_err() {
+            self.current = None;
+        }
+        result
+    }
+
+    fn append(&mut self, events: &[AuditEvent]) -> SimpleResult<()> {
+        let today = Local::today().naive_local();
+        let writer = self.writer(today)?;
+        for event in events {
+            let line = serde_json::to_string(event)
+                .map_err(|err| format!("can not serialize audit event: {}", err))?;
+            writeln!(writer, "{}", line)
+                .map_err(|err| format!("can not write audit file: {}", err))?;
+        }
+        writer.flush()
+            .map_err(|err| format!("can not write audit file: {}", err))
+    }
+
+    /// writer for current day, rotate file if day changed
+    fn writer(&mut self, today: NaiveDate) -> SimpleResult<&mut BufWriter<File>> {
+        let rotate = match &self.current {
+            Some((date, _)) => *date != today,
+            None => true
+        };
+
+        if rotate {
+            if let Some((_, mut writer)) = self.current.take() {
+                let _ = writer.flush();
+            }
+            let path = self.dir.join(format!("{}{}{}", FILE_PREFIX, today.format("%Y-%m-%d"), FILE_SUFFIX));
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .map_err(|err| format!("can not open audit file {:?}: {}", &path, err))?;
+            self.current = Some((today, BufWriter::new(file)));
+            self.remove_expired(today);
+        }
+
+        Ok(&mut self.current.as_mut().unwrap().1)
+    }
+
+    fn remove_expired(&self, today: NaiveDate) {
+        let retention_days = match self.retention_days {
+            Some(days) => days,
+            None => return
+        };
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(_) => return
+        };
+
+        for entry in entries.filter_map(|e| e.ok()) {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if !name.starts_with(FILE_PREFIX) || !name.ends_with(FILE_SUFFIX) {
+                continue;
+            }
+            let date = &name[FILE_PREFIX.len()..name.len() - FILE_SUFFIX.len()];
+            if let Ok(date) = NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+                if (today - date).num_days() > retention_days as i64 {
+                    if let Err(err) = fs::remove_file(entry.path()) {
+                        error!(self.log, "can not remove expired audit file"; "file" => %name, "error" => %err);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl TableSink {
+    /// insert events in one transaction, rolled back on error for next attempt
+    fn write(&mut self, events: &[AuditEvent]) -> SimpleResult<()> {
+        let conn = self.datasources.get_connection().map_err(|err| err.to_string())?;
+        let result = self.insert(&conn, events);
+        if result.is_err() {
+            let _ = conn.rollback();
+        }
+        result
+    }
+
+    fn insert(&mut self, conn: &crate::server::Connection, events: &[AuditEvent]) -> SimpleResult<()> {
+
+        let sql = format!(
+            "INSERT INTO {} (EVENT_TIME, USER_ID, ENDPOINT, ACTION, SCHEMA_NAME, TABLE_NAME, SENSITIVITY, ROW_COUNT, DETAILS) \
+            VALUES (:1, :2, :3, :4, :5, :6, :7, :8, :9)",
+            &self.name
+        );
+        let mut stmt = conn.prepare(&sql, &[])
+            .map_err(|err| format!("prepare stmt for audit err: {:?}", err))?;
+
+        for event in events {
+            let details = serde_json::to_string(event).map_err(|err| err.to_string())?;
+
+            stmt.execute(&[
+                &event.timestamp,
+                &event.user_id,
+                &event.endpoint,
+                &event.action.as_str(),
+                &event.schema,
+                &event.table,
+                &event.sensitivity.as_str(),
+                &(event.rows as u64),
+                &details
+            ]).map_err(|err| format!("insert audit event err: {:?}", err))?;
+        }
+
+        self.remove_expired(conn)?;
+
+        conn.commit()
+            .map_err(|err| format!("commit audit events err: {:?}", err))
+    }
+
+    /// delete expired events once a day
+    fn remove_expired(&mut self, conn: &crate::server::Connection) -> SimpleResult<()> {
+        let retention_days = match self.retention_days {
+            Some(days) => days,
+            None => return Ok(())
+        };
+        let today = Local::today().naive_local();
+        if self.cleaned == Some(today) {
+            return Ok(());
+        }
+
+        let sql = format!("DELETE FROM {} WHERE EVENT_TIME < SYSDATE - :1", &self.name);
+        conn.execute(&sql, &[&retention_days])
+            .map_err(|err| format!("delete expired audit events err: {:?}", err))?;
+        self.cleaned = Some(today);
+        Ok(())
+    }
+}
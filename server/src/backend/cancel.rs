@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use lazy_static::lazy_static;
+
+use super::BackendError;
+
+lazy_static! {
+    // one thread interrupts statements of all requests
+    static ref WATCHER: Watcher = Watcher::start();
+}
+
+struct Inner {
+    started:   Instant,
+    timeout:   Duration,
+    cancelled: Mutex<bool>,
+    condvar:   Condvar,
+}
+
+/// Timeout of statements of request and cancellation by caller, shared with executing thread
+#[derive(Clone)]
+pub struct Cancellation {
+    inner: Arc<Inner>,
+}
+
+/// Cancels statements in progress when dropped, e.g. with future of request of disconnected client
+pub struct CancelGuard {
+    cancellation: Cancellation,
+}
+
+/// Statement in progress, interrupted on cancellation or timeout
+struct Watched {
+    cancellation: Cancellation,
+    interrupt:    Box<dyn FnOnce() + Send>,
+}
+
+#[derive(Default)]
+struct WatcherState {
+    next_id:      u64,
+    watched:      HashMap<u64, Watched>,
+    interrupting: Option<u64>,
+}
+
+/// Thread, which waits for the nearest timeout of watched statements or for cancellation
+struct Watcher {
+    state:   Mutex<WatcherState>,
+    condvar: Condvar,
+}
+
+impl Watcher {
+    fn start() -> Watcher {
+        thread::Builder::new()
+            .name("statement-watcher".to_string())
+            .spawn(|| WATCHER.run())
+            .expect("statement watcher thread");
+        Watcher { state: Mutex::new(WatcherState::default()), condvar: Condvar::new() }
+    }
+
+    fn run(&self) {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            let interrupted = state.watched
+                .iter()
+                .find(|(_, w)| w.cancellation.is_interrupted())
+                .map(|(id, _)| *id);
+
+            if let Some(id) = interrupted {
+                let watched = state.watched.remove(&id).unwrap();
+                state.interrupting = Some(id);
+                drop(state);
+                (watched.interrupt)();
+                state = self.state.lock().unwrap();
+                state.interrupting = None;
+                self.condvar.notify_all();
+                continue;
+            }
+
+            let nearest = state.watched.values().map(|w| w.cancellation.remaining()).min();
+            state = match nearest {
+                Some(remaining) => self.condvar.wait_timeout(state, remaining).unwrap().0,
+                None => self.condvar.wait(state).unwrap()
+            };
+        }
+    }
+
+    fn add(&self, cancellation: Cancellation, interrupt: Box<dyn FnOnce() + Send>) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.watched.insert(id, Watched { cancellation, interrupt });
+        self.condvar.notify_all();
+        id
+    }
+
+    /// stop watching; interrupt in progress is finished before return
+    fn remove(&self, id: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.watched.remove(&id);
+        while state.interrupting == Some(id) {
+            state = self.condvar.wait(state).unwrap();
+        }
+    }
+
+    fn wake(&self) {
+        let _state = self.state.lock().unwrap();
+        self.condvar.notify_all();
+    }
+}
+
+impl Cancellation {
+    pub fn new(timeout: Duration) -> Cancellation {
+        let inner = Inner { started: Instant::now(), timeout, cancelled: Mutex::new(false), condvar: Condvar::new() };
+        Cancellation { inner: Arc::new(inner) }
+    }
+
+    pub fn guard(&self) -> CancelGuard {
+        CancelGuard { cancellation: self.clone() }
+    }
+
+    pub fn cancel(&self) {
+        *self.inner.cancelled.lock().unwrap() = true;
+        self.inner.condvar.notify_all();
+        WATCHER.wake();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.inner.cancelled.lock().unwrap()
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.inner.started.elapsed()
+    }
+
+    /// time left until timeout, zero after timeout
+    pub fn remaining(&self) -> Duration {
+        self.inner.timeout.saturating_sub(self.elapsed())
+    }
+
+    /// error of statement interrupted by cancellation or timeout
+    pub fn error(&self) -> BackendError {
+        if self.is_cancelled() {
+            BackendError::Cancelled
+        } else {
+            BackendError::Timeout { timeout: self.inner.timeout, elapsed: self.elapsed() }
+        }
+    }
+
+    /// interrupted state: cancelled or timed out
+    pub fn is_interrupted(&self) -> bool {
+        self.is_cancelled() || self.remaining().is_zero()
+    }
+
+    /// block until cancellation, timeout or end of `duration`; true if interrupted;
+    /// simulated execution of statements in tests
+    #[cfg(test)]
+    pub fn wait(&self, duration: Duration) -> bool {
+        let until = Instant::now() + duration.min(self.remaining());
+        let mut cancelled = self.inner.cancelled.lock().unwrap();
+        while !*cancelled {
+            let now = Instant::now();
+            if now >= until {
+                break;
+            }
+            cancelled = self.inner.condvar.wait_timeout(cancelled, until - now).unwrap().0;
+        }
+        drop(cancelled);
+        self.is_interrupted()
+    }
+
+    /// run blocking `work`, `interrupt` is called from watcher thread on cancellation or timeout
+    pub fn watch<T>(&self, interrupt: impl FnOnce() + Send + 'static, work: impl FnOnce() -> T) -> T {
+        let id = WATCHER.add(self.clone(), Box::new(interrupt));
+        let result = work();
+        WATCHER.remove(id);
+        result
+    }
+}
+
+impl Drop for CancelGuard {
+    fn drop(&mut self) {
+        self.cancellation.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn work_is_interrupted_by_timeout_or_cancellation() {
+        let cancellation = Cancellation::new(Duration::from_millis(50));
+        let interrupted = Arc::new(AtomicBool::new(false));
+        let interrupt = interrupted.clone();
+        // work runs until it is interrupted, as statement with break
+        let timed_out = cancellation.watch(
+            move || interrupt.store(true, Ordering::Release),
+            || {
+                while !interrupted.load(Ordering::Acquire) {
+                    thread::sleep(Duration::from_millis(1));
+                }
+                cancellation.is_interrupted()
+            }
+        );
+        assert!(timed_out);
+        assert!(interrupted.load(Ordering::Acquire));
+        assert!(matches!(cancellation.error(), BackendError::Timeout { .. }));
+
+        let cancellation = Cancellation::new(Duration::from_secs(10));
+        let guard = cancellation.guard();
+        let waiting = cancellation.clone();
+        let waiter = thread::spawn(move || waiting.wait(Duration::from_secs(10)));
+        drop(guard);
+        assert!(waiter.join().unwrap());
+        assert!(matches!(cancellation.error(), BackendError::Cancelled));
+
+        // fast work is not interrupted
+        let cancellation = Cancellation::new(Duration::from_secs(10));
+        let interrupted = Arc::new(AtomicBool::new(false));
+        let interrupt = interrupted.clone();
+        assert_eq!(cancellation.watch(move || interrupt.store(true, Ordering::Release), || 42), 42);
+        assert!(!interrupted.load(Ordering::Acquire));
+    }
+
+    #[test]
+    fn statements_are_interrupted_by_own_timeout() {
+        let run = |timeout: u64| thread::spawn(move || {
+            let cancellation = Cancellation::new(Duration::from_millis(timeout));
+            let interrupted = Arc::new(AtomicBool::new(false));
+            let interrupt = interrupted.clone();
+            cancellation.watch(move || interrupt.store(true, Ordering::Release), || {
+                while !interrupted.load(Ordering::Acquire) {
+                    thread::sleep(Duration::from_millis(1));
+                }
+                cancellation.elapsed()
+            })
+        });
+
+        let slow = run(300);
+        let fast = run(30);
+        assert!(fast.join().unwrap() < Duration::from_millis(300));
+        assert!(slow.join().unwrap() >= Duration::from_millis(300));
+    }
+}
@@ -0,0 +1,226 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::time::Duration;
+
+use chrono::{Local, TimeZone};
+
+use crate::metainfo::{MetaInfo, MetaInfoChanges, Rules};
+use crate::server::{DatasourceError, PoolState, DEFAULT_DATASOURCE};
+use crate::telemetry::Trace;
+use super::{Backend, BackendError, Cancellation, Row, Select, Value};
+
+/// metainfo of fixture schema `hr`, in format of snapshot
+const FIXTURE_METAINFO: &str = r#"{"schemas":{"hr":{"datasource":"default","entities":{
+    "departments":{
+        "entity_type":"Table","num_rows":3,"last_ddl_time":"2020-01-01T00:00:00",
+        "columns":[
+            {"name":"id","col_type":"Integer","sql_type":{"Number":[4,0]},"col_size":2,"nullable":false,"comment":null},
+            {"name":"name","col_type":"String","sql_type":{"Varchar2":30},"col_size":30,"nullable":false,"comment":"Name of department"}
+        ],
+        "primary_key":[0],
+        "indexes":[{"unique":true,"columns":[{"column_index":0,"desc":false}]}],
+        "foreign_keys":[],
+        "comment":"Departments"
+    },
+    "employees":{
+        "entity_type":"Table","num_rows":4,"last_ddl_time":"2020-01-01T00:00:00",
+        "columns":[
+            {"name":"id","col_type":"Integer","sql_type":{"Number":[6,0]},"col_size":4,"nullable":false,"comment":null},
+            {"name":"name","col_type":"String","sql_type":{"Varchar2":50},"col_size":50,"nullable":false,"comment":null},
+            {"name":"salary","col_type":"Number","sql_type":{"Number":[8,2]},"col_size":8,"nullable":true,"comment":"Monthly salary"},
+            {"name":"department_id","col_type":"Integer","sql_type":{"Number":[4,0]},"col_size":2,"nullable":true,"comment":null},
+            {"name":"hired","col_type":"DateTime","sql_type":"Date","col_size":8,"nullable":true,"comment":null}
+        ],
+        "primary_key":[0],
+        "indexes":[
+            {"unique":true,"columns":[{"column_index":0,"desc":false}]},
+            {"unique":false,"columns":[{"column_index":3,"desc":false}]}
+        ],
+        "foreign_keys":[{"columns":[3],"ref_schema":"hr","ref_entity":"departments","ref_columns":["id"]}],
+        "comment":"Employees"
+    },
+    "events":{
+        "entity_type":"Table","num_rows":5000000,"last_ddl_time":"2020-01-01T00:00:00",
+        "columns":[
+            {"name":"id","col_type":"Integer","sql_type":{"Number":[0,0]},"col_size":8,"nullable":false,"comment":null},
+            {"name":"kind","col_type":"String","sql_type":{"Varchar2":20},"col_size":20,"nullable":false,"comment":null}
+        ],
+        "primary_key":[0],
+        "indexes":[{"unique":true,"columns":[{"column_index":0,"desc":false}]}],
+        "foreign_keys":[],
+        "comment":"Large table without data"
+    },
+    "employees_v":{
+        "entity_type":"View","num_rows":null,"last_ddl_time":"2020-01-01T00:00:00",
+        "columns":[
+            {"name":"name","col_type":"String","sql_type":{"Varchar2":50},"col_size":50,"nullable":false,"comment":null}
+        ],
+        "primary_key":null,"indexes":[],"foreign_keys":[],"comment":null
+    }
+}}}}"#;
+
+/// In-memory backend with fixture schema, for tests without database
+pub struct MemoryBackend {
+    metainfo:  MetaInfo,
+    tables:    HashMap<(String, String), Vec<Vec<Value>>>,
+    exhausted: bool, // selects fail as with exhausted pool
+    delay:     Option<Duration>, // execution time of selects
+    selects:   AtomicUsize, // count of executed selects
+}
+
+impl MemoryBackend {
+    /// schema `hr` with departments, employees and large table of events
+    pub fn fixture() -> MemoryBackend {
+        let metainfo = serde_json::from_str(FIXTURE_METAINFO).expect("valid fixture metainfo");
+
+        let int = Value::Integer;
+        let text = |v: &str| Value::String(v.to_string());
+        let number = |v: &str| Value::Number(v.to_string());
+        let date = |y, m, d| Value::DateTime(Local.ymd(y, m, d).and_hms(9, 0, 0));
+
+        let mut tables = HashMap::new();
+        tables.insert(("hr".to_string(), "departments".to_string()), vec![
+            vec![int(10), text("Administration")],
+            vec![int(20), text("Research")],
+            vec![int(30), text("Sales")],
+        ]);
+        tables.insert(("hr".to_string(), "employees".to_string()), vec![
+            vec![int(1), text("King"), number("5000"), int(10), date(2001, 6, 17)],
+            vec![int(2), text("Blake"), number("2850.5"), int(30), date(2003, 5, 1)],
+            vec![int(3), text("Clark \"Junior\""), Value::Null, int(10), Value::Null],
+            vec![int(4), text("Scott"), number("3000"), int(20), date(2007, 4, 19)],
+        ]);
+        tables.insert(("hr".to_string(), "events".to_string()), Vec::new());
+        tables.insert(("hr".to_string(), "employees_v".to_string()), vec![
+            vec![text("King")],
+            vec![text("Blake")],
+        ]);
+
+        MemoryBackend { metainfo, tables, exhausted: false, delay: None, selects: AtomicUsize::new(0) }
+    }
+
+    /// fixture without free connections for selects
+    pub fn exhausted() -> MemoryBackend {
+        MemoryBackend { exhausted: true, ..MemoryBackend::fixture() }
+    }
+
+    /// fixture with slow selects, interrupted by timeout or cancellation
+    pub fn slow(delay: Duration) -> MemoryBackend {
+        MemoryBackend { delay: Some(delay), ..MemoryBackend::fixture() }
+    }
+
+    pub fn selects(&self) -> usize {
+        self.selects.load(AtomicOrdering::SeqCst)
+    }
+}
+
+impl Backend for MemoryBackend {
+    fn load_metainfo(&self, rules: &Rules) -> Result<MetaInfo, BackendError> {
+        let mut metainfo = self.metainfo.clone();
+        metainfo.apply_rules(rules);
+        Ok(metainfo)
+    }
+
+    fn refresh_metainfo(&self, previous: &MetaInfo, rules: &Rules) -> Result<(MetaInfo, MetaInfoChanges), BackendError> {
+        let metainfo = self.load_metainfo(rules)?;
+        let changes = MetaInfoChanges::between(previous, &metainfo.ddl_times());
+        Ok((metainfo, changes))
+    }
+
+    fn select(&self, datasource: &str, select: &Select, _trace: &Trace, cancellation: &Cancellation) -> Result<Vec<Row>, BackendError> {
+        if self.exhausted {
+            return Err(DatasourceError::Exhausted(datasource.to_string()).into());
+        }
+        self.selects.fetch_add(1, AtomicOrdering::SeqCst);
+        if let Some(delay) = self.delay {
+            if cancellation.wait(delay) {
+                return Err(cancellation.error());
+            }
+        }
+
+        let key = (select.schema_name.clone(), select.entity_name.clone());
+        let table = self.tables
+            .get(&key)
+            .ok_or_else(|| format!("table or view {}.{} does not exist", select.schema_name, select.entity_name))?;
+
+        let mut rows: Vec<&Vec<Value>> = table
+            .iter()
+            .filter(|row| select.params.iter().all(|p| matches(&row[p.column], &p.value)))
+            .filter(|row| select.keys.as_ref().is_none_or(|keys| keys.values
+                .iter()
+                .any(|key| key.iter().all(|p| matches(&row[p.column], &p.value)))))
+            .collect();
+
+        rows.sort_by(|a, b| select.order
+            .iter()
+            .map(|i| compare(&a[*i], &b[*i]))
+            .find(|o| *o != Ordering::Equal)
+            .unwrap_or(Ordering::Equal));
+
+        let offset = select.offset.unwrap_or(0) as usize;
+        let limit = select.limit as usize;
+        let rows: Vec<&Vec<Value>> = match &select.keys {
+            // rows of every key are paged separately
+            Some(keys) => {
+                let mut positions: HashMap<Vec<String>, usize> = HashMap::new();
+                rows.into_iter()
+                    .filter(|row| {
+                        let key = keys.columns.iter().map(|i| row[*i].to_json()).collect();
+                        let position = positions.entry(key).or_insert(0);
+                        *position += 1;
+                        *position > offset && *position <= offset + limit
+                    })
+                    .collect()
+            },
+            None => rows.into_iter().skip(offset).take(limit).collect()
+        };
+        Ok(rows.into_iter().map(|row| Row::new(row.clone())).collect())
+    }
+
+    fn datasources(&self) -> Vec<String> {
+        vec![DEFAULT_DATASOURCE.to_string()]
+    }
+
+    fn pool_state(&self, datasource: &str) -> Result<PoolState, BackendError> {
+        if datasource != DEFAULT_DATASOURCE {
+            return Err(DatasourceError::NotConfigured(datasource.to_string()).into());
+        }
+        let idle = if self.exhausted { 0 } else { 1 };
+        Ok(PoolState { connections: 1, idle, max_size: 1 })
+    }
+
+    fn ping(&self, datasource: &str, _timeout: Duration) -> Result<(), BackendError> {
+        if self.pool_state(datasource)?.is_exhausted() {
+            return Err(DatasourceError::Exhausted(datasource.to_string()).into());
+        }
+        Ok(())
+    }
+}
+
+/// equality of sql condition: null is not equal to anything
+fn matches(value: &Value, param: &Value) -> bool {
+    match (value, param) {
+        (Value::Null, _) | (_, Value::Null) => false,
+        (Value::Number(a), Value::Integer(b)) => a.parse::<f64>().ok() == Some(*b as f64),
+        _ => value == param
+    }
+}
+
+/// order of values of one column, nulls are last as in oracle
+fn compare(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Null, Value::Null) => Ordering::Equal,
+        (Value::Null, _) => Ordering::Greater,
+        (_, Value::Null) => Ordering::Less,
+        (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::DateTime(a), Value::DateTime(b)) => a.cmp(b),
+        (Value::Number(a), Value::Number(b)) => {
+            let a: f64 = a.parse().unwrap_or(f64::NAN);
+            let b: f64 = b.parse().unwrap_or(f64::NAN);
+            a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+        },
+        _ => Ordering::Equal
+    }
+}
@@ -0,0 +1,152 @@
+mod cancel;
+#[cfg(test)]
+mod memory;
+mod ora;
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Local};
+
+use crate::metainfo::{Entity, MetaInfo, MetaInfoChanges, Rules};
+use crate::server::{DatasourceError, PoolState};
+use crate::telemetry::Trace;
+
+#[cfg(test)]
+pub use memory::MemoryBackend;
+pub use ora::OracleBackend;
+pub use cancel::Cancellation;
+
+/// Database behind api: loading of metainfo and execution of prepared selects
+pub trait Backend: Send + Sync {
+    /// load all objects, which are exposed by rules
+    fn load_metainfo(&self, rules: &Rules) -> Result<MetaInfo, BackendError>;
+
+    /// reload only objects changed since previous metainfo
+    fn refresh_metainfo(&self, previous: &MetaInfo, rules: &Rules) -> Result<(MetaInfo, MetaInfoChanges), BackendError>;
+
+    /// execute select on datasource and fetch rows with values of all columns of entity, in spans of trace;
+    /// statement is interrupted by timeout or cancellation
+    fn select(&self, datasource: &str, select: &Select, trace: &Trace, cancellation: &Cancellation) -> Result<Vec<Row>, BackendError>;
+
+    /// names of all datasources, default first
+    fn datasources(&self) -> Vec<String>;
+
+    /// counts of connections in pool of datasource
+    fn pool_state(&self, datasource: &str) -> Result<PoolState, BackendError>;
+
+    /// round trip to database of datasource, fails after timeout
+    fn ping(&self, datasource: &str, timeout: Duration) -> Result<(), BackendError>;
+}
+
+/// Error of select: connection can not be acquired, query failed or was interrupted
+#[derive(Debug)]
+pub enum BackendError {
+    Datasource(DatasourceError),
+    Query(String),
+    Database { code: i32, message: String }, // ORA error of statement
+    Timeout { timeout: Duration, elapsed: Duration },
+    Cancelled, // caller is gone
+}
+
+/// Select of entity rows by values of columns, translated to query by backend
+pub struct Select {
+    pub schema_name: String,
+    pub entity_name: String,
+    pub entity:      Arc<Entity>, // snapshot of metainfo, stays valid while metainfo is reloaded
+    pub params:      Vec<Parameter>, // values of columns, compared by equality
+    pub order:       Vec<usize>,     // positions of columns
+    pub limit:       u32,
+    pub offset:      Option<u32>,
+    pub keys:        Option<Keys>,   // batch of keys, order, limit and offset apply to rows of every key
+}
+
+/// Values of key columns for rows of many parents in one select, row matches any of keys
+pub struct Keys {
+    pub columns: Vec<usize>,          // positions of columns
+    pub values:  Vec<Vec<Parameter>>, // values of columns for every key
+}
+
+/// Value of column for condition of select
+pub struct Parameter {
+    pub column:   usize, // position of column in entity
+    pub sql_type: oracle::sql_type::OracleType,
+    pub value:    Value,
+}
+
+/// Value of column in row
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Integer(i64),
+    Number(String), // decimal text, exact for any precision
+    String(String),
+    DateTime(DateTime<Local>),
+    Unsupported,
+}
+
+/// Fetched row, values are in order of entity columns
+pub struct Row {
+    values: Vec<Value>,
+}
+
+impl Row {
+    pub fn new(values: Vec<Value>) -> Row {
+        Row { values }
+    }
+
+    pub fn get(&self, column: usize) -> &Value {
+        self.values.get(column).unwrap_or(&Value::Null)
+    }
+}
+
+impl Value {
+    /// value as JSON literal
+    pub fn to_json(&self) -> String {
+        match self {
+            Value::Null => "null".to_string(),
+            Value::Integer(v) => v.to_string(),
+            Value::Number(v) => v.clone(),
+            // quotes and control characters must be escaped in JSON
+            Value::String(v) => serde_json::Value::String(v.clone()).to_string(),
+            Value::DateTime(v) => format!("\"{}\"", v.to_rfc3339()),
+            Value::Unsupported => "\"not-implemented\"".to_string(),
+        }
+    }
+}
+
+impl BackendError {
+    /// error of statement, ORA code is kept for status of response
+    pub fn statement(context: &str, err: oracle::Error) -> BackendError {
+        match &err {
+            oracle::Error::OciError(db) => BackendError::Database { code: db.code(), message: format!("{}: {}", context, err) },
+            _ => BackendError::Query(format!("{}: {}", context, err)),
+        }
+    }
+}
+
+impl From<DatasourceError> for BackendError {
+    fn from(err: DatasourceError) -> Self {
+        BackendError::Datasource(err)
+    }
+}
+
+impl From<String> for BackendError {
+    fn from(err: String) -> Self {
+        BackendError::Query(err)
+    }
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackendError::Datasource(err) => err.fmt(f),
+            BackendError::Query(err) => f.write_str(err),
+            BackendError::Database { message, .. } => f.write_str(message),
+            BackendError::Timeout { timeout, elapsed } =>
+                write!(f, "statement timed out after {} ms, timeout is {} ms", elapsed.as_millis(), timeout.as_millis()),
+            BackendError::Cancelled => f.write_str("statement cancelled"),
+        }
+    }
+}
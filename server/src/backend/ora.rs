@@ -0,0 +1,350 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+use itertools::Itertools;
+use oracle::sql_type::{OracleType, ToSql};
+use oracle::StmtParam;
+use slog::{debug, o, warn, Logger};
+
+use crate::metainfo::{self, Column, ColumnType, MetaInfo, MetaInfoChanges, Rules};
+use crate::metrics::Metrics;
+use crate::server::{Connection, Datasources, PoolState, SimpleResult};
+use crate::telemetry::{SpanKind, Trace};
+use super::{Backend, BackendError, Cancellation, Keys, Parameter, Row, Select, Value};
+
+// waiting for connection of pool is checked for cancellation in these intervals
+const CHECKOUT_POLL: Duration = Duration::from_millis(100);
+
+/// Production backend: oracle databases from pools of datasources
+pub struct OracleBackend {
+    datasources: Arc<Datasources>,
+    metrics:     Arc<Metrics>,
+    log:         Logger,
+}
+
+impl OracleBackend {
+    pub fn new(datasources: Arc<Datasources>, metrics: Arc<Metrics>, log: Logger) -> Self {
+        Self { datasources, metrics, log }
+    }
+
+    /// connection from pool, waits until timeout of statement or connection timeout of pool;
+    /// waiting ends, when caller is gone
+    fn checkout(&self, datasource: &str, cancellation: &Cancellation) -> Result<Connection, BackendError> {
+        let deadline = Instant::now() + self.datasources.connection_timeout(datasource)?;
+        loop {
+            if cancellation.is_interrupted() {
+                return Err(cancellation.error());
+            }
+            let wait = cancellation.remaining()
+                .min(deadline.saturating_duration_since(Instant::now()))
+                .min(CHECKOUT_POLL);
+            match self.datasources.get_connection_timeout(datasource, wait) {
+                Ok(conn) => return Ok(conn),
+                Err(_) if cancellation.is_interrupted() => return Err(cancellation.error()),
+                Err(err) if Instant::now() >= deadline => return Err(err.into()),
+                Err(_) => continue
+            }
+        }
+    }
+
+    /// execution of statement and fetch of all rows, in spans of trace
+    fn execute(&self, conn: &oracle::Connection, datasource: &str, sql: &str, select: &Select, trace: &Trace) -> Result<Vec<Row>, BackendError> {
+        let mut span = trace.span_of_kind("db.execute", SpanKind::Client);
+        span.attribute("db.system", "oracle");
+        span.attribute("db.name", datasource);
+        span.attribute("db.statement", sql);
+        // session of pooled connection is marked with trace of request for views of database, if possible
+        if let Err(err) = conn.set_action(&trace.trace_id().to_string())
+            .and_then(|_| conn.set_client_info(&span.trace().traceparent().to_string())) {
+            warn!(self.log, "can not set trace of session"; "datasource" => datasource, "error" => %err);
+        }
+        let mut stmt = conn.prepare(sql, &[StmtParam::FetchArraySize(select.limit)])
+            .map_err(|err| BackendError::statement("can not prepare statement", err))
+            .inspect_err(|err| span.error(err))?;
+
+        // binds of keys follow binds of params, as in `generate_sql`
+        let params_view: Vec<&dyn ToSql> = select.params
+            .iter()
+            .chain(select.keys.iter().flat_map(|keys| keys.values.iter().flatten()))
+            .map(|p| p as &dyn ToSql)
+            .collect();
+
+        let executed = Instant::now();
+        let rows = stmt
+            .query(&params_view[..])
+            .map_err(|err| BackendError::statement("can not dynamic query from statement", err))
+            .inspect_err(|err| span.error(err))?;
+        let fetched = Instant::now();
+        drop(span);
+
+        let mut span = trace.span("db.fetch");
+        let mut result = Vec::new();
+        for row in rows {
+            let row = row.map_err(|err| BackendError::statement("can not fetch query result", err))
+                .inspect_err(|err| span.error(err))?;
+            let values = select.entity.columns
+                .iter()
+                .enumerate()
+                .map(|(idx, column)| column_value(column, &row, idx))
+                .collect::<SimpleResult<Vec<Value>>>()?;
+            result.push(Row::new(values));
+        }
+        span.attribute("db.rows", result.len());
+        self.metrics.record_query(datasource, fetched - executed, fetched.elapsed());
+        Ok(result)
+    }
+}
+
+impl Backend for OracleBackend {
+    fn load_metainfo(&self, rules: &Rules) -> Result<MetaInfo, BackendError> {
+        metainfo::load(&self.datasources, rules, &self.log)
+    }
+
+    fn refresh_metainfo(&self, previous: &MetaInfo, rules: &Rules) -> Result<(MetaInfo, MetaInfoChanges), BackendError> {
+        metainfo::refresh(&self.datasources, previous, rules, &self.log)
+    }
+
+    fn select(&self, datasource: &str, select: &Select, trace: &Trace, cancellation: &Cancellation) -> Result<Vec<Row>, BackendError> {
+        let mut span = trace.span("pool.checkout");
+        span.attribute("db.name", datasource);
+        let started = Instant::now();
+        let conn = self.checkout(datasource, cancellation)
+            .inspect_err(|err| span.error(err))?;
+        self.metrics.record_pool_wait(datasource, started.elapsed());
+        drop(span);
+
+        let span = trace.span("sql.generate");
+        let sql = generate_sql(select);
+        drop(span);
+        debug!(self.log, "select"; "datasource" => datasource, "sql" => &sql, "binds" => select.params.len());
+
+        if cancellation.is_interrupted() {
+            return Err(cancellation.error());
+        }
+
+        // call timeout limits round trips, which can not be interrupted by break, e.g. with lost network;
+        // zero call timeout would disable timeout
+        conn.set_call_timeout(Some(cancellation.remaining().max(Duration::from_millis(1))))
+            .map_err(|err| format!("can not set call timeout: {}", err))?;
+        // connection is shared with watcher thread, which breaks execution
+        let conn = Arc::new(conn);
+        let interrupted = conn.clone();
+        let log = self.log.new(o!("datasource" => datasource.to_string()));
+        let result = cancellation.watch(
+            move || if let Err(err) = interrupted.break_execution() {
+                warn!(log, "can not break execution of statement"; "error" => %err);
+            },
+            || self.execute(&conn, datasource, &sql, select, trace)
+        );
+        // connection returns to pool for other statements, without trace of this request
+        if let Err(err) = conn.set_action("").and_then(|_| conn.set_client_info("")) {
+            warn!(self.log, "can not reset trace of session"; "datasource" => datasource, "error" => %err);
+        }
+        conn.set_call_timeout(None)
+            .map_err(|err| format!("can not reset call timeout: {}", err))?;
+
+        match result {
+            Err(_) if cancellation.is_interrupted() => {
+                let err = cancellation.error();
+                warn!(self.log, "select interrupted"; "datasource" => datasource, "error" => %err);
+                Err(err)
+            },
+            result => result
+        }
+    }
+
+    fn datasources(&self) -> Vec<String> {
+        self.datasources.names()
+    }
+
+    fn pool_state(&self, datasource: &str) -> Result<PoolState, BackendError> {
+        Ok(self.datasources.state(datasource)?)
+    }
+
+    fn ping(&self, datasource: &str, timeout: Duration) -> Result<(), BackendError> {
+        let started = Instant::now();
+        let conn = self.datasources.get_connection_timeout(datasource, timeout)?;
+
+        // zero call timeout would disable timeout
+        let remaining = timeout.saturating_sub(started.elapsed()).max(Duration::from_millis(1));
+        conn.set_call_timeout(Some(remaining))
+            .map_err(|err| format!("can not set call timeout: {}", err))?;
+        let result = conn.query_row_as::<i32>("SELECT 1 FROM DUAL", &[]);
+        // connection returns to pool for other statements
+        conn.set_call_timeout(None)
+            .map_err(|err| format!("can not reset call timeout: {}", err))?;
+
+        result
+            .map(|_| ())
+            .map_err(|err| BackendError::Query(format!("round trip to database failed: {}", err)))
+    }
+}
+
+/// sql of select with bind parameters for values of columns
+fn generate_sql(select: &Select) -> String {
+    let columns = &select.entity.columns;
+    let joined_result_columns = columns.iter().map(|c|&c.name).join(",");
+
+    let mut conditions: Vec<String> =
+    select.params.iter().enumerate().map(|(idx,p)|format!("{} = :{}", columns[p.column].name, idx+1)).collect();
+    if let Some(keys) = &select.keys {
+        conditions.push(keys_condition(select, keys));
+    }
+    let joined_param_columns = conditions.join(" AND ");
+
+    let mut source = format!("{}.{}", select.schema_name, select.entity_name);
+    if !conditions.is_empty() {
+        source.push_str(" WHERE ");
+        source.push_str(&joined_param_columns);
+    }
+
+    let joined_order_columns = select.order.iter().map(|i| &columns[*i].name).join(",");
+
+    if let Some(keys) = &select.keys {
+        // rows of every key are numbered in order and paged separately
+        let joined_key_columns = keys.columns.iter().map(|i| &columns[*i].name).join(",");
+        let window_order = if select.order.is_empty() { "NULL".to_string() } else { joined_order_columns };
+        let first = select.offset.unwrap_or(0);
+        return format!(
+            "SELECT {} FROM (SELECT {}, ROW_NUMBER() OVER (PARTITION BY {} ORDER BY {}) AS row_number_ FROM {}) \
+            WHERE row_number_ > {} AND row_number_ <= {} ORDER BY {},row_number_",
+            joined_result_columns, joined_result_columns, joined_key_columns, window_order, source,
+            first, first + select.limit, joined_key_columns
+        );
+    }
+
+    let mut sql = format!("SELECT {} FROM {}", joined_result_columns, source);
+    if !select.order.is_empty() {
+        let order_clause = format!(" ORDER BY {}", joined_order_columns);
+        sql.push_str(&order_clause);
+    }
+
+    if select.limit > 1 {
+        if let Some(offset) = select.offset {
+            let offset_clause = format!(" OFFSET {} ROWS", offset);
+            sql.push_str(&offset_clause);
+        }
+        let fetch_clause = format!(" FETCH NEXT {} ROWS ONLY", select.limit);
+        sql.push_str(&fetch_clause);
+    }
+    sql
+}
+
+/// `column IN (...)` for keys of one column, `(column, ...) IN ((...), ...)` for composite keys
+fn keys_condition(select: &Select, keys: &Keys) -> String {
+    let columns = &select.entity.columns;
+    let mut bind = select.params.len();
+    let tuples = keys.values
+        .iter()
+        .map(|key| {
+            let binds = key.iter().map(|_| { bind += 1; format!(":{}", bind) }).join(",");
+            if keys.columns.len() == 1 { binds } else { format!("({})", binds) }
+        })
+        .join(",");
+    let key_columns = keys.columns.iter().map(|i| &columns[*i].name).join(",");
+    if keys.columns.len() == 1 {
+        format!("{} IN ({})", key_columns, tuples)
+    } else {
+        format!("({}) IN ({})", key_columns, tuples)
+    }
+}
+
+/// value of column in fetched row by type of column
+fn column_value(column: &Column, rs: &oracle::Row, colidx: usize) -> SimpleResult<Value> {
+    let err = |err: oracle::Error| format!("can not read value of column {}: {}", column.name, err);
+    let value = match column.col_type {
+        ColumnType::String => {
+            // null strings are rendered as empty strings
+            let v: Option<String> = rs.get(colidx).map_err(err)?;
+            Value::String(v.unwrap_or_default())
+        },
+        // numbers are read as text, without loss of precision
+        ColumnType::Integer | ColumnType::Number => {
+            let v: Option<String> = rs.get(colidx).map_err(err)?;
+            v.map(Value::Number).unwrap_or(Value::Null)
+        },
+        ColumnType::DateTime => {
+            let v: Option<DateTime<Local>> = rs.get(colidx).map_err(err)?;
+            v.map(Value::DateTime).unwrap_or(Value::Null)
+        },
+        ColumnType::Unsupported => Value::Unsupported
+    };
+    Ok(value)
+}
+
+impl ToSql for Parameter {
+    fn oratype(&self, _conn: &oracle::Connection) -> oracle::Result<OracleType> {
+        Ok(self.sql_type.clone())
+    }
+
+    fn to_sql(&self, p: &mut oracle::SqlValue) -> oracle::Result<()> {
+        match &self.value {
+            Value::Integer(val) => val.to_sql(p),
+            Value::Number(val) | Value::String(val) => val.to_sql(p),
+            Value::DateTime(val) => val.to_sql(p),
+            Value::Null | Value::Unsupported => p.set_null(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MemoryBackend;
+
+    fn select(entity_name: &str, params: Vec<Parameter>, order: Vec<usize>, limit: u32, offset: Option<u32>) -> Select {
+        let metainfo = MemoryBackend::fixture().load_metainfo(&Rules::default()).unwrap();
+        let entity = metainfo.find_schema("hr").and_then(|s| s.find_entity(entity_name)).unwrap();
+        Select {
+            schema_name: "hr".to_string(),
+            entity_name: entity_name.to_string(),
+            entity: Arc::clone(entity),
+            params,
+            order,
+            limit,
+            offset,
+            keys: None
+        }
+    }
+
+    fn param(column: usize, value: Value) -> Parameter {
+        Parameter { column, sql_type: OracleType::Number(4, 0), value }
+    }
+
+    #[test]
+    fn sql_by_primary_key() {
+        let sql = generate_sql(&select("departments", vec![param(0, Value::Integer(10))], vec![], 1, None));
+        assert_eq!(sql, "SELECT id,name FROM hr.departments WHERE id = :1");
+    }
+
+    #[test]
+    fn sql_with_order_and_paging() {
+        let params = vec![param(3, Value::Integer(10)), param(1, Value::String("King".to_string()))];
+        let sql = generate_sql(&select("employees", params, vec![2, 0], 25, Some(50)));
+        assert_eq!(
+            sql,
+            "SELECT id,name,salary,department_id,hired FROM hr.employees \
+            WHERE department_id = :1 AND name = :2 ORDER BY salary,id OFFSET 50 ROWS FETCH NEXT 25 ROWS ONLY"
+        );
+    }
+
+    #[test]
+    fn sql_of_keys_with_paging_by_key() {
+        let mut select = select("employees", vec![param(1, Value::String("King".to_string()))], vec![2], 25, None);
+        select.keys = Some(Keys { columns: vec![3], values: vec![vec![param(3, Value::Integer(10))], vec![param(3, Value::Integer(20))]] });
+        assert_eq!(
+            generate_sql(&select),
+            "SELECT id,name,salary,department_id,hired FROM (SELECT id,name,salary,department_id,hired, \
+            ROW_NUMBER() OVER (PARTITION BY department_id ORDER BY salary) AS row_number_ \
+            FROM hr.employees WHERE name = :1 AND department_id IN (:2,:3)) \
+            WHERE row_number_ > 0 AND row_number_ <= 25 ORDER BY department_id,row_number_"
+        );
+    }
+
+    #[test]
+    fn sql_without_filter() {
+        let sql = generate_sql(&select("departments", vec![], vec![], 25, None));
+        assert_eq!(sql, "SELECT id,name FROM hr.departments FETCH NEXT 25 ROWS ONLY");
+    }
+}
@@ -0,0 +1,113 @@
+use std::path::Path;
+
+use crate::application;
+use crate::metainfo;
+use crate::server::{self, SimpleResult};
+
+const USAGE: &str = "\
+Usage: server [command]
+
+Without command starts server.
+
+Commands:
+    snapshot save <file>                   load metainfo from database and save it to snapshot
+    snapshot inspect <file> [schema]       print content of snapshot
+    diff <from> <to> [--json]              print differences between two snapshots
+    typescript <snapshot> <schema> <file>  write TypeScript interfaces (.d.ts) for tables of schema";
+
+/// run command from command line arguments
+pub fn run(args: &[String]) -> SimpleResult<()> {
+    let args: Vec<&str> = args.iter().map(|a| a.as_str()).collect();
+    match args.as_slice() {
+        ["snapshot", "save", file] => save_snapshot(file),
+        ["snapshot", "inspect", file] => inspect_snapshot(file, None),
+        ["snapshot", "inspect", file, schema] => inspect_snapshot(file, Some(schema)),
+        ["diff", from, to] => diff_snapshots(from, to, false),
+        ["diff", from, to, "--json"] => diff_snapshots(from, to, true),
+        ["typescript", snapshot, schema, file] => write_typescript(snapshot, schema, file),
+        _ => Err(USAGE.to_string())
+    }
+}
+
+fn save_snapshot(file: &str) -> SimpleResult<()> {
+    let config = server::load_config()
+        .map_err(|err| format!("Can not load config file: {}", err))?;
+    let datasources = server::Datasources::new(&config)?;
+    let log = server::setup_logging(&config.logging)?;
+
+    let rules = metainfo::Rules::new(&config.others)?;
+    let metainfo = metainfo::load(&datasources, &rules, &log).map_err(|err| err.to_string())?;
+    metainfo::save_snapshot(&metainfo, Path::new(file))?;
+    println!("Snapshot saved to {}", file);
+    Ok(())
+}
+
+fn inspect_snapshot(file: &str, schema_name: Option<&str>) -> SimpleResult<()> {
+    let snapshot = metainfo::load_snapshot(Path::new(file))?;
+    let metainfo = &snapshot.metainfo;
+
+    println!("version: {}", snapshot.version);
+    println!("created: {}", snapshot.created.to_rfc3339());
+
+    let summary = metainfo.summary();
+    println!("schemas: {}, entities: {}, columns: {}, primary keys: {}, indexes: {}",
+        summary.schemas, summary.entities, summary.columns, summary.primary_keys, summary.indexes);
+    println!();
+
+    match schema_name {
+        None => {
+            let mut schemas: Vec<_> = metainfo.schemas_iter().collect();
+            schemas.sort_by(|a, b| a.0.cmp(b.0));
+            for (name, schema) in schemas {
+                println!("{:<32} {:>6} entities", name, schema.entities_iter().count());
+            }
+        },
+        Some(schema_name) => {
+            let schema = metainfo.find_schema(&schema_name.to_lowercase())
+                .ok_or_else(|| format!("Schema {} not found in snapshot", schema_name))?;
+
+            let mut entities: Vec<_> = schema.entities_iter().collect();
+            entities.sort_by(|a, b| a.0.cmp(b.0));
+            for (name, entity) in entities {
+                let last_ddl_time = entity.last_ddl_time
+                    .map(|t| t.to_string())
+                    .unwrap_or_default();
+                println!("{:<32} {:<10} {:>4} columns  pk: {:<3} {:>2} indexes  {}",
+                    name,
+                    format!("{:?}", entity.entity_type),
+                    entity.columns.len(),
+                    if entity.primary_key.is_some() { "yes" } else { "no" },
+                    entity.indexes.len(),
+                    last_ddl_time);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn diff_snapshots(from: &str, to: &str, json: bool) -> SimpleResult<()> {
+    let from = metainfo::load_snapshot(Path::new(from))?;
+    let to = metainfo::load_snapshot(Path::new(to))?;
+
+    let diff = metainfo::diff(&from.metainfo, &to.metainfo);
+    if json {
+        let json = serde_json::to_string_pretty(&diff)
+            .map_err(|err| format!("Can not serialize diff: {}", err))?;
+        println!("{}", json);
+    } else {
+        print!("{}", diff);
+    }
+    Ok(())
+}
+
+fn write_typescript(snapshot: &str, schema_name: &str, file: &str) -> SimpleResult<()> {
+    let snapshot = metainfo::load_snapshot(Path::new(snapshot))?;
+    let schema = snapshot.metainfo.find_schema(&schema_name.to_lowercase())
+        .ok_or_else(|| format!("Schema {} not found in snapshot", schema_name))?;
+
+    let definitions = application::typescript_definitions(&schema_name.to_lowercase(), schema);
+    std::fs::write(file, definitions)
+        .map_err(|err| format!("Can not write {}: {}", file, err))?;
+    println!("TypeScript interfaces written to {}", file);
+    Ok(())
+}
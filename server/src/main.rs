@@ -1,15 +1,24 @@
 mod application;
+mod audit;
+mod backend;
+mod cli;
 mod metainfo;
+mod metrics;
 mod security;
 mod server;
+mod telemetry;
+
+#[cfg(test)]
+mod tests;
 
 use std::io::{Error, ErrorKind};
+use std::sync::Arc;
 
-use actix_web::{middleware, App, HttpServer};
+use actix_web::{middleware, App};
 use actix_web::http::ContentEncoding;
 use actix_slog::StructuredLogger;
 
-use slog::info;
+use slog::{info, o};
 
 // TODO: threadlocal: https://doc.rust-lang.org/std/macro.thread_local.html
 // TODO: authorization: roles and privileges
@@ -22,45 +31,74 @@ use slog::info;
 // rest api structure:
 //   /mgmt            management
 //       /health      health checking
+//       /health/live   liveness probe
+//       /health/ready  readiness probe: pools, round trip to databases, metainfo, tls certificate
 //       /schemas     metadata-catalog
+//       /metainfo    reload, changes and diff of metainfo
+//       /openapi.json  api description for clients
+//       /search      search of tables and columns
+//       /metrics     metrics in prometheus text format
 //   /api             web applications api
 //       /v1/schemas  tables / views / procedures
+//       /graphql     nested queries of tables by foreign keys
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let log = server::setup_logging();
-    info!(log, "Starting Foundation Server");
+    // commands for maintenance, without starting server
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        return cli::run(&args).map_err(Error::other);
+    }
 
     // configure server
     let config = server::load_config()
         .expect("Can not load config file");
 
-    let db_config = &config.connection;
-    server::create_datasource(db_config)
+    let log = server::setup_logging(&config.logging)
+        .map_err(Error::other)?;
+    info!(log, "Starting Foundation Server");
+
+    let datasources = server::Datasources::new(&config)
         .map_err(|e|Error::new(ErrorKind::Other, e))?;
 
-    let application = application::ApplicationState::load(&config)
+    let application = application::ApplicationState::load(&config, Arc::new(datasources), log.clone())
         .map_err(|e|Error::new(ErrorKind::Other, e))?;
 
     let http = &config.http;
-    let builder = server::setup_tls(&http);    
-    let identity_service = server::setup_identity(&config.jwt);
+    let acceptor = server::setup_tls(http)
+        .map_err(Error::other)?;
+    let identity_service = server::setup_identity(&config.jwt, application.metrics(), log.new(o!("component" => "identity")));
+    let request_metrics = metrics::RequestMetrics::new(application.metrics());
+    let tracer = telemetry::Tracer::start(&config.tracing, log.new(o!("component" => "tracing")))
+        .map_err(Error::other)?;
+    let request_tracing = telemetry::RequestTracing::new(tracer);
+    let apikey_service = server::setup_apikeys(&config.apikeys)
+        .map_err(Error::other)?;
+    let clientcert_service = server::setup_clientcert(http)
+        .map_err(Error::other)?;
+    let connection_service = clientcert_service.clone();
+
+    let limits = config.limits.as_ref();
+    let api_limits = security::RateLimit::new(limits.and_then(|l| l.api.as_ref()));
+    let mgmt_limits = security::RateLimit::new(limits.and_then(|l| l.mgmt.as_ref()));
 
     let listen = &http.listen;
     let listen = format!("{}:{}", &listen.domain, &listen.port);
+    let listener = std::net::TcpListener::bind(&listen)?;
     info!(log, "Server Started on https://{}", &listen);
 
-    HttpServer::new(move || {
+    server::https_server(listener, acceptor, connection_service, 75, move || {
         App::new()
             .data(application.clone())
             .wrap(StructuredLogger::new(log.clone()))
             .wrap(middleware::Compress::new(ContentEncoding::Br))
+            .wrap(clientcert_service.clone())
+            .wrap(apikey_service.clone())
             .wrap(identity_service.clone())
-            .service(application::base_scope())  
-            .service(application::v1_api_scope())
-    })
-        .keep_alive(75)
-        .bind_openssl(&listen, builder)?
-        .run()
+            .wrap(request_metrics.clone())
+            .wrap(request_tracing.clone())
+            .service(application::base_scope(mgmt_limits.clone()))
+            .service(application::v1_api_scope(api_limits.clone()))
+    })?
         .await
 }
@@ -0,0 +1,411 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::Serialize;
+
+use super::{Column, Entity, EntityName, EntityType, MetaInfo, TableIndex};
+
+/// Differences between two metainfos: what must be applied to `from` to get `to`
+#[derive(Default, Serialize)]
+pub struct MetaInfoDiff {
+    pub added:   Vec<EntityName>,
+    pub removed: Vec<EntityName>,
+    pub changed: Vec<EntityDiff>,
+}
+
+#[derive(Serialize)]
+pub struct EntityDiff {
+    pub schema: String,
+    pub entity: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entity_type: Option<Change<EntityType>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub added_columns: Vec<ColumnDef>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub removed_columns: Vec<ColumnDef>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub changed_columns: Vec<ColumnDiff>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub primary_key: Option<Change<Option<Vec<String>>>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub added_indexes: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub removed_indexes: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct ColumnDef {
+    pub name:     String,
+    pub sql_type: String,
+    pub size:     u16,
+    pub nullable: bool,
+}
+
+#[derive(Serialize)]
+pub struct ColumnDiff {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sql_type: Option<Change<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<Change<u16>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nullable: Option<Change<bool>>,
+}
+
+#[derive(Serialize)]
+pub struct Change<T> {
+    pub from: T,
+    pub to:   T,
+}
+
+fn change<T: PartialEq>(from: T, to: T) -> Option<Change<T>> {
+    if from == to {
+        None
+    } else {
+        Some(Change { from, to })
+    }
+}
+
+impl ColumnDef {
+    fn new(column: &Column) -> Self {
+        ColumnDef {
+            name: column.name.clone(),
+            sql_type: column.sql_type.to_string(),
+            size: column.col_size,
+            nullable: column.nullable
+        }
+    }
+}
+
+impl EntityDiff {
+    fn between(name: &EntityName, from: &Entity, to: &Entity) -> Option<Self> {
+        let mut added_columns = Vec::new();
+        let mut removed_columns = Vec::new();
+        let mut changed_columns = Vec::new();
+
+        for column in to.columns.iter() {
+            match from.columns.iter().find(|c| c.name == column.name) {
+                None => added_columns.push(ColumnDef::new(column)),
+                Some(previous) => {
+                    let diff = ColumnDiff {
+                        name: column.name.clone(),
+                        sql_type: change(previous.sql_type.to_string(), column.sql_type.to_string()),
+                        size: change(previous.col_size, column.col_size),
+                        nullable: change(previous.nullable, column.nullable),
+                    };
+                    if diff.sql_type.is_some() || diff.size.is_some() || diff.nullable.is_some() {
+                        changed_columns.push(diff);
+                    }
+                }
+            }
+        }
+        for column in from.columns.iter() {
+            if !to.columns.iter().any(|c| c.name == column.name) {
+                removed_columns.push(ColumnDef::new(column));
+            }
+        }
+
+        // indexes have no names in metainfo, compare their definitions; same definition may exist twice
+        let from_indexes: Vec<String> = from.indexes.iter().map(|i| index_definition(from, i)).collect();
+        let to_indexes: Vec<String> = to.indexes.iter().map(|i| index_definition(to, i)).collect();
+
+        let diff = EntityDiff {
+            schema: name.schema.clone(),
+            entity: name.entity.clone(),
+            entity_type: change(from.entity_type, to.entity_type),
+            added_columns,
+            removed_columns,
+            changed_columns,
+            primary_key: change(primary_key_columns(from), primary_key_columns(to)),
+            added_indexes: difference(&to_indexes, &from_indexes),
+            removed_indexes: difference(&from_indexes, &to_indexes),
+        };
+
+        if diff.is_empty() { None } else { Some(diff) }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.entity_type.is_none()
+            && self.added_columns.is_empty()
+            && self.removed_columns.is_empty()
+            && self.changed_columns.is_empty()
+            && self.primary_key.is_none()
+            && self.added_indexes.is_empty()
+            && self.removed_indexes.is_empty()
+    }
+}
+
+fn column_name(entity: &Entity, index: usize) -> String {
+    entity.columns
+        .get(index)
+        .map(|c| c.name.clone())
+        .unwrap_or_else(|| format!("#{}", index))
+}
+
+fn primary_key_columns(entity: &Entity) -> Option<Vec<String>> {
+    entity.primary_key
+        .as_ref()
+        .map(|pk| pk.iter().map(|i| column_name(entity, *i)).collect())
+}
+
+/// items of `a` not in `b`, counting duplicates, sorted
+fn difference(a: &[String], b: &[String]) -> Vec<String> {
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for item in b.iter() {
+        *counts.entry(item).or_insert(0) += 1;
+    }
+    let mut result: Vec<String> = a.iter()
+        .filter(|item| match counts.get_mut(item.as_str()) {
+            Some(count) if *count > 0 => { *count -= 1; false },
+            _ => true
+        })
+        .cloned()
+        .collect();
+    result.sort();
+    result
+}
+
+/// definition of index like `unique (id, created desc)`
+fn index_definition(entity: &Entity, index: &TableIndex) -> String {
+    let columns: Vec<String> = index.columns
+        .iter()
+        .map(|c| {
+            let name = column_name(entity, c.column_index);
+            if c.desc { format!("{} desc", name) } else { name }
+        })
+        .collect();
+    let unique = if index.unique { "unique " } else { "" };
+    format!("{}({})", unique, columns.join(", "))
+}
+
+/// compare all schemas and entities of two metainfos
+pub fn diff(from: &MetaInfo, to: &MetaInfo) -> MetaInfoDiff {
+    let mut result = MetaInfoDiff::default();
+
+    for (schema_name, schema) in to.schemas.iter() {
+        for (entity_name, entity) in schema.entities_iter() {
+            let name = EntityName::new(schema_name, entity_name);
+            let previous = from
+                .find_schema(schema_name)
+                .and_then(|s| s.find_entity(entity_name));
+            match previous {
+                None => result.added.push(name),
+                Some(previous) => {
+                    if let Some(diff) = EntityDiff::between(&name, previous, entity) {
+                        result.changed.push(diff);
+                    }
+                }
+            }
+        }
+    }
+
+    for (schema_name, schema) in from.schemas.iter() {
+        for (entity_name, _) in schema.entities_iter() {
+            let exists = to
+                .find_schema(schema_name)
+                .and_then(|s| s.find_entity(entity_name))
+                .is_some();
+            if !exists {
+                result.removed.push(EntityName::new(schema_name, entity_name));
+            }
+        }
+    }
+
+    result.added.sort();
+    result.removed.sort();
+    result.changed.sort_by(|a, b| (&a.schema, &a.entity).cmp(&(&b.schema, &b.entity)));
+    result
+}
+
+impl MetaInfoDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl fmt::Display for ColumnDef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let nullable = if self.nullable { "null" } else { "not null" };
+        write!(f, "{} {} {}", self.name, self.sql_type, nullable)
+    }
+}
+
+fn format_pk(pk: &Option<Vec<String>>) -> String {
+    match pk {
+        Some(columns) => format!("({})", columns.join(", ")),
+        None => "none".to_string()
+    }
+}
+
+/// human-readable report, one line per change
+impl fmt::Display for MetaInfoDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No differences");
+        }
+
+        for name in self.added.iter() {
+            writeln!(f, "+ {}.{}", name.schema, name.entity)?;
+        }
+        for name in self.removed.iter() {
+            writeln!(f, "- {}.{}", name.schema, name.entity)?;
+        }
+        for diff in self.changed.iter() {
+            writeln!(f, "~ {}.{}", diff.schema, diff.entity)?;
+            if let Some(Change { from, to }) = &diff.entity_type {
+                writeln!(f, "    type: {:?} -> {:?}", from, to)?;
+            }
+            for column in diff.added_columns.iter() {
+                writeln!(f, "    + column {}", column)?;
+            }
+            for column in diff.removed_columns.iter() {
+                writeln!(f, "    - column {}", column)?;
+            }
+            for column in diff.changed_columns.iter() {
+                write!(f, "    ~ column {}:", column.name)?;
+                if let Some(Change { from, to }) = &column.sql_type {
+                    write!(f, " type {} -> {}", from, to)?;
+                }
+                if let Some(Change { from, to }) = &column.size {
+                    write!(f, " size {} -> {}", from, to)?;
+                }
+                if let Some(Change { from, to }) = &column.nullable {
+                    write!(f, " nullable {} -> {}", from, to)?;
+                }
+                writeln!(f)?;
+            }
+            if let Some(Change { from, to }) = &diff.primary_key {
+                writeln!(f, "    ~ primary key {} -> {}", format_pk(from), format_pk(to))?;
+            }
+            for index in diff.added_indexes.iter() {
+                writeln!(f, "    + index {}", index)?;
+            }
+            for index in diff.removed_indexes.iter() {
+                writeln!(f, "    - index {}", index)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::backend::{Backend, MemoryBackend};
+    use crate::metainfo::{IndexColumn, Rules};
+
+    fn fixture() -> MetaInfo {
+        MemoryBackend::fixture().load_metainfo(&Rules::default()).unwrap()
+    }
+
+    fn employees(metainfo: &mut MetaInfo) -> &mut Entity {
+        let schema = metainfo.schemas.get_mut("hr").unwrap();
+        Arc::make_mut(schema.entities.get_mut("employees").unwrap())
+    }
+
+    fn index(unique: bool, columns: &[usize]) -> TableIndex {
+        TableIndex {
+            unique,
+            columns: columns.iter().map(|i| IndexColumn { column_index: *i, desc: false }).collect()
+        }
+    }
+
+    fn changed(from: &MetaInfo, to: &MetaInfo) -> EntityDiff {
+        let mut diff = diff(from, to);
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+        assert_eq!(diff.changed.len(), 1);
+        diff.changed.remove(0)
+    }
+
+    #[test]
+    fn same_metainfo_has_no_differences() {
+        let diff = diff(&fixture(), &fixture());
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "No differences\n");
+    }
+
+    #[test]
+    fn added_and_removed_entities() {
+        let from = fixture();
+        let mut to = fixture();
+        let schema = to.schemas.get_mut("hr").unwrap();
+        let events = schema.entities.remove("events").unwrap();
+        schema.entities.insert("audit".to_string(), events);
+
+        let diff = diff(&from, &to);
+        assert_eq!(diff.added, vec![EntityName::new("hr", "audit")]);
+        assert_eq!(diff.removed, vec![EntityName::new("hr", "events")]);
+        assert!(diff.changed.is_empty());
+        assert_eq!(diff.to_string(), "+ hr.audit\n- hr.events\n");
+    }
+
+    #[test]
+    fn added_dropped_and_changed_columns() {
+        let from = fixture();
+        let mut to = fixture();
+        let entity = employees(&mut to);
+        let mut email = entity.columns[1].clone();
+        email.name = "email".to_string();
+        entity.columns.remove(4); // hired
+        entity.columns[1].nullable = true;
+        entity.columns[1].col_size = 100;
+        entity.columns.push(email);
+
+        let diff = changed(&from, &to);
+        assert_eq!(diff.added_columns.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(), vec!["email"]);
+        assert_eq!(diff.removed_columns.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(), vec!["hired"]);
+        assert_eq!(diff.changed_columns.len(), 1);
+        let name = &diff.changed_columns[0];
+        assert_eq!(name.name, "name");
+        assert!(name.sql_type.is_none());
+        assert!(matches!(name.size, Some(Change { from: 50, to: 100 })));
+        assert!(matches!(name.nullable, Some(Change { from: false, to: true })));
+        assert!(diff.primary_key.is_none() && diff.added_indexes.is_empty() && diff.removed_indexes.is_empty());
+    }
+
+    #[test]
+    fn changed_primary_key() {
+        let from = fixture();
+        let mut to = fixture();
+        employees(&mut to).primary_key = Some(vec![0, 1]);
+
+        let diff = changed(&from, &to);
+        let pk = diff.primary_key.as_ref().unwrap();
+        assert_eq!(pk.from, Some(vec!["id".to_string()]));
+        assert_eq!(pk.to, Some(vec!["id".to_string(), "name".to_string()]));
+
+        employees(&mut to).primary_key = None;
+        let diff = changed(&from, &to);
+        assert_eq!(diff.primary_key.as_ref().unwrap().to, None);
+    }
+
+    #[test]
+    fn added_and_removed_indexes() {
+        let mut from = fixture();
+        employees(&mut from).indexes = vec![index(true, &[0]), index(false, &[3])];
+        let mut to = fixture();
+        employees(&mut to).indexes = vec![index(true, &[0]), index(false, &[1, 3])];
+
+        let diff = changed(&from, &to);
+        assert_eq!(diff.added_indexes, vec!["(name, department_id)".to_string()]);
+        assert_eq!(diff.removed_indexes, vec!["(department_id)".to_string()]);
+    }
+
+    #[test]
+    fn duplicate_indexes_are_counted() {
+        let mut from = fixture();
+        employees(&mut from).indexes = vec![index(true, &[0]), index(false, &[3])];
+        let mut to = fixture();
+        employees(&mut to).indexes = vec![index(true, &[0]), index(false, &[3]), index(false, &[3])];
+
+        let diff = changed(&from, &to);
+        assert_eq!(diff.added_indexes, vec!["(department_id)".to_string()]);
+        assert!(diff.removed_indexes.is_empty());
+
+        let diff = changed(&to, &from);
+        assert!(diff.added_indexes.is_empty());
+        assert_eq!(diff.removed_indexes, vec!["(department_id)".to_string()]);
+    }
+}
@@ -1,45 +1,126 @@
 use std::collections::HashMap;
+use std::sync::Arc;
+use chrono::NaiveDateTime;
 use itertools::Itertools;
-use oracle::{self,StmtParam, sql_type::OracleType};
+use oracle::{self,StmtParam, sql_type::{OracleType, ToSql}};
 
 use oracle_derive::RowValue;
 
 use super::{
     Column, 
+    ColumnStatistics,
     ColumnType, 
     Entity, 
     EntityType, 
+    ForeignKey,
     IndexColumn,
     TableIndex,
+    TableStatistics,
     Schema
 };
-use crate::server::{Connection, SimpleResult};
+use super::Rules;
+use crate::backend::BackendError;
+use crate::server::Connection;
 
+/// owners of exposed schemas, in upper case
 pub fn load_available_schemas(
     conn: &Connection,
-    others: &Option<crate::server::config::OthersConfig>,
-) -> SimpleResult<String> {
+    rules: &Rules,
+) -> Result<Vec<String>, BackendError> {
     let sql = "SELECT USERNAME FROM SYS.ALL_USERS WHERE ORACLE_MAINTAINED = 'N'";
     let rows = conn
         .query_as::<String>(sql, &[])
-        .map_err(|err| format!("query available users err: {:?}", err))?;
+        .map_err(|err| BackendError::statement("query available users", err))?;
 
     let mut owners = Vec::<String>::with_capacity(16);
 
     for row_result in rows {
-        let owner = row_result.map_err(|err| format!("fetch available users err: {:?}", err))?;
+        let owner = row_result.map_err(|err| BackendError::statement("fetch available users", err))?;
 
-        let mut to_push = true;
+        if rules.schema_allowed(&owner) {
+            owners.push(owner);
+        }
+    }
 
-        if let Some(others) = others {
-            to_push = others.excludes.iter().position(|s| s == &owner).is_none();
+    Ok(owners)
+}
+
+/// Objects to load: all objects of available schemas or only some objects
+pub enum Selection<'a> {
+    Schemas(&'a [String]),           // owners, in upper case
+    Objects(&'a [(String, String)]), // owner and name of object, in upper case
+}
+
+impl<'a> Selection<'a> {
+    /// sql condition for owner and object name columns, with bind parameters
+    fn condition(&self, owner_column: &str, name_column: &str) -> String {
+        self.condition_from(owner_column, name_column, 1)
+    }
+
+    /// sql condition with bind parameters numbered from `first`
+    fn condition_from(&self, owner_column: &str, name_column: &str, first: usize) -> String {
+        match self {
+            Selection::Schemas([]) | Selection::Objects([]) => "1 = 0".to_string(),
+            Selection::Schemas(owners) => {
+                let placeholders = (0..owners.len())
+                    .map(|i| format!(":{}", first + i))
+                    .join(",");
+                format!("{} IN ( {} )", owner_column, placeholders)
+            },
+            Selection::Objects(objects) => {
+                let placeholders = (0..objects.len())
+                    .map(|i| format!("(:{},:{})", first + 2 * i, first + 2 * i + 1))
+                    .join(",");
+                format!("({},{}) IN ( {} )", owner_column, name_column, placeholders)
+            }
         }
-        if to_push {
-            owners.push(format!("'{}'", owner));
+    }
+
+    /// values of bind parameters of condition
+    fn params(&self) -> Vec<&dyn ToSql> {
+        match self {
+            Selection::Schemas(owners) => owners
+                .iter()
+                .map(|owner| owner as &dyn ToSql)
+                .collect(),
+            Selection::Objects(objects) => objects
+                .iter()
+                .flat_map(|(owner, name)| vec![owner as &dyn ToSql, name as &dyn ToSql])
+                .collect(),
         }
     }
+}
 
-    Ok(owners.join(","))
+#[derive(RowValue)]
+struct OraObject {
+    owner: String,
+    object_name: String,
+    last_ddl_time: NaiveDateTime,
+}
+
+/// time of last DDL for tables and views, by schema and entity name
+pub fn load_ddl_times(
+    conn: &Connection,
+    selection: &Selection,
+) -> Result<HashMap<(String, String), NaiveDateTime>, BackendError> {
+    let sql = format!(
+        "SELECT OWNER, OBJECT_NAME, LAST_DDL_TIME FROM SYS.ALL_OBJECTS \
+        WHERE OBJECT_TYPE IN ('TABLE', 'VIEW') AND {}",
+        selection.condition("OWNER", "OBJECT_NAME")
+    );
+    let mut stmt = conn.prepare(&sql, &[StmtParam::FetchArraySize(10000)])
+        .map_err(|err| BackendError::statement("prepare stmt for ddl times", err))?;
+    let rows = stmt
+        .query_as::<OraObject>(&selection.params())
+        .map_err(|err| BackendError::statement("query ddl times", err))?;
+
+    let mut ddl_times = HashMap::with_capacity(4096);
+    for row_result in rows {
+        let o = row_result.map_err(|err| BackendError::statement("fetch ddl times", err))?;
+        ddl_times.insert((o.owner.to_lowercase(), o.object_name.to_lowercase()), o.last_ddl_time);
+    }
+
+    Ok(ddl_times)
 }
 
 #[derive(RowValue)]
@@ -53,8 +134,9 @@ struct OraTable {
 
 pub fn load_entities(
     conn: &Connection,
-    available_schemas: &str,
-) -> SimpleResult<HashMap<String, Schema>> {
+    selection: &Selection,
+    datasource: &str,
+) -> Result<HashMap<String, Schema>, BackendError> {
     let sql = format!(
         "SELECT OWNER, TABLE_NAME, TABLE_TYPE, NUM_ROWS, TEMPORARY FROM (
         SELECT OWNER, TABLE_NAME, 'TABLE' AS TABLE_TYPE, NUM_ROWS, TEMPORARY
@@ -62,19 +144,22 @@ pub fn load_entities(
         UNION
         SELECT OWNER, VIEW_NAME, 'VIEW' AS TABLE_TYPE, 0, 'N'
         FROM SYS.ALL_VIEWS
-        ) WHERE OWNER IN ( {} )
+        ) WHERE {}
         ORDER BY OWNER, TABLE_TYPE, TABLE_NAME",
-        available_schemas
+        selection.condition("OWNER", "TABLE_NAME")
     );
     let mut stmt = conn.prepare(&sql, &[StmtParam::FetchArraySize(1000)])
-        .map_err(|err| format!("prepare stmt for tables and viws err: {:?}", err))?;
+        .map_err(|err| BackendError::statement("prepare stmt for tables and viws", err))?;
     let rows = stmt
-        .query_as::<OraTable>(&[])
-        .map_err(|err| format!("query tables and viws err: {:?}", err))?;
+        .query_as::<OraTable>(&selection.params())
+        .map_err(|err| BackendError::statement("query tables and viws", err))?;
 
     let mut schemas = HashMap::with_capacity(64);
 
-    let grouped_entities = rows.filter_map(|r| r.ok()).group_by(|t| t.owner.clone());
+    let rows = rows
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| BackendError::statement("fetch tables and views", err))?;
+    let grouped_entities = rows.into_iter().group_by(|t| t.owner.clone());
     for (owner, row_result) in grouped_entities.into_iter() {
         let mut entities = HashMap::with_capacity(64);
 
@@ -98,17 +183,21 @@ pub fn load_entities(
             let indexes = Vec::new();
             entities.insert(
                 table_name,
-                Entity {
+                Arc::new(Entity {
                     entity_type,
                     columns,
                     num_rows: t.num_rows,
+                    last_ddl_time: None,
                     primary_key,
-                    indexes
-                },
+                    indexes,
+                    foreign_keys: Vec::new(),
+                    comment: None,
+                    statistics: None
+                }),
             );
         }
 
-        schemas.insert(owner.to_lowercase(), Schema { entities });
+        schemas.insert(owner.to_lowercase(), Schema { entities, datasource: datasource.to_string() });
     }
 
     Ok(schemas)
@@ -128,24 +217,27 @@ struct OraColumn {
 
 pub fn load_columns(
     conn: &Connection,
-    available_schemas: &str,
+    selection: &Selection,
     metainfo: &mut HashMap<String, Schema>,
-) -> SimpleResult<()> {
+) -> Result<(), BackendError> {
     let sql = format!(
         "SELECT OWNER, TABLE_NAME, COLUMN_NAME, DATA_TYPE, DATA_LENGTH, DATA_PRECISION, DATA_SCALE, NULLABLE \
-        FROM SYS.ALL_TAB_COLUMNS WHERE OWNER IN ( {} ) ORDER BY OWNER, TABLE_NAME, COLUMN_ID"
-        ,available_schemas
+        FROM SYS.ALL_TAB_COLUMNS WHERE {} ORDER BY OWNER, TABLE_NAME, COLUMN_ID"
+        ,selection.condition("OWNER", "TABLE_NAME")
     );
 
     let mut stmt = conn.prepare(&sql, &[StmtParam::FetchArraySize(10000)])
-        .map_err(|err| format!("prepare stmt for columns err: {:?}", err))?;
+        .map_err(|err| BackendError::statement("prepare stmt for columns", err))?;
 
     let rows = stmt
-        .query_as::<OraColumn>(&[])
-        .map_err(|err| format!("query columns err: {:?}", err))?;
+        .query_as::<OraColumn>(&selection.params())
+        .map_err(|err| BackendError::statement("query columns", err))?;
 
     // group columns by schema
-    let grouped_columns = rows.filter_map(|r| r.ok()).group_by(|t| t.owner.clone());
+    let rows = rows
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| BackendError::statement("fetch columns", err))?;
+    let grouped_columns = rows.into_iter().group_by(|t| t.owner.clone());
 
     for (owner, row_result) in grouped_columns.into_iter() {
         let schema_name = owner.to_lowercase();
@@ -156,7 +248,7 @@ pub fn load_columns(
 
             for (table_name, columns) in grouped_columns.into_iter() {
                 let table_name = table_name.to_lowercase();
-                let entity = schema.entities.get_mut(&table_name);
+                let entity = schema.entities.get_mut(&table_name).and_then(Arc::get_mut);
                 if let Some(entity) = entity {
                     for c in columns
                     {
@@ -211,6 +303,8 @@ pub fn load_columns(
                             sql_type,
                             col_size,
                             nullable,
+                            comment: None,
+                            statistics: None,
                         });
                     }
                 }
@@ -231,27 +325,30 @@ struct OraPrimaryKey {
 
 pub fn load_primary_keys(
     conn: &Connection,
-    available_schemas: &str,
+    selection: &Selection,
     metainfo: &mut HashMap<String, Schema>,
-) -> SimpleResult<()> {
+) -> Result<(), BackendError> {
     let sql = format!(
         "SELECT C.OWNER, C.TABLE_NAME, C.CONSTRAINT_NAME, CC.COLUMN_NAME \
         FROM SYS.ALL_CONSTRAINTS C \
         JOIN SYS.ALL_CONS_COLUMNS CC ON C.OWNER = CC.OWNER AND C.TABLE_NAME = CC.TABLE_NAME AND C.CONSTRAINT_NAME = CC.CONSTRAINT_NAME
-        WHERE C.OWNER IN ( {} ) AND C.CONSTRAINT_TYPE = 'P' AND C.STATUS = 'ENABLED'
+        WHERE {} AND C.CONSTRAINT_TYPE = 'P' AND C.STATUS = 'ENABLED'
         ORDER BY C.OWNER, C.TABLE_NAME, C.CONSTRAINT_NAME, CC.POSITION"
-        ,available_schemas
+        ,selection.condition("C.OWNER", "C.TABLE_NAME")
     );
 
     let mut stmt = conn.prepare(&sql, &[StmtParam::FetchArraySize(1000)])
-        .map_err(|err| format!("prepare stmt for primary keys err: {:?}", err))?;
+        .map_err(|err| BackendError::statement("prepare stmt for primary keys", err))?;
 
     let rows = stmt
-        .query_as::<OraPrimaryKey>(&[])
-        .map_err(|err| format!("query primary keys err: {:?}", err))?;
+        .query_as::<OraPrimaryKey>(&selection.params())
+        .map_err(|err| BackendError::statement("query primary keys", err))?;
 
     // group primary keys by schema
-    let grouped_keys = rows.filter_map(|r| r.ok()).group_by(|t| t.owner.clone());
+    let rows = rows
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| BackendError::statement("fetch primary keys", err))?;
+    let grouped_keys = rows.into_iter().group_by(|t| t.owner.clone());
 
     for (owner, row_result) in grouped_keys.into_iter() {
         let schema_name = owner.to_lowercase();
@@ -261,7 +358,7 @@ pub fn load_primary_keys(
             let grouped_keys = row_result.group_by(|t| (t.table_name.clone(), t.constraint_name.clone()));
             for ((table_name, _), key_columns) in grouped_keys.into_iter() {
                 let table_name = table_name.to_lowercase();
-                let entity = schema.entities.get_mut(&table_name);
+                let entity = schema.entities.get_mut(&table_name).and_then(Arc::get_mut);
                 if let Some(entity) = entity {
                     let column_indices: Vec<usize> = key_columns
                         .map(|c| {
@@ -291,27 +388,30 @@ struct OraIndex {
 
 pub fn load_indexes(
     conn: &Connection,
-    available_schemas: &str,
+    selection: &Selection,
     metainfo: &mut HashMap<String, Schema>,
-) -> SimpleResult<()> {
+) -> Result<(), BackendError> {
     let sql = format!(
         "SELECT C.TABLE_OWNER, C.TABLE_NAME, C.INDEX_NAME, C.UNIQUENESS, CC.COLUMN_NAME, CC.DESCEND \
         FROM SYS.ALL_INDEXES C \
         JOIN SYS.ALL_IND_COLUMNS CC ON C.TABLE_OWNER = CC.INDEX_OWNER AND C.INDEX_NAME = CC.INDEX_NAME
-        WHERE C.OWNER IN ( {} ) AND C.STATUS = 'VALID'
+        WHERE {} AND C.STATUS = 'VALID'
         ORDER BY C.TABLE_OWNER, C.TABLE_NAME, C.INDEX_NAME, CC.COLUMN_POSITION"
-        ,available_schemas
+        ,selection.condition("C.TABLE_OWNER", "C.TABLE_NAME")
     );
 
     let mut stmt = conn.prepare(&sql, &[StmtParam::FetchArraySize(1000)])
-        .map_err(|err| format!("prepare stmt for indexes err: {:?}", err))?;
+        .map_err(|err| BackendError::statement("prepare stmt for indexes", err))?;
 
     let rows = stmt
-        .query_as::<OraIndex>(&[])
-        .map_err(|err| format!("query indexes err: {:?}", err))?;
+        .query_as::<OraIndex>(&selection.params())
+        .map_err(|err| BackendError::statement("query indexes", err))?;
 
     // group indexes by schema
-    let grouped_indexes = rows.filter_map(|r| r.ok()).group_by(|t| t.owner.clone());
+    let rows = rows
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| BackendError::statement("fetch indexes", err))?;
+    let grouped_indexes = rows.into_iter().group_by(|t| t.owner.clone());
 
     for (owner, row_result) in grouped_indexes.into_iter() {
         let schema_name = owner.to_lowercase();
@@ -321,7 +421,7 @@ pub fn load_indexes(
             let grouped_indexes = row_result.group_by(|t| (t.table_name.clone(), t.index_name.clone(), t.uniqueness.clone()));
             for ((table_name, _,uniqueness), key_columns) in grouped_indexes.into_iter() {
                 let table_name = table_name.to_lowercase();
-                let entity = schema.entities.get_mut(&table_name);
+                let entity = schema.entities.get_mut(&table_name).and_then(Arc::get_mut);
                 if let Some(entity) = entity {
 
                     let columns: Vec<IndexColumn> = key_columns.map(|c| {
@@ -345,4 +445,253 @@ pub fn load_indexes(
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+#[derive(RowValue)]
+struct OraForeignKey {
+    owner: String,
+    table_name: String,
+    constraint_name: String,
+    column_name: String,
+    r_owner: String,
+    r_table_name: String,
+    r_column_name: String,
+}
+
+pub fn load_foreign_keys(
+    conn: &Connection,
+    selection: &Selection,
+    metainfo: &mut HashMap<String, Schema>,
+) -> Result<(), BackendError> {
+    let sql = format!(
+        "SELECT C.OWNER, C.TABLE_NAME, C.CONSTRAINT_NAME, CC.COLUMN_NAME, R.OWNER, R.TABLE_NAME, RC.COLUMN_NAME \
+        FROM SYS.ALL_CONSTRAINTS C \
+        JOIN SYS.ALL_CONS_COLUMNS CC ON C.OWNER = CC.OWNER AND C.TABLE_NAME = CC.TABLE_NAME AND C.CONSTRAINT_NAME = CC.CONSTRAINT_NAME \
+        JOIN SYS.ALL_CONSTRAINTS R ON C.R_OWNER = R.OWNER AND C.R_CONSTRAINT_NAME = R.CONSTRAINT_NAME \
+        JOIN SYS.ALL_CONS_COLUMNS RC ON R.OWNER = RC.OWNER AND R.CONSTRAINT_NAME = RC.CONSTRAINT_NAME AND RC.POSITION = CC.POSITION
+        WHERE {} AND C.CONSTRAINT_TYPE = 'R' AND C.STATUS = 'ENABLED'
+        ORDER BY C.OWNER, C.TABLE_NAME, C.CONSTRAINT_NAME, CC.POSITION"
+        ,selection.condition("C.OWNER", "C.TABLE_NAME")
+    );
+
+    let mut stmt = conn.prepare(&sql, &[StmtParam::FetchArraySize(1000)])
+        .map_err(|err| BackendError::statement("prepare stmt for foreign keys", err))?;
+
+    let rows = stmt
+        .query_as::<OraForeignKey>(&selection.params())
+        .map_err(|err| BackendError::statement("query foreign keys", err))?;
+
+    // group foreign keys by schema
+    let rows = rows
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| BackendError::statement("fetch foreign keys", err))?;
+    let grouped_keys = rows.into_iter().group_by(|t| t.owner.clone());
+
+    for (owner, row_result) in grouped_keys.into_iter() {
+        let schema_name = owner.to_lowercase();
+        let schema = metainfo.get_mut(&schema_name);
+        if let Some(schema) = schema {
+            // group by table_name and constraint name
+            let grouped_keys = row_result.group_by(|t| (t.table_name.clone(), t.constraint_name.clone()));
+            for ((table_name, _), key_columns) in grouped_keys.into_iter() {
+                let table_name = table_name.to_lowercase();
+                let entity = schema.entities.get_mut(&table_name).and_then(Arc::get_mut);
+                if let Some(entity) = entity {
+                    let key_columns: Vec<OraForeignKey> = key_columns.collect();
+                    let columns: Vec<usize> = key_columns
+                        .iter()
+                        .filter_map(|c| {
+                            let column_name = c.column_name.to_lowercase();
+                            entity.columns.iter().position(|c| c.name == column_name)
+                        })
+                        .collect();
+
+                    if columns.len() == key_columns.len() && !columns.is_empty() {
+                        let first = &key_columns[0];
+                        entity.foreign_keys.push(ForeignKey {
+                            columns,
+                            ref_schema: first.r_owner.to_lowercase(),
+                            ref_entity: first.r_table_name.to_lowercase(),
+                            ref_columns: key_columns.iter().map(|c| c.r_column_name.to_lowercase()).collect(),
+                        });
+                    }
+                }
+            }
+        };
+    }
+
+    Ok(())
+}
+
+#[derive(RowValue)]
+struct OraComment {
+    owner: String,
+    table_name: String,
+    column_name: Option<String>, // comment of table without column
+    comments: String,
+}
+
+pub fn load_comments(
+    conn: &Connection,
+    selection: &Selection,
+    metainfo: &mut HashMap<String, Schema>,
+) -> Result<(), BackendError> {
+    let sql = format!(
+        "SELECT OWNER, TABLE_NAME, CAST(NULL AS VARCHAR2(128)) AS COLUMN_NAME, COMMENTS \
+        FROM SYS.ALL_TAB_COMMENTS WHERE {} AND COMMENTS IS NOT NULL \
+        UNION ALL \
+        SELECT OWNER, TABLE_NAME, COLUMN_NAME, COMMENTS \
+        FROM SYS.ALL_COL_COMMENTS WHERE {} AND COMMENTS IS NOT NULL",
+        selection.condition("OWNER", "TABLE_NAME"),
+        selection.condition_from("OWNER", "TABLE_NAME", selection.params().len() + 1)
+    );
+    // condition is used twice
+    let mut params = selection.params();
+    params.extend(selection.params());
+
+    let mut stmt = conn.prepare(&sql, &[StmtParam::FetchArraySize(10000)])
+        .map_err(|err| BackendError::statement("prepare stmt for comments", err))?;
+
+    let rows = stmt
+        .query_as::<OraComment>(&params)
+        .map_err(|err| BackendError::statement("query comments", err))?;
+
+    for row_result in rows {
+        let c = row_result.map_err(|err| BackendError::statement("fetch comments", err))?;
+        let entity = metainfo
+            .get_mut(&c.owner.to_lowercase())
+            .and_then(|s| s.entities.get_mut(&c.table_name.to_lowercase()))
+            .and_then(Arc::get_mut);
+        if let Some(entity) = entity {
+            match c.column_name {
+                None => entity.comment = Some(c.comments),
+                Some(column_name) => {
+                    let column_name = column_name.to_lowercase();
+                    if let Some(column) = entity.columns.iter_mut().find(|col| col.name == column_name) {
+                        column.comment = Some(c.comments);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(RowValue)]
+struct OraTableStatistics {
+    owner: String,
+    table_name: String,
+    num_rows: Option<u32>,
+    last_analyzed: Option<NaiveDateTime>,
+    blocks: Option<u32>,
+    avg_row_len: Option<u32>,
+    partitioned: String,
+    stale_stats: Option<String>,
+}
+
+/// statistics and count of rows of tables; entities shared with previous metainfo are copied
+pub fn load_table_statistics(
+    conn: &Connection,
+    selection: &Selection,
+    metainfo: &mut HashMap<String, Schema>,
+) -> Result<(), BackendError> {
+    let sql = format!(
+        "SELECT S.OWNER, S.TABLE_NAME, S.NUM_ROWS, S.LAST_ANALYZED, S.BLOCKS, S.AVG_ROW_LEN, T.PARTITIONED, S.STALE_STATS \
+        FROM SYS.ALL_TAB_STATISTICS S \
+        JOIN SYS.ALL_TABLES T ON S.OWNER = T.OWNER AND S.TABLE_NAME = T.TABLE_NAME
+        WHERE {} AND S.OBJECT_TYPE = 'TABLE'"
+        ,selection.condition("S.OWNER", "S.TABLE_NAME")
+    );
+
+    let mut stmt = conn.prepare(&sql, &[StmtParam::FetchArraySize(1000)])
+        .map_err(|err| BackendError::statement("prepare stmt for table statistics", err))?;
+
+    let rows = stmt
+        .query_as::<OraTableStatistics>(&selection.params())
+        .map_err(|err| BackendError::statement("query table statistics", err))?;
+
+    for row_result in rows {
+        let s = row_result.map_err(|err| BackendError::statement("fetch table statistics", err))?;
+        let entity = metainfo
+            .get_mut(&s.owner.to_lowercase())
+            .and_then(|schema| schema.entities.get_mut(&s.table_name.to_lowercase()))
+            .map(Arc::make_mut);
+        if let Some(entity) = entity {
+            entity.num_rows = s.num_rows;
+            entity.statistics = Some(TableStatistics {
+                last_analyzed: s.last_analyzed,
+                blocks: s.blocks,
+                avg_row_len: s.avg_row_len,
+                partitioned: s.partitioned.trim() == "YES",
+                stale: s.stale_stats.as_deref() == Some("YES"),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(RowValue)]
+struct OraColumnStatistics {
+    owner: String,
+    table_name: String,
+    column_name: String,
+    num_distinct: Option<u64>,
+    num_nulls: Option<u64>,
+    low_value: Option<String>,
+    high_value: Option<String>,
+}
+
+/// statistics of columns; entities shared with previous metainfo are copied
+pub fn load_column_statistics(
+    conn: &Connection,
+    selection: &Selection,
+    metainfo: &mut HashMap<String, Schema>,
+) -> Result<(), BackendError> {
+    // low and high values are stored in internal format
+    let decode = |column: &str| format!(
+        "CASE C.DATA_TYPE \
+        WHEN 'NUMBER' THEN TO_CHAR(UTL_RAW.CAST_TO_NUMBER(S.{0})) \
+        WHEN 'VARCHAR2' THEN UTL_RAW.CAST_TO_VARCHAR2(S.{0}) \
+        WHEN 'CHAR' THEN UTL_RAW.CAST_TO_VARCHAR2(S.{0}) \
+        ELSE RAWTOHEX(S.{0}) END AS {0}",
+        column
+    );
+    let sql = format!(
+        "SELECT S.OWNER, S.TABLE_NAME, S.COLUMN_NAME, S.NUM_DISTINCT, S.NUM_NULLS, {}, {} \
+        FROM SYS.ALL_TAB_COL_STATISTICS S \
+        JOIN SYS.ALL_TAB_COLUMNS C ON S.OWNER = C.OWNER AND S.TABLE_NAME = C.TABLE_NAME AND S.COLUMN_NAME = C.COLUMN_NAME
+        WHERE {}"
+        ,decode("LOW_VALUE")
+        ,decode("HIGH_VALUE")
+        ,selection.condition("S.OWNER", "S.TABLE_NAME")
+    );
+
+    let mut stmt = conn.prepare(&sql, &[StmtParam::FetchArraySize(10000)])
+        .map_err(|err| BackendError::statement("prepare stmt for column statistics", err))?;
+
+    let rows = stmt
+        .query_as::<OraColumnStatistics>(&selection.params())
+        .map_err(|err| BackendError::statement("query column statistics", err))?;
+
+    for row_result in rows {
+        let s = row_result.map_err(|err| BackendError::statement("fetch column statistics", err))?;
+        let entity = metainfo
+            .get_mut(&s.owner.to_lowercase())
+            .and_then(|schema| schema.entities.get_mut(&s.table_name.to_lowercase()))
+            .map(Arc::make_mut);
+        if let Some(entity) = entity {
+            let column_name = s.column_name.to_lowercase();
+            if let Some(column) = entity.columns.iter_mut().find(|col| col.name == column_name) {
+                column.statistics = Some(ColumnStatistics {
+                    num_distinct: s.num_distinct,
+                    num_nulls: s.num_nulls,
+                    low_value: s.low_value,
+                    high_value: s.high_value,
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
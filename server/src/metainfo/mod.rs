@@ -1,29 +1,75 @@
+mod diff;
 mod loaders;
+mod rules;
+mod search;
+mod snapshot;
 
-use std::collections::HashMap;
-use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use chrono::{DateTime, Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use slog::{info, Logger};
 
-use crate::server::{get_connection, SimpleResult};
+use crate::backend::BackendError;
+use crate::server::{Connection, Datasources, DEFAULT_DATASOURCE};
 
+use loaders::Selection;
+
+pub use diff::{diff, MetaInfoDiff};
+pub use rules::Rules;
+pub use search::{search, SearchResult};
+pub use snapshot::{save_snapshot, load_snapshot};
+
+// more changed objects are loaded with full reload
+const MAX_INCREMENTAL_OBJECTS: usize = 500;
+
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct MetaInfo {
     schemas: HashMap<String,Schema>,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Schema {
     // name of schema allready in metainfo Map
-    entities: HashMap<String, Entity>,
+    entities: HashMap<String, Arc<Entity>>,
+    datasource: String, // name of datasource with this schema
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Entity {
     // name of entity allready in schema Map
     pub entity_type: EntityType,
     pub num_rows: Option<u32>,
+    pub last_ddl_time: Option<NaiveDateTime>,
     pub columns: Vec<Column>,
     pub primary_key: Option<Vec<usize>>, // positions of pk columns
-    pub indexes:     Vec<TableIndex>
+    pub indexes:     Vec<TableIndex>,
+    pub foreign_keys: Vec<ForeignKey>,
+    pub comment:     Option<String>,
+    pub statistics:  Option<TableStatistics>,
+}
+
+/// Optimizer statistics and storage of table
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TableStatistics {
+    pub last_analyzed: Option<NaiveDateTime>,
+    pub blocks:        Option<u32>,
+    pub avg_row_len:   Option<u32>, // in bytes
+    pub partitioned:   bool,
+    pub stale:         bool,
+}
+
+/// Count of loaded objects
+#[derive(Default, Serialize)]
+pub struct MetaInfoSummary {
+    pub schemas:      usize,
+    pub entities:     usize,
+    pub columns:      usize,
+    pub primary_keys: usize,
+    pub indexes:      usize,
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum EntityType {
     #[serde(alias="table")]
     Table, 
@@ -33,16 +79,28 @@ pub enum EntityType {
     Temporary
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Column {
     pub name: String,
     pub col_type: ColumnType,
+    #[serde(with = "snapshot::oracle_type")]
     pub sql_type: oracle::sql_type::OracleType,
     pub col_size: u16, // in bytes
-    pub nullable: bool
+    pub nullable: bool,
+    pub comment:  Option<String>,
+    pub statistics: Option<ColumnStatistics>,
 }
 
-#[derive(Clone, Copy, Debug, Serialize)]
+/// Optimizer statistics of column
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ColumnStatistics {
+    pub num_distinct: Option<u64>,
+    pub num_nulls:    Option<u64>,
+    pub low_value:    Option<String>, // decoded for numbers and strings, hex for other types
+    pub high_value:   Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum ColumnType {
     #[serde(alias="int")]
     Integer, 
@@ -56,134 +114,359 @@ pub enum ColumnType {
     Unsupported,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TableIndex {
     pub unique:  bool,
     pub columns: Vec<IndexColumn>
 }
 
-#[derive(Debug)]
+/// Reference to primary or unique key of other entity
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ForeignKey {
+    pub columns:     Vec<usize>, // positions of columns in entity
+    pub ref_schema:  String,
+    pub ref_entity:  String,
+    pub ref_columns: Vec<String>, // names of referenced columns
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct IndexColumn {
     pub column_index: usize,
     pub desc: bool
 }
 
-pub fn load(others: &Option<crate::server::config::OthersConfig>) -> SimpleResult<MetaInfo> {
-    // sleep for sinchronize log output
-    std::thread::sleep(std::time::Duration::from_millis(10));
-    println!();
-    println!("READING METAINFO FROM ORACLE...");
-
+pub fn load(datasources: &Datasources, rules: &Rules, log: &Logger) -> Result<MetaInfo, BackendError> {
+    info!(log, "reading metainfo from oracle");
     let start = chrono::offset::Local::now();
 
-    let conn = get_connection()?;
-
-    let available_schemas = loaders::load_available_schemas(&conn, others)?;
-
-    let mut schemas = loaders::load_entities(&conn, &available_schemas)?;
-    loaders::load_columns(&conn, &available_schemas, &mut schemas)?;
-    loaders::load_primary_keys(&conn, &available_schemas, &mut schemas)?;
-    loaders::load_indexes(&conn, &available_schemas, &mut schemas)?;
-
-    let mut schemas_count = 0;
-    let mut tables_count = 0;
-    let mut columns_count = 0;
-    let mut pks_count = 0;
-    let mut indexes_count = 0;
-
-    for (_,schema) in schemas.iter() {
-        for (_,entity) in schema.entities_iter() {
-            tables_count += 1;
-            columns_count += entity.columns.len();
-            indexes_count += entity.indexes.len();
-                        
-            if entity.primary_key.is_some() {
-                pks_count += 1;
+    let mut schemas = HashMap::new();
+    for source in available_sources(datasources, rules)? {
+        schemas.extend(load_objects(&source, &Selection::Schemas(&source.schemas), rules)?);
+    }
+
+    let metainfo = MetaInfo{schemas};
+    let summary = metainfo.summary();
+    let elapsed = chrono::offset::Local::now() - start;
+
+    info!(log, "metainfo loaded";
+        "schemas" => summary.schemas,
+        "entities" => summary.entities,
+        "columns" => summary.columns,
+        "primary_keys" => summary.primary_keys,
+        "indexes" => summary.indexes,
+        "elapsed_ms" => elapsed.num_milliseconds());
+
+    Ok(metainfo)
+}
+
+/// Connection to datasource with its available schemas
+struct Source {
+    name:    String,
+    conn:    Connection,
+    schemas: Vec<String>, // owners in upper case
+}
+
+/// available schemas of every datasource: named datasources have only mapped schemas,
+/// default datasource has all other schemas
+fn available_sources(datasources: &Datasources, rules: &Rules) -> Result<Vec<Source>, BackendError> {
+    let datasource_schemas = datasources.schemas();
+    let mapped: HashSet<&String> = datasource_schemas.iter().flat_map(|(_, schemas)| schemas.iter()).collect();
+
+    let mut sources = Vec::with_capacity(datasource_schemas.len());
+    for (name, mapped_schemas) in datasource_schemas.iter() {
+        let conn = datasources.get_named_connection(name)?;
+        let mut schemas = loaders::load_available_schemas(&conn, rules)?;
+        if name == DEFAULT_DATASOURCE {
+            schemas.retain(|s| !mapped.contains(s));
+        } else {
+            schemas.retain(|s| mapped_schemas.contains(s));
+        }
+        sources.push(Source { name: name.clone(), conn, schemas });
+    }
+    Ok(sources)
+}
+
+/// load entities with columns, keys and indexes, which are exposed by rules
+fn load_objects(source: &Source, selection: &Selection, rules: &Rules) -> Result<HashMap<String, Schema>, BackendError> {
+    let conn = &source.conn;
+    let mut schemas = loaders::load_entities(conn, selection, &source.name)?;
+    loaders::load_columns(conn, selection, &mut schemas)?;
+    loaders::load_primary_keys(conn, selection, &mut schemas)?;
+    loaders::load_indexes(conn, selection, &mut schemas)?;
+    loaders::load_foreign_keys(conn, selection, &mut schemas)?;
+    loaders::load_comments(conn, selection, &mut schemas)?;
+    load_statistics(conn, selection, &mut schemas)?;
+
+    let ddl_times = loaders::load_ddl_times(conn, selection)?;
+    for (schema_name, schema) in schemas.iter_mut() {
+        for (entity_name, entity) in schema.entities.iter_mut() {
+            if let Some(entity) = Arc::get_mut(entity) {
+                entity.last_ddl_time = ddl_times.get(&(schema_name.clone(), entity_name.clone())).copied();
+            }
+        }
+    }
+
+    apply_rules(&mut schemas, rules);
+    Ok(schemas)
+}
+
+/// statistics of tables and columns, with count of rows for scans without index
+fn load_statistics(conn: &Connection, selection: &Selection, schemas: &mut HashMap<String, Schema>) -> Result<(), BackendError> {
+    loaders::load_table_statistics(conn, selection, schemas)?;
+    loaders::load_column_statistics(conn, selection, schemas)
+}
+
+/// remove schemas, entities and columns, which are not exposed by rules
+fn apply_rules(schemas: &mut HashMap<String, Schema>, rules: &Rules) {
+    schemas.retain(|schema_name, _| rules.schema_allowed(schema_name));
+
+    for (schema_name, schema) in schemas.iter_mut() {
+        schema.entities.retain(|entity_name, _| rules.table_allowed(schema_name, entity_name));
+
+        for (entity_name, entity) in schema.entities.iter_mut() {
+            if let Some(entity) = Arc::get_mut(entity) {
+                entity.retain_columns(|c| rules.column_allowed(schema_name, entity_name, &c.name));
+            }
+        }
+    }
+
+    schemas.retain(|_, schema| !schema.entities.is_empty());
+}
+
+/// Incremental refresh: reload only objects created or changed (by `LAST_DDL_TIME`) since previous snapshot
+/// and remove dropped objects; unchanged entities are shared with previous snapshot.
+/// Gathering of statistics does not change `LAST_DDL_TIME`, so statistics of all entities are reloaded
+pub fn refresh(datasources: &Datasources, previous: &MetaInfo, rules: &Rules, log: &Logger) -> Result<(MetaInfo, MetaInfoChanges), BackendError> {
+    let sources = available_sources(datasources, rules)?;
+
+    let mut ddl_times = HashMap::new();
+    for source in sources.iter() {
+        ddl_times.extend(loaders::load_ddl_times(&source.conn, &Selection::Schemas(&source.schemas))?);
+    }
+    ddl_times.retain(|(schema_name, entity_name), _| rules.table_allowed(schema_name, entity_name));
+
+    let mut changes = MetaInfoChanges::between(previous, &ddl_times);
+
+    let objects: Vec<(String, String)> = changes.added
+        .iter()
+        .chain(changes.changed.iter())
+        .map(|n| (n.schema.to_uppercase(), n.entity.to_uppercase()))
+        .collect();
+
+    if objects.len() > MAX_INCREMENTAL_OBJECTS {
+        let metainfo = load(datasources, rules, log)?;
+        changes.full = true;
+        return Ok((metainfo, changes));
+    }
+
+    let mut schemas = previous.schemas.clone();
+
+    for dropped in changes.dropped.iter() {
+        if let Some(schema) = schemas.get_mut(&dropped.schema) {
+            schema.entities.remove(&dropped.entity);
+        }
+    }
+
+    for source in sources.iter() {
+        let source_objects: Vec<(String, String)> = objects
+            .iter()
+            .filter(|(owner, _)| source.schemas.contains(owner))
+            .cloned()
+            .collect();
+        if source_objects.is_empty() {
+            continue;
+        }
+
+        let loaded = load_objects(source, &Selection::Objects(&source_objects), rules)?;
+        for (schema_name, schema) in loaded {
+            match schemas.get_mut(&schema_name) {
+                Some(previous) => previous.entities.extend(schema.entities),
+                None => {
+                    schemas.insert(schema_name, schema);
+                }
             }
-            
         }
-        schemas_count += 1;
     }
 
-    println!();
-    println!("TOTAL:   {} schemas with {} tables & views and {} columns", schemas_count,  tables_count, columns_count);
-    println!("         {} tables with primary keys", pks_count);
-    println!("         {} indexes found", indexes_count);
+    for source in sources.iter() {
+        load_statistics(&source.conn, &Selection::Schemas(&source.schemas), &mut schemas)?;
+    }
 
-    let end = chrono::offset::Local::now();
-    let duration = end - start;
+    schemas.retain(|_, schema| !schema.entities.is_empty());
 
-    let seconds = duration.num_seconds();
-    let milliseconds = duration.num_milliseconds() - seconds * 1000;
+    Ok((MetaInfo{schemas}, changes))
+}
+
+/// Objects added, changed and dropped between two snapshots of metainfo
+#[derive(Clone, Serialize)]
+pub struct MetaInfoChanges {
+    pub timestamp: DateTime<Local>,
+    pub full:      bool, // metainfo was reloaded completely
+    pub added:     Vec<EntityName>,
+    pub changed:   Vec<EntityName>,
+    pub dropped:   Vec<EntityName>,
+}
 
-    println!();
-    println!("ELAPSED: {} seconds, {} milliseconds", seconds, milliseconds);
-    println!();
-        
-    Ok(MetaInfo{schemas})
+/// Qualified name of entity
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct EntityName {
+    pub schema: String,
+    pub entity: String,
+}
+
+impl EntityName {
+    fn new(schema: &str, entity: &str) -> Self {
+        EntityName { schema: schema.to_string(), entity: entity.to_string() }
+    }
+}
+
+impl MetaInfoChanges {
+    /// compare entities of snapshot with current time of last DDL for objects
+    pub fn between(previous: &MetaInfo, ddl_times: &HashMap<(String, String), NaiveDateTime>) -> Self {
+        let mut added = Vec::new();
+        let mut changed = Vec::new();
+        let mut dropped = Vec::new();
+
+        for ((schema_name, entity_name), ddl_time) in ddl_times.iter() {
+            let entity = previous
+                .find_schema(schema_name)
+                .and_then(|s| s.find_entity(entity_name));
+            match entity {
+                None => added.push(EntityName::new(schema_name, entity_name)),
+                Some(entity) => {
+                    if entity.last_ddl_time != Some(*ddl_time) {
+                        changed.push(EntityName::new(schema_name, entity_name));
+                    }
+                }
+            }
+        }
+
+        for (schema_name, schema) in previous.schemas.iter() {
+            for (entity_name, _) in schema.entities_iter() {
+                if !ddl_times.contains_key(&(schema_name.clone(), entity_name.clone())) {
+                    dropped.push(EntityName::new(schema_name, entity_name));
+                }
+            }
+        }
+
+        added.sort();
+        changed.sort();
+        dropped.sort();
+
+        MetaInfoChanges { timestamp: Local::now(), full: false, added, changed, dropped }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.dropped.is_empty()
+    }
+}
+
+impl Entity {
+    /// column is leading column of primary key or index, so it can be used for index access
+    pub fn is_indexed(&self, column: usize) -> bool {
+        let pk_leading = self.primary_key.as_ref().and_then(|pk| pk.first()) == Some(&column);
+        pk_leading || self.indexes.iter().any(|index| index.columns.first().map(|c| c.column_index) == Some(column))
+    }
+
+    /// remove columns and remap their positions; keys and indexes with removed columns are dropped
+    fn retain_columns(&mut self, keep: impl Fn(&Column) -> bool) {
+        let mut positions = Vec::with_capacity(self.columns.len());
+        let mut retained = 0;
+        for column in self.columns.iter() {
+            if keep(column) {
+                positions.push(Some(retained));
+                retained += 1;
+            } else {
+                positions.push(None);
+            }
+        }
+        if retained == self.columns.len() {
+            return;
+        }
+
+        let remap = |columns: &[usize]| columns.iter().map(|i| positions[*i]).collect::<Option<Vec<usize>>>();
+
+        self.columns.retain(|c| keep(c));
+        self.primary_key = self.primary_key.as_ref().and_then(|pk| remap(pk));
+        self.indexes.retain_mut(|index| {
+            index.columns
+                .iter_mut()
+                .all(|c| positions[c.column_index].map(|i| c.column_index = i).is_some())
+        });
+        self.foreign_keys.retain_mut(|fk| match remap(&fk.columns) {
+            Some(columns) => {
+                fk.columns = columns;
+                true
+            },
+            None => false
+        });
+    }
 }
 
 impl MetaInfo {
+    /// remove schemas, entities and columns, which are not exposed by rules
+    pub fn apply_rules(&mut self, rules: &Rules) {
+        apply_rules(&mut self.schemas, rules);
+    }
+
+    /// time of last DDL for all entities
+    pub fn ddl_times(&self) -> HashMap<(String, String), NaiveDateTime> {
+        let mut ddl_times = HashMap::new();
+        for (schema_name, schema) in self.schemas.iter() {
+            for (entity_name, entity) in schema.entities_iter() {
+                if let Some(ddl_time) = entity.last_ddl_time {
+                    ddl_times.insert((schema_name.clone(), entity_name.clone()), ddl_time);
+                }
+            }
+        }
+        ddl_times
+    }
+
     pub fn find_schema<'a,'s>(&'s self, name: &'a str) -> Option<&'s Schema> {
         self.schemas.get(name)
     }
 
+    /// name of datasource with schema
+    pub fn datasource(&self, schema_name: &str) -> &str {
+        self.schemas.get(schema_name).map(|s| s.datasource.as_str()).unwrap_or(DEFAULT_DATASOURCE)
+    }
+
     pub fn schema_names(&self) -> std::collections::hash_map::Keys<'_, String, Schema> {
         self.schemas.keys()
     }
-    /*
+
+    pub fn summary(&self) -> MetaInfoSummary {
+        let mut summary = MetaInfoSummary::default();
+
+        for (_,schema) in self.schemas.iter() {
+            for (_,entity) in schema.entities_iter() {
+                summary.entities += 1;
+                summary.columns += entity.columns.len();
+                summary.indexes += entity.indexes.len();
+
+                if entity.primary_key.is_some() {
+                    summary.primary_keys += 1;
+                }
+            }
+            summary.schemas += 1;
+        }
+        summary
+    }
     pub fn schemas_iter(&self) -> std::collections::hash_map::Iter<'_, String, Schema> {
         self.schemas.iter()
     }
-    */
 }
 
 impl Schema {
-    pub fn find_entity<'a,'s>(&'s self, name: &'a str) -> Option<&'s Entity> {
+    pub fn datasource(&self) -> &str {
+        &self.datasource
+    }
+
+    pub fn find_entity(&self, name: &str) -> Option<&Arc<Entity>> {
         self.entities.get(name)
     }
 
-    pub fn entities_iter(&self) -> std::collections::hash_map::Iter<'_, String, Entity> {
+    pub fn entities_iter(&self) -> std::collections::hash_map::Iter<'_, String, Arc<Entity>> {
         self.entities.iter()
     }
 }
-
-impl Column {
-    fn null_value_repr() -> String {
-        "null".to_string()
-    }
-
-    pub fn try_to_string(&self, rs: &oracle::Row, colidx: usize) -> String {
-        match self.col_type {
-            ColumnType::String => {
-                let v: String = rs.get(colidx).unwrap_or("".to_owned());
-                format!("\"{}\"",v)
-            },
-            ColumnType::Integer | ColumnType::Number => {
-                if self.nullable {
-                    let v: Option<String> = rs.get(colidx).unwrap();
-                    match v {
-                        None => Column::null_value_repr(),
-                        Some (v) => v
-                    }
-                } else {
-                    let v: String = rs.get(colidx).unwrap();
-                    v
-                }
-            },            
-            ColumnType::DateTime => {
-                if self.nullable {
-                    let v: Option<chrono::DateTime<chrono::Local>> = rs.get(colidx).unwrap();
-                    match v {
-                        None => Column::null_value_repr(),
-                        Some (v) => format!("\"{}\"", v.to_rfc3339())
-                    }
-                } else {
-                    let v: chrono::DateTime<chrono::Local> = rs.get(colidx).unwrap();
-                    format!("\"{}\"", v.to_rfc3339())
-                }
-            }
-            _ => "\"not-implemented\"".to_owned()
-        }
-    }
-}
\ No newline at end of file
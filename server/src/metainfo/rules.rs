@@ -0,0 +1,187 @@
+use regex::Regex;
+
+use crate::server::config::{ExposeRules, OthersConfig};
+use crate::server::SimpleResult;
+
+/// Compiled include and exclude patterns of one level
+#[derive(Default)]
+struct Patterns {
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+}
+
+impl Patterns {
+    fn new(include: &[String], exclude: &[String]) -> SimpleResult<Self> {
+        let compile = |patterns: &[String]| patterns.iter().map(|p| compile(p)).collect::<SimpleResult<Vec<_>>>();
+        Ok(Patterns { include: compile(include)?, exclude: compile(exclude)? })
+    }
+
+    /// object is allowed when any of its names is included and none is excluded
+    fn allows(&self, names: &[&str]) -> bool {
+        let matches = |patterns: &[Regex]| patterns.iter().any(|p| names.iter().any(|n| p.is_match(n)));
+        (self.include.is_empty() || matches(&self.include)) && !matches(&self.exclude)
+    }
+}
+
+/// `/regex/` or glob with `*` and `?`, always matched with whole name and case insensitive
+fn compile(pattern: &str) -> SimpleResult<Regex> {
+    let expression = if pattern.len() > 1 && pattern.starts_with('/') && pattern.ends_with('/') {
+        pattern[1..pattern.len() - 1].to_string()
+    } else {
+        pattern
+            .split('*')
+            .map(|part| part.split('?').map(regex::escape).collect::<Vec<_>>().join("."))
+            .collect::<Vec<_>>()
+            .join(".*")
+    };
+    Regex::new(&format!("(?i)^(?:{})$", expression))
+        .map_err(|err| format!("Invalid pattern {}: {}", pattern, err))
+}
+
+/// Rules for schemas, tables and columns, which are exposed by api
+#[derive(Default)]
+pub struct Rules {
+    excludes: Vec<String>, // exact names of schemas, case sensitive as before expose rules
+    schemas: Patterns,
+    tables:  Patterns,
+    columns: Patterns,
+}
+
+impl Rules {
+    pub fn new(others: &Option<OthersConfig>) -> SimpleResult<Rules> {
+        let others = match others {
+            Some(others) => others,
+            None => return Ok(Rules::default())
+        };
+        let default_rules = ExposeRules::default();
+        let rules = others.expose.as_ref().unwrap_or(&default_rules);
+
+        Ok(Rules {
+            excludes: others.excludes.clone(),
+            schemas: Patterns::new(&rules.include_schemas, &rules.exclude_schemas)?,
+            tables:  Patterns::new(&rules.include_tables, &rules.exclude_tables)?,
+            columns: Patterns::new(&rules.include_columns, &rules.exclude_columns)?,
+        })
+    }
+
+    pub fn schema_allowed(&self, schema: &str) -> bool {
+        !self.excludes.iter().any(|s| s == schema) && self.schemas.allows(&[schema])
+    }
+
+    pub fn table_allowed(&self, schema: &str, table: &str) -> bool {
+        self.schema_allowed(schema)
+            && self.tables.allows(&[table, &format!("{}.{}", schema, table)])
+    }
+
+    pub fn column_allowed(&self, schema: &str, table: &str, column: &str) -> bool {
+        self.columns.allows(&[
+            column,
+            &format!("{}.{}", table, column),
+            &format!("{}.{}.{}", schema, table, column)
+        ])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn others(excludes: &[&str], expose: ExposeRules) -> Option<OthersConfig> {
+        Some(OthersConfig {
+            excludes: strings(excludes),
+            expose: Some(expose),
+            refresh_interval: None,
+            snapshot: None,
+            snapshots_dir: None,
+            max_scan_rows: None,
+        })
+    }
+
+    fn rules(excludes: &[&str], expose: ExposeRules) -> Rules {
+        Rules::new(&others(excludes, expose)).unwrap()
+    }
+
+    fn strings(patterns: &[&str]) -> Vec<String> {
+        patterns.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn everything_allowed_without_rules() {
+        let rules = Rules::new(&None).unwrap();
+        assert!(rules.schema_allowed("HR"));
+        assert!(rules.table_allowed("HR", "EMPLOYEES"));
+        assert!(rules.column_allowed("HR", "EMPLOYEES", "SALARY"));
+    }
+
+    #[test]
+    fn globs_match_whole_name_case_insensitive() {
+        let globs = rules(&[], ExposeRules { exclude_tables: strings(&["*_BAK", "TMP?"]), ..Default::default() });
+        assert!(!globs.table_allowed("HR", "EMPLOYEES_BAK"));
+        assert!(!globs.table_allowed("HR", "employees_bak"));
+        assert!(!globs.table_allowed("HR", "TMP1"));
+        assert!(globs.table_allowed("HR", "TMP12"));
+        assert!(globs.table_allowed("HR", "EMPLOYEES_BAK_OLD"));
+        // dot of name is no wildcard
+        let dotted = rules(&[], ExposeRules { exclude_tables: strings(&["A.B"]), ..Default::default() });
+        assert!(!dotted.table_allowed("A", "B"));
+        assert!(dotted.table_allowed("X", "AxB"));
+    }
+
+    #[test]
+    fn regex_matches_whole_name() {
+        let rules = rules(&[], ExposeRules { exclude_schemas: strings(&["/COPIE.*/", "/APEX_[0-9]+/"]), ..Default::default() });
+        assert!(!rules.schema_allowed("COPIE"));
+        assert!(!rules.schema_allowed("copie_2020"));
+        assert!(!rules.schema_allowed("APEX_200100"));
+        assert!(rules.schema_allowed("APEX_PUBLIC"));
+        assert!(rules.schema_allowed("OLD_COPIE"));
+        let invalid = ExposeRules { include_tables: strings(&["/(/"]), ..Default::default() };
+        assert!(Rules::new(&others(&[], invalid)).is_err());
+    }
+
+    #[test]
+    fn includes_restrict_and_excludes_win() {
+        let rules = rules(&[], ExposeRules {
+            include_schemas: strings(&["HR", "SALES"]),
+            exclude_schemas: strings(&["SALES"]),
+            ..Default::default()
+        });
+        assert!(rules.schema_allowed("HR"));
+        assert!(!rules.schema_allowed("SALES"));
+        assert!(!rules.schema_allowed("SYS"));
+        assert!(!rules.table_allowed("SYS", "DUAL"));
+    }
+
+    #[test]
+    fn tables_by_schema_and_table() {
+        let rules = rules(&[], ExposeRules { exclude_tables: strings(&["HR.SECRETS", "AUDIT_*"]), ..Default::default() });
+        assert!(!rules.table_allowed("HR", "SECRETS"));
+        assert!(rules.table_allowed("SALES", "SECRETS"));
+        assert!(!rules.table_allowed("SALES", "AUDIT_LOG"));
+    }
+
+    #[test]
+    fn columns_by_table_and_schema() {
+        let rules = rules(&[], ExposeRules {
+            exclude_columns: strings(&["PASSWORD*", "EMPLOYEES.SALARY", "HR.EMPLOYEES.SSN"]),
+            ..Default::default()
+        });
+        assert!(!rules.column_allowed("HR", "USERS", "PASSWORD_HASH"));
+        assert!(!rules.column_allowed("HR", "EMPLOYEES", "SALARY"));
+        assert!(rules.column_allowed("HR", "CONTRACTS", "SALARY"));
+        assert!(!rules.column_allowed("HR", "EMPLOYEES", "SSN"));
+        assert!(rules.column_allowed("SALES", "EMPLOYEES", "SSN"));
+        assert!(rules.column_allowed("HR", "EMPLOYEES", "NAME"));
+    }
+
+    #[test]
+    fn legacy_excludes_are_exact_and_case_sensitive() {
+        let rules = rules(&["COPIE", "A.B"], ExposeRules::default());
+        assert!(!rules.schema_allowed("COPIE"));
+        assert!(!rules.table_allowed("COPIE", "EMPLOYEES"));
+        assert!(rules.schema_allowed("copie"));
+        assert!(rules.schema_allowed("COPIE2"));
+        assert!(!rules.schema_allowed("A.B"));
+        assert!(rules.schema_allowed("AxB"));
+    }
+}
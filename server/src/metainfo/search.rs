@@ -0,0 +1,298 @@
+use std::cmp::Ordering;
+
+use serde::Serialize;
+
+use super::MetaInfo;
+
+// shorter queries are not compared with typos
+const MIN_FUZZY_LENGTH: usize = 4;
+const MAX_FUZZY_DISTANCE: usize = 3;
+
+/// Kind of match, from best to worst
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchKind {
+    Exact,
+    Prefix,
+    Substring,
+    Fuzzy,
+}
+
+/// Table or view with matches in its name, comment or columns
+#[derive(Serialize)]
+pub struct SearchResult {
+    pub schema: String,
+    pub entity: String,
+    #[serde(rename = "match")]
+    pub best: MatchKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<Highlight>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<Highlight>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub columns: Vec<ColumnMatch>,
+}
+
+#[derive(Serialize)]
+pub struct ColumnMatch {
+    pub name: String,
+    #[serde(rename = "match")]
+    pub best: MatchKind,
+    pub highlight: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+/// Matched text, matched part is marked with `<em>`
+#[derive(Serialize)]
+pub struct Highlight {
+    #[serde(rename = "match")]
+    pub kind: MatchKind,
+    pub highlight: String,
+}
+
+/// search names and comments of entities and columns in all schemas, best matches first
+pub fn search(metainfo: &MetaInfo, query: &str, limit: usize) -> Vec<SearchResult> {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    let mut results = Vec::new();
+
+    for (schema_name, schema) in metainfo.schemas.iter() {
+        for (entity_name, entity) in schema.entities_iter() {
+            let name = match_text(entity_name, &query, true);
+            let comment = entity.comment.as_ref().and_then(|c| match_text(c, &query, false));
+
+            let mut columns: Vec<ColumnMatch> = entity.columns
+                .iter()
+                .filter_map(|column| {
+                    let name = match_text(&column.name, &query, true);
+                    let comment = column.comment.as_ref().and_then(|c| match_text(c, &query, false));
+                    let best = best_kind(&[&name, &comment])?;
+                    Some(ColumnMatch {
+                        name: column.name.clone(),
+                        best,
+                        highlight: name.map(|h| h.highlight).unwrap_or_else(|| escape(&column.name)),
+                        comment: comment.map(|h| h.highlight),
+                    })
+                })
+                .collect();
+            columns.sort_by(|a, b| a.best.cmp(&b.best).then_with(|| a.name.cmp(&b.name)));
+
+            let column_kind = columns.first().map(|c| c.best);
+            let best = match best_kind(&[&name, &comment]).into_iter().chain(column_kind).min() {
+                Some(best) => best,
+                None => continue
+            };
+
+            results.push(SearchResult {
+                schema: schema_name.clone(),
+                entity: entity_name.clone(),
+                best,
+                name,
+                comment,
+                columns
+            });
+        }
+    }
+
+    results.sort_by(compare_results);
+    results.truncate(limit);
+    results
+}
+
+fn best_kind(matches: &[&Option<Highlight>]) -> Option<MatchKind> {
+    matches.iter().filter_map(|m| m.as_ref().map(|h| h.kind)).min()
+}
+
+/// matches of entity itself are better than matches of its columns
+fn compare_results(a: &SearchResult, b: &SearchResult) -> Ordering {
+    let own = |r: &SearchResult| best_kind(&[&r.name, &r.comment]).is_none();
+    a.best.cmp(&b.best)
+        .then_with(|| own(a).cmp(&own(b)))
+        .then_with(|| (&a.schema, &a.entity).cmp(&(&b.schema, &b.entity)))
+}
+
+/// match query with text; names are compared with their parts separated by `_`, comments by words
+fn match_text(text: &str, query: &str, is_name: bool) -> Option<Highlight> {
+    let lowercase = text.to_lowercase();
+    // offsets in lowercase text are valid for original text only when lengths are equal
+    let same_offsets = lowercase.len() == text.len();
+
+    let (kind, range) = if lowercase == query {
+        (MatchKind::Exact, Some((0, text.len())))
+    } else if lowercase.starts_with(query) {
+        (MatchKind::Prefix, Some((0, query.len())))
+    } else if let Some(start) = lowercase.find(query) {
+        (MatchKind::Substring, Some((start, start + query.len())))
+    } else {
+        let word = fuzzy_match(&lowercase, query, is_name)?;
+        (MatchKind::Fuzzy, Some(word))
+    };
+
+    let highlight = match range {
+        Some((start, end)) if same_offsets => format!(
+            "{}<em>{}</em>{}",
+            escape(&text[..start]),
+            escape(&text[start..end]),
+            escape(&text[end..])
+        ),
+        _ => escape(text)
+    };
+    Some(Highlight { kind, highlight })
+}
+
+/// range of word with few typos in comparison with query
+fn fuzzy_match(text: &str, query: &str, is_name: bool) -> Option<(usize, usize)> {
+    let query_length = query.chars().count();
+    if query_length < MIN_FUZZY_LENGTH {
+        return None;
+    }
+    let max_distance = (query_length / 3).clamp(1, MAX_FUZZY_DISTANCE);
+
+    let is_separator = |c: char| if is_name { c == '_' } else { !c.is_alphanumeric() };
+
+    let mut start = None;
+    for (i, c) in text.char_indices().chain(std::iter::once((text.len(), '_'))) {
+        match (start, i == text.len() || is_separator(c)) {
+            (None, false) => start = Some(i),
+            (Some(word_start), true) => {
+                if distance(&text[word_start..i], query) <= max_distance {
+                    return Some((word_start, i));
+                }
+                start = None;
+            },
+            _ => {}
+        }
+    }
+
+    if is_name && distance(text, query) <= max_distance {
+        return Some((0, text.len()));
+    }
+    None
+}
+
+/// Levenshtein distance
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == *cb { 0 } else { 1 };
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn entity(comment: Option<&str>, columns: &[(&str, Option<&str>)]) -> serde_json::Value {
+        let columns: Vec<_> = columns.iter().map(|(name, comment)| json!({
+            "name": name, "col_type": "String", "sql_type": {"Varchar2": 20}, "col_size": 20, "nullable": true, "comment": comment
+        })).collect();
+        json!({
+            "entity_type": "Table", "columns": columns, "primary_key": null, "indexes": [], "foreign_keys": [], "comment": comment
+        })
+    }
+
+    fn metainfo() -> MetaInfo {
+        serde_json::from_value(json!({ "schemas": {
+            "shop": { "datasource": "default", "entities": {
+                "customer":        entity(Some("Customers of shop"), &[("id", None)]),
+                "customer_orders": entity(None, &[("id", None)]),
+                "old_customers":   entity(None, &[("id", None)]),
+                "kustomer":        entity(None, &[("id", None)]),
+                "orders":          entity(None, &[("id", None), ("customer", Some("Who ordered"))]),
+                "products":        entity(Some("Articles for sale"), &[("id", None), ("price", None)]),
+            }},
+        }})).unwrap()
+    }
+
+    fn names(results: &[SearchResult]) -> Vec<(&str, MatchKind)> {
+        results.iter().map(|r| (r.entity.as_str(), r.best)).collect()
+    }
+
+    #[test]
+    fn best_matches_first() {
+        let results = search(&metainfo(), " Customer ", 10);
+        assert_eq!(names(&results), vec![
+            ("customer", MatchKind::Exact),
+            ("orders", MatchKind::Exact), // match of column only
+            ("customer_orders", MatchKind::Prefix),
+            ("old_customers", MatchKind::Substring),
+            ("kustomer", MatchKind::Fuzzy),
+        ]);
+        let orders = &results[1];
+        assert!(orders.name.is_none() && orders.comment.is_none());
+        assert_eq!(orders.columns.len(), 1);
+        assert_eq!(orders.columns[0].highlight, "<em>customer</em>");
+        assert_eq!(orders.columns[0].comment, None);
+    }
+
+    #[test]
+    fn results_are_limited() {
+        assert_eq!(names(&search(&metainfo(), "customer", 2)), vec![("customer", MatchKind::Exact), ("orders", MatchKind::Exact)]);
+        assert!(search(&metainfo(), "   ", 10).is_empty());
+        assert!(search(&metainfo(), "nothing", 10).is_empty());
+    }
+
+    #[test]
+    fn comments_are_searched() {
+        let results = search(&metainfo(), "sale", 10);
+        assert_eq!(names(&results), vec![("products", MatchKind::Substring)]);
+        assert_eq!(results[0].comment.as_ref().unwrap().highlight, "Articles for <em>sale</em>");
+    }
+
+    #[test]
+    fn fuzzy_match_of_words() {
+        // one typo for each three characters
+        assert_eq!(fuzzy_match("order_itens", "items", true), Some((6, 11)));
+        assert_eq!(fuzzy_match("order_itnes", "items", true), None);
+        assert_eq!(fuzzy_match("list of custmers here", "customers", false), Some((8, 16)));
+        // names are split only by `_`, comments by all other characters
+        assert_eq!(fuzzy_match("price-list", "prise", true), None);
+        assert_eq!(fuzzy_match("price-list", "prise", false), Some((0, 5)));
+        // whole name with typos in separators
+        assert_eq!(fuzzy_match("order_item", "orderitem", true), Some((0, 10)));
+        // short queries are not compared with typos
+        assert_eq!(fuzzy_match("ord", "ore", true), None);
+    }
+
+    #[test]
+    fn highlight_of_match() {
+        let substring = match_text("Customer_Orders", "order", true).unwrap();
+        assert_eq!(substring.kind, MatchKind::Substring);
+        assert_eq!(substring.highlight, "Customer_<em>Order</em>s");
+
+        let escaped = match_text("a <b> & customer", "customer", false).unwrap();
+        assert_eq!(escaped.highlight, "a &lt;b&gt; &amp; <em>customer</em>");
+
+        // lowercase has other length, offsets are not valid for original text
+        let unicode = match_text("İstanbul", "stanbul", true).unwrap();
+        assert_eq!(unicode.kind, MatchKind::Substring);
+        assert_eq!(unicode.highlight, "İstanbul");
+    }
+
+    #[test]
+    fn levenshtein_distance() {
+        assert_eq!(distance("", "abc"), 3);
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("itmes", "items"), 2);
+        assert_eq!(distance("straße", "strasse"), 2);
+    }
+}
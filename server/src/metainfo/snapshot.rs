@@ -0,0 +1,174 @@
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use super::MetaInfo;
+use crate::server::SimpleResult;
+
+// increment on every change of metainfo structures
+pub const SNAPSHOT_VERSION: u32 = 5;
+
+/// Serialized metainfo, for fast startup without querying of data dictionary
+#[derive(Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version:  u32,
+    pub created:  DateTime<Local>,
+    pub metainfo: MetaInfo,
+}
+
+#[derive(Deserialize)]
+struct SnapshotHeader {
+    version: u32,
+}
+
+/// write snapshot to temporary file and replace previous snapshot with it
+pub fn save_snapshot(metainfo: &MetaInfo, path: &Path) -> SimpleResult<()> {
+    #[derive(Serialize)]
+    struct SnapshotRef<'a> {
+        version:  u32,
+        created:  DateTime<Local>,
+        metainfo: &'a MetaInfo,
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|err| format!("Can not create snapshot directory {:?}: {}", dir, err))?;
+    }
+
+    let tmp_path = path.with_extension("tmp");
+    let file = File::create(&tmp_path)
+        .map_err(|err| format!("Can not create snapshot {:?}: {}", &tmp_path, err))?;
+    let mut writer = BufWriter::new(file);
+
+    let snapshot = SnapshotRef { version: SNAPSHOT_VERSION, created: Local::now(), metainfo };
+    serde_json::to_writer(&mut writer, &snapshot)
+        .map_err(|err| format!("Can not write snapshot {:?}: {}", &tmp_path, err))?;
+    writer.flush()
+        .map_err(|err| format!("Can not write snapshot {:?}: {}", &tmp_path, err))?;
+
+    fs::rename(&tmp_path, path)
+        .map_err(|err| format!("Can not replace snapshot {:?}: {}", path, err))
+}
+
+/// read snapshot, snapshots of other versions are rejected
+pub fn load_snapshot(path: &Path) -> SimpleResult<Snapshot> {
+    let file = File::open(path)
+        .map_err(|err| format!("Can not open snapshot {:?}: {}", path, err))?;
+
+    let version_err = |version: u32| format!("Snapshot {:?} has version {}, expected {}", path, version, SNAPSHOT_VERSION);
+
+    match serde_json::from_reader::<_, Snapshot>(BufReader::new(file)) {
+        Ok(snapshot) if snapshot.version == SNAPSHOT_VERSION => Ok(snapshot),
+        Ok(snapshot) => Err(version_err(snapshot.version)),
+        Err(err) => {
+            // structures of other versions can not be read, report version if possible
+            let header = File::open(path)
+                .ok()
+                .and_then(|file| serde_json::from_reader::<_, SnapshotHeader>(BufReader::new(file)).ok());
+            match header {
+                Some(header) if header.version != SNAPSHOT_VERSION => Err(version_err(header.version)),
+                _ => Err(format!("Can not read snapshot {:?}: {}", path, err))
+            }
+        }
+    }
+}
+
+/// serialization of oracle types, which are used in metainfo
+pub mod oracle_type {
+    use oracle::sql_type::OracleType;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    enum SnapshotType {
+        Varchar2(u32),
+        Long,
+        Date,
+        Number(u8, i8),
+        UInt64,
+    }
+
+    pub fn serialize<S: Serializer>(value: &OracleType, serializer: S) -> Result<S::Ok, S::Error> {
+        let value = match value {
+            OracleType::Varchar2(size) => SnapshotType::Varchar2(*size),
+            OracleType::Long => SnapshotType::Long,
+            OracleType::Date => SnapshotType::Date,
+            OracleType::Number(p, s) => SnapshotType::Number(*p, *s),
+            OracleType::UInt64 => SnapshotType::UInt64,
+            other => return Err(serde::ser::Error::custom(format!("unsupported oracle type in snapshot: {}", other)))
+        };
+        value.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<OracleType, D::Error> {
+        let value = match SnapshotType::deserialize(deserializer)? {
+            SnapshotType::Varchar2(size) => OracleType::Varchar2(size),
+            SnapshotType::Long => OracleType::Long,
+            SnapshotType::Date => OracleType::Date,
+            SnapshotType::Number(p, s) => OracleType::Number(p, s),
+            SnapshotType::UInt64 => OracleType::UInt64,
+        };
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use oracle::sql_type::OracleType;
+
+    use super::*;
+    use crate::backend::{Backend, MemoryBackend};
+    use crate::metainfo::Rules;
+
+    fn snapshot_path(name: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("foundation-snapshot-{}", std::process::id()))
+            .join(name)
+    }
+
+    #[test]
+    fn saved_snapshot_is_loaded() {
+        let metainfo = MemoryBackend::fixture().load_metainfo(&Rules::default()).unwrap();
+        let path = snapshot_path("roundtrip.json");
+        save_snapshot(&metainfo, &path).unwrap();
+        let snapshot = load_snapshot(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(snapshot.version, SNAPSHOT_VERSION);
+        assert_eq!(serde_json::to_value(&snapshot.metainfo).unwrap(), serde_json::to_value(&metainfo).unwrap());
+        let employees = snapshot.metainfo.find_schema("hr").and_then(|s| s.find_entity("employees")).unwrap();
+        let types: Vec<_> = employees.columns.iter().map(|c| c.sql_type.clone()).collect();
+        assert_eq!(types, vec![
+            OracleType::Number(6, 0),
+            OracleType::Varchar2(50),
+            OracleType::Number(8, 2),
+            OracleType::Number(4, 0),
+            OracleType::Date,
+        ]);
+    }
+
+    #[test]
+    fn other_version_is_rejected() {
+        let path = snapshot_path("version.json");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        // structures of older versions may differ, version is still reported
+        fs::write(&path, r#"{"version":1,"created":"2020-01-01T00:00:00+01:00","metainfo":{"tables":[]}}"#).unwrap();
+        let err = load_snapshot(&path).err().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(err.contains(&format!("has version 1, expected {}", SNAPSHOT_VERSION)), "{}", err);
+    }
+
+    #[test]
+    fn unreadable_snapshot_is_rejected() {
+        let path = snapshot_path("broken.json");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "{\"metainfo\":").unwrap();
+        let err = load_snapshot(&path).err().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(err.starts_with("Can not read snapshot"), "{}", err);
+    }
+}
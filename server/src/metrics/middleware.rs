@@ -0,0 +1,139 @@
+use std::future::{Future, Ready, ready};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Poll, Context};
+use std::time::Instant;
+
+use actix_web::{Error, HttpRequest};
+use actix_web::dev::{ServiceRequest, ServiceResponse, Service, Transform};
+
+use super::Metrics;
+
+// route of requests, which are not matched by any resource
+const UNMATCHED: &str = "unmatched";
+
+pub struct RequestMetricsMiddleware<S> {
+    service: S,
+    metrics: Arc<Metrics>,
+}
+
+/// Schema and table of exposed entity, which was found by handler of request
+struct EntityLabels {
+    schema: String,
+    table:  String,
+}
+
+/// label metrics of request with entity; names from path are not labels before entity was found,
+/// otherwise every unknown name would create new series
+pub fn label_entity(req: &HttpRequest, schema: &str, table: &str) {
+    req.extensions_mut().insert(EntityLabels { schema: schema.to_string(), table: table.to_string() });
+}
+
+impl<S,B> Service for RequestMetricsMiddleware<S>
+    where
+        S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+        S::Future: 'static,
+        B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, ctx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(ctx)
+    }
+
+    fn call(&mut self, req: Self::Request) -> Self::Future {
+        let started = Instant::now();
+        let method = req.method().to_string();
+        // patterns instead of paths keep count of series small
+        let route = req.match_pattern().unwrap_or_else(|| UNMATCHED.to_string());
+        let metrics = self.metrics.clone();
+
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let res = fut.await;
+            match &res {
+                Ok(res) => {
+                    let extensions = res.request().extensions();
+                    let entity = extensions.get::<EntityLabels>();
+                    metrics.record_request(
+                        &method, &route, res.status().as_u16(),
+                        entity.map(|e| e.schema.as_str()), entity.map(|e| e.table.as_str()), started.elapsed()
+                    );
+                },
+                Err(err) => {
+                    let status = err.as_response_error().status_code();
+                    metrics.record_request(&method, &route, status.as_u16(), None, None, started.elapsed());
+                }
+            }
+            res
+        })
+    }
+}
+
+/// Latency and count of requests by route and status
+#[derive(Clone)]
+pub struct RequestMetrics {
+    metrics: Arc<Metrics>,
+}
+
+impl RequestMetrics {
+    pub fn new(metrics: Arc<Metrics>) -> Self {
+        Self { metrics }
+    }
+}
+
+impl <S,B> Transform<S> for RequestMetrics
+    where
+        S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+        S::Future: 'static,
+        B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RequestMetricsMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestMetricsMiddleware { service, metrics: self.metrics.clone() }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{test, web, App, HttpResponse};
+
+    use super::*;
+    use crate::server::config::MetricsConfig;
+
+    #[actix_rt::test]
+    async fn only_found_entities_are_labels() {
+        let metrics = Arc::new(Metrics::new(&Some(MetricsConfig { table_labels: vec!["hr".to_string()] })));
+        let mut app = test::init_service(
+            App::new()
+                .wrap(RequestMetrics::new(metrics.clone()))
+                .route("/{schema}/{table}", web::get().to(|req: HttpRequest, path: web::Path<(String, String)>| {
+                    let (schema, table) = path.into_inner();
+                    if table == "employees" {
+                        label_entity(&req, &schema, &table);
+                        HttpResponse::Ok().finish()
+                    } else {
+                        HttpResponse::NotFound().finish()
+                    }
+                }))
+        ).await;
+
+        for path in &["/hr/employees", "/hr/random1", "/hr/random2"] {
+            test::call_service(&mut app, test::TestRequest::get().uri(path).to_request()).await;
+        }
+
+        let text = metrics.render(&[]);
+        assert!(text.contains("foundation_http_requests_total{method=\"GET\",route=\"/{schema}/{table}\",status=\"200\",schema=\"hr\",table=\"employees\"} 1\n"));
+        assert!(text.contains("foundation_http_requests_total{method=\"GET\",route=\"/{schema}/{table}\",status=\"404\",schema=\"\",table=\"\"} 2\n"));
+        assert!(!text.contains("random"));
+    }
+}
@@ -0,0 +1,216 @@
+mod middleware;
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::server::config::MetricsConfig;
+use crate::server::PoolState;
+
+pub use middleware::{RequestMetrics, label_entity};
+
+// upper bounds of latency buckets in seconds
+const BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// names and values of labels of one series
+type Labels = Vec<(&'static str, String)>;
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; BUCKETS.len()], // not cumulative, summed up by rendering
+    sum:     f64,
+    count:   u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        if let Some(idx) = BUCKETS.iter().position(|b| seconds <= *b) {
+            self.buckets[idx] += 1;
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+type Counters = Mutex<BTreeMap<Labels, u64>>;
+type Histograms = Mutex<BTreeMap<Labels, Histogram>>;
+
+/// Counters and histograms of server, rendered in Prometheus text format
+#[derive(Default)]
+pub struct Metrics {
+    table_schemas: HashSet<String>, // schemas with tables as labels, in lower case
+    requests:      Histograms,
+    pool_wait:     Histograms,
+    query_execute: Histograms,
+    query_fetch:   Histograms,
+    rows:          Counters,
+    bytes:         Counters,
+    auth_failures: Counters,
+    audit_dropped: Counters,
+}
+
+impl Metrics {
+    pub fn new(config: &Option<MetricsConfig>) -> Metrics {
+        let table_schemas = config.iter()
+            .flat_map(|c| c.table_labels.iter())
+            .map(|s| s.to_lowercase())
+            .collect();
+        Metrics { table_schemas, ..Default::default() }
+    }
+
+    /// schema and table as labels, only for configured schemas
+    fn table_labels(&self, schema: Option<&str>, table: Option<&str>) -> Labels {
+        match (schema, table) {
+            (Some(schema), Some(table)) if self.table_schemas.contains(&schema.to_lowercase()) =>
+                vec![("schema", schema.to_string()), ("table", table.to_string())],
+            _ => vec![("schema", String::new()), ("table", String::new())]
+        }
+    }
+
+    /// request handled by route, route is pattern of resource
+    pub fn record_request(&self, method: &str, route: &str, status: u16, schema: Option<&str>, table: Option<&str>, elapsed: Duration) {
+        let mut labels = vec![("method", method.to_string()), ("route", route.to_string()), ("status", status.to_string())];
+        labels.extend(self.table_labels(schema, table));
+        observe(&self.requests, labels, elapsed);
+    }
+
+    /// time until connection was acquired from pool
+    pub fn record_pool_wait(&self, datasource: &str, elapsed: Duration) {
+        observe(&self.pool_wait, vec![("datasource", datasource.to_string())], elapsed);
+    }
+
+    /// time of query execution and of fetching all rows
+    pub fn record_query(&self, datasource: &str, execute: Duration, fetch: Duration) {
+        observe(&self.query_execute, vec![("datasource", datasource.to_string())], execute);
+        observe(&self.query_fetch, vec![("datasource", datasource.to_string())], fetch);
+    }
+
+    /// rows returned by api and size of serialized JSON
+    pub fn record_result(&self, schema: &str, table: &str, rows: usize, bytes: usize) {
+        let labels = self.table_labels(Some(schema), Some(table));
+        increment(&self.rows, labels.clone(), rows as u64);
+        increment(&self.bytes, labels, bytes as u64);
+    }
+
+    /// failed authentication, by reason
+    pub fn record_auth_failure(&self, reason: &str) {
+        increment(&self.auth_failures, vec![("reason", reason.to_string())], 1);
+    }
+
+    /// audit event, which could not be queued for writing
+    pub fn record_audit_dropped(&self) {
+        increment(&self.audit_dropped, Vec::new(), 1);
+    }
+
+    /// all metrics with current state of pools
+    pub fn render(&self, pools: &[(String, PoolState)]) -> String {
+        let mut out = String::new();
+
+        write_histogram(&mut out, "foundation_http_request_duration_seconds", "Latency of http requests", &self.requests);
+        let requests: BTreeMap<Labels, u64> = self.requests.lock().unwrap()
+            .iter()
+            .map(|(labels, h)| (labels.clone(), h.count))
+            .collect();
+        write_series(&mut out, "foundation_http_requests_total", "Count of http requests", "counter", &requests);
+
+        let gauge = |value: fn(&PoolState) -> u32| -> BTreeMap<Labels, u64> {
+            pools.iter().map(|(name, state)| (vec![("datasource", name.clone())], value(state) as u64)).collect()
+        };
+        write_series(&mut out, "foundation_pool_connections_in_use", "Connections of pool in use", "gauge", &gauge(|s| s.connections - s.idle));
+        write_series(&mut out, "foundation_pool_connections_idle", "Idle connections of pool", "gauge", &gauge(|s| s.idle));
+        write_series(&mut out, "foundation_pool_connections_max", "Max size of pool", "gauge", &gauge(|s| s.max_size));
+        write_histogram(&mut out, "foundation_pool_wait_seconds", "Time until connection was acquired from pool", &self.pool_wait);
+
+        write_histogram(&mut out, "foundation_query_execute_seconds", "Time of query execution", &self.query_execute);
+        write_histogram(&mut out, "foundation_query_fetch_seconds", "Time of fetching rows of query", &self.query_fetch);
+        write_series(&mut out, "foundation_rows_returned_total", "Rows returned by api", "counter", &self.rows.lock().unwrap());
+        write_series(&mut out, "foundation_bytes_serialized_total", "Bytes of serialized results", "counter", &self.bytes.lock().unwrap());
+        write_series(&mut out, "foundation_auth_failures_total", "Failed authentications by reason", "counter", &self.auth_failures.lock().unwrap());
+        write_series(&mut out, "foundation_audit_events_dropped_total", "Audit events, which could not be recorded", "counter", &self.audit_dropped.lock().unwrap());
+
+        out
+    }
+}
+
+fn observe(histograms: &Histograms, labels: Labels, elapsed: Duration) {
+    histograms.lock().unwrap().entry(labels).or_default().observe(elapsed);
+}
+
+fn increment(counters: &Counters, labels: Labels, value: u64) {
+    *counters.lock().unwrap().entry(labels).or_default() += value;
+}
+
+/// `{name="value",...}`, empty for series without labels
+fn format_labels(labels: &[(&str, String)]) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+    let pairs: Vec<String> = labels.iter()
+        .map(|(name, value)| {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+            format!("{}=\"{}\"", name, value)
+        })
+        .collect();
+    format!("{{{}}}", pairs.join(","))
+}
+
+fn write_series(out: &mut String, name: &str, help: &str, kind: &str, series: &BTreeMap<Labels, u64>) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    for (labels, value) in series {
+        let _ = writeln!(out, "{}{} {}", name, format_labels(labels), value);
+    }
+}
+
+fn write_histogram(out: &mut String, name: &str, help: &str, histograms: &Histograms) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} histogram", name);
+    for (labels, histogram) in histograms.lock().unwrap().iter() {
+        let mut cumulative = 0;
+        for (bound, count) in BUCKETS.iter().zip(histogram.buckets.iter()) {
+            cumulative += count;
+            let mut labels = labels.clone();
+            labels.push(("le", bound.to_string()));
+            let _ = writeln!(out, "{}_bucket{} {}", name, format_labels(&labels), cumulative);
+        }
+        let mut labels_inf = labels.clone();
+        labels_inf.push(("le", "+Inf".to_string()));
+        let _ = writeln!(out, "{}_bucket{} {}", name, format_labels(&labels_inf), histogram.count);
+        let _ = writeln!(out, "{}_sum{} {}", name, format_labels(labels), histogram.sum);
+        let _ = writeln!(out, "{}_count{} {}", name, format_labels(labels), histogram.count);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histograms_are_cumulative() {
+        let metrics = Metrics::new(&None);
+        metrics.record_query("default", Duration::from_millis(3), Duration::from_millis(30));
+        metrics.record_query("default", Duration::from_millis(40), Duration::from_secs(20));
+
+        let text = metrics.render(&[]);
+        assert!(text.contains("foundation_query_execute_seconds_bucket{datasource=\"default\",le=\"0.005\"} 1\n"));
+        assert!(text.contains("foundation_query_execute_seconds_bucket{datasource=\"default\",le=\"0.05\"} 2\n"));
+        assert!(text.contains("foundation_query_fetch_seconds_bucket{datasource=\"default\",le=\"10\"} 1\n"));
+        assert!(text.contains("foundation_query_fetch_seconds_bucket{datasource=\"default\",le=\"+Inf\"} 2\n"));
+        assert!(text.contains("foundation_query_execute_seconds_count{datasource=\"default\"} 2\n"));
+    }
+
+    #[test]
+    fn tables_are_labels_only_for_configured_schemas() {
+        let metrics = Metrics::new(&Some(MetricsConfig { table_labels: vec!["HR".to_string()] }));
+        metrics.record_result("hr", "employees", 2, 100);
+        metrics.record_result("billing", "invoices", 1, 50);
+        metrics.record_result("billing", "payments", 3, 70);
+
+        let text = metrics.render(&[]);
+        assert!(text.contains("foundation_rows_returned_total{schema=\"hr\",table=\"employees\"} 2\n"));
+        assert!(text.contains("foundation_rows_returned_total{schema=\"\",table=\"\"} 4\n"));
+        assert!(text.contains("foundation_bytes_serialized_total{schema=\"\",table=\"\"} 120\n"));
+    }
+}
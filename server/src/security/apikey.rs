@@ -0,0 +1,340 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::future::{Future, Ready, ready};
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::task::{Poll, Context};
+
+use actix_web::{Error, HttpMessage};
+use actix_web::dev::{ServiceRequest, ServiceResponse, Service, Transform};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer};
+
+use crate::security::SecurityContext;
+use crate::security::credentials::AuthError;
+
+const API_KEY_HEADER: &str = "X-API-Key";
+
+/// Keyring file with api keys for service-to-service clients.
+/// Keys stored as hex-encoded SHA-256 hashes, to generate: `echo -n "<key>" | sha256sum`
+#[derive(Deserialize)]
+struct ApiKeyring {
+    keys: Vec<ApiKey>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct ApiKey {
+    name:    String,
+    hash:    String,
+    user_id: u32, // unique, not 0
+    groups:  HashSet<String>,
+    expires: Option<DateTime<Utc>>,
+    #[serde(default)]
+    allowed_ips: Vec<IpRange>,
+}
+
+/// Range of ip addresses in CIDR notation, e.g. `10.10.0.0/16`
+struct IpRange {
+    addr:   IpAddr,
+    prefix: u8,
+}
+
+struct Inner {
+    keys: HashMap<String, ApiKey>, // hash => api key
+}
+
+impl Inner {
+    fn load(key_file: &str) -> Result<Self, String> {
+        let file = File::open(key_file)
+            .map_err(|err| format!("Can not open api keys file {}: {}", key_file, err))?;
+        let keyring: ApiKeyring = serde_yaml::from_reader(file)
+            .map_err(|err| format!("Can not parse api keys file {}: {}", key_file, err))?;
+        Self::new(keyring, key_file)
+    }
+
+    fn new(keyring: ApiKeyring, key_file: &str) -> Result<Self, String> {
+        let source = format!("api keys file {}", key_file);
+        super::check_user_ids(&source, keyring.keys.iter().map(|k| (k.name.as_str(), k.user_id)))?;
+
+        let keys = keyring.keys
+            .into_iter()
+            .map(|k| (k.hash.to_lowercase(), k))
+            .collect();
+        Ok(Self { keys })
+    }
+
+    fn authenticate(&self, key: &str, peer: Option<IpAddr>) -> Result<SecurityContext, AuthError> {
+        let hash = hex(&openssl::sha::sha256(key.as_bytes()));
+
+        let api_key = self.keys
+            .get(&hash)
+            .ok_or_else(|| AuthError::InvalidToken("Unknown api key".to_string()))?;
+
+        if let Some(expires) = api_key.expires {
+            if expires < Utc::now() {
+                return Err(AuthError::InvalidToken(format!("Api key {} expired", api_key.name)));
+            }
+        }
+
+        if !api_key.allowed_ips.is_empty() {
+            let allowed = peer.map(|ip| api_key.allowed_ips.iter().any(|r| r.contains(&ip))).unwrap_or(false);
+            if !allowed {
+                return Err(AuthError::InvalidToken(format!("Api key {} is not allowed from this address", api_key.name)));
+            }
+        }
+
+        Ok(SecurityContext::new(api_key.user_id, api_key.groups.clone()))
+    }
+}
+
+pub struct ApiKeyMiddleware<S> {
+    service: S,
+    inner: Arc<Inner>,
+}
+
+impl <S,B> ApiKeyMiddleware<S>
+    where
+        S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+        S::Future: 'static,
+        B: 'static,
+{
+    fn construct_context(&mut self, req: &ServiceRequest) -> Result<(), AuthError> {
+        // already authenticated with jwt
+        if req.extensions().get::<SecurityContext>().is_some() {
+            return Ok(());
+        }
+
+        match req.headers().get(API_KEY_HEADER) {
+            Some(value) => {
+                let key = value
+                    .to_str()
+                    .map_err(|_| AuthError::InvalidRequest("Api key contains non-ASCII characters".to_string()))?
+                    .trim();
+                let peer = req.peer_addr().map(|addr| addr.ip());
+
+                let context = self.inner.authenticate(key, peer)?;
+                req.extensions_mut().insert(context);
+                Ok(())
+            },
+            None => Ok(())
+        }
+    }
+}
+
+impl<S,B> Service for ApiKeyMiddleware<S>
+    where
+        S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+        S::Future: 'static,
+        B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, ctx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(ctx)
+    }
+
+    fn call(&mut self, req: Self::Request) -> Self::Future {
+        match self.construct_context(&req) {
+            Ok(_) => {
+                let fut = self.service.call(req);
+
+                Box::pin(async move {
+                    let res = fut.await?;
+                    Ok(res)
+                })
+            },
+            Err(err) => {
+                Box::pin(async { Err(err.into())})
+            }
+        }
+    }
+
+}
+
+/// Alternative authenticator for backend services without interactive login:
+/// accepts `X-API-Key` header and produces the same `SecurityContext` as `IdentityService`
+#[derive(Clone)]
+pub struct ApiKeyService {
+    inner: Arc<Inner>,
+}
+
+impl ApiKeyService {
+    pub fn load(key_file: &str) -> Result<Self, String> {
+        let inner = Arc::new(Inner::load(key_file)?);
+        Ok(Self { inner })
+    }
+
+    /// service without keys, when api keys are not configured
+    pub fn empty() -> Self {
+        let inner = Arc::new(Inner { keys: HashMap::new() });
+        Self { inner }
+    }
+}
+
+impl <S,B> Transform<S> for ApiKeyService
+    where
+        S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+        S::Future: 'static,
+        B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = ApiKeyMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ApiKeyMiddleware { service, inner: self.inner.clone() }))
+    }
+}
+
+impl IpRange {
+    fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.addr, ip) {
+            (IpAddr::V4(range), IpAddr::V4(ip)) => {
+                prefix_matches(&range.octets(), &ip.octets(), self.prefix)
+            },
+            (IpAddr::V6(range), IpAddr::V6(ip)) => {
+                prefix_matches(&range.octets(), &ip.octets(), self.prefix)
+            },
+            (IpAddr::V6(range), IpAddr::V4(ip)) => {
+                prefix_matches(&range.octets(), &ip.to_ipv6_mapped().octets(), self.prefix)
+            },
+            (IpAddr::V4(range), IpAddr::V6(ip)) => {
+                match ip.to_ipv4() {
+                    Some(ip) => prefix_matches(&range.octets(), &ip.octets(), self.prefix),
+                    None => false
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for IpRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, '/');
+        let addr: IpAddr = parts.next().unwrap_or_default().trim().parse()
+            .map_err(|err| format!("Invalid ip range {}: {}", s, err))?;
+        let max_prefix = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match parts.next() {
+            Some(prefix) => prefix.trim().parse::<u8>()
+                .map_err(|err| format!("Invalid prefix in ip range {}: {}", s, err))?,
+            None => max_prefix
+        };
+        if prefix > max_prefix {
+            return Err(format!("Invalid prefix in ip range {}: must be <= {}", s, max_prefix));
+        }
+        Ok(IpRange { addr, prefix })
+    }
+}
+
+impl<'de> Deserialize<'de> for IpRange {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+fn prefix_matches(range: &[u8], ip: &[u8], prefix: u8) -> bool {
+    let full_bytes = (prefix / 8) as usize;
+    let rest_bits = prefix % 8;
+
+    if range[..full_bytes] != ip[..full_bytes] {
+        return false;
+    }
+    if rest_bits == 0 {
+        return true;
+    }
+    let mask = 0xFFu8 << (8 - rest_bits);
+    range[full_bytes] & mask == ip[full_bytes] & mask
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(s: &str) -> IpRange {
+        s.parse().unwrap()
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn ipv4_ranges_match_by_prefix() {
+        let network = range("10.10.0.0/16");
+        assert!(network.contains(&ip("10.10.0.1")));
+        assert!(network.contains(&ip("10.10.255.255")));
+        assert!(!network.contains(&ip("10.11.0.1")));
+
+        // prefix inside of byte
+        let network = range("192.168.4.0/22");
+        assert!(network.contains(&ip("192.168.7.200")));
+        assert!(!network.contains(&ip("192.168.8.1")));
+
+        let host = range("127.0.0.1");
+        assert_eq!(host.prefix, 32);
+        assert!(host.contains(&ip("127.0.0.1")));
+        assert!(!host.contains(&ip("127.0.0.2")));
+        assert!(range("127.0.0.1/32").contains(&ip("127.0.0.1")));
+
+        let all = range("0.0.0.0/0");
+        assert!(all.contains(&ip("8.8.8.8")));
+        assert!(all.contains(&ip("::ffff:8.8.8.8"))); // mapped ipv6 address of ipv4 client
+        assert!(!all.contains(&ip("2001:db8::1")));
+    }
+
+    #[test]
+    fn ipv6_ranges_match_by_prefix() {
+        let network = range("2001:db8::/32");
+        assert!(network.contains(&ip("2001:db8:1::1")));
+        assert!(!network.contains(&ip("2001:db9::1")));
+
+        let host = range("::1");
+        assert_eq!(host.prefix, 128);
+        assert!(host.contains(&ip("::1")));
+        assert!(!host.contains(&ip("::2")));
+
+        assert!(range("::/0").contains(&ip("fe80::1")));
+        assert!(range("::ffff:10.0.0.0/104").contains(&ip("10.1.2.3")));
+    }
+
+    #[test]
+    fn malformed_ranges_are_rejected() {
+        for s in &["", "10.0.0", "10.0.0.0/", "10.0.0.0/33", "::/129", "10.0.0.0/-1", "10.0.0.0/8/8", "host/24"] {
+            assert!(s.parse::<IpRange>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn keys_must_have_own_user_ids() {
+        let keyring = |ids: &[u32]| {
+            let keys: Vec<String> = ids.iter().enumerate()
+                .map(|(i, id)| format!("- {{ name: key-{0}, hash: \"{0:064}\", user-id: {1}, groups: [] }}", i, id))
+                .collect();
+            serde_yaml::from_str::<ApiKeyring>(&format!("keys:\n{}", keys.join("\n"))).unwrap()
+        };
+
+        assert!(Inner::new(keyring(&[1, 2]), "keys.yaml").is_ok());
+        assert_eq!(Inner::new(keyring(&[1, 0]), "keys.yaml").err().unwrap(), "User key-1 of api keys file keys.yaml has no user-id");
+        assert_eq!(
+            Inner::new(keyring(&[1, 1]), "keys.yaml").err().unwrap(),
+            "Users key-0 and key-1 of api keys file keys.yaml have same user-id 1"
+        );
+        assert!(serde_yaml::from_str::<ApiKeyring>("keys:\n- { name: key, hash: \"00\", groups: [] }").is_err());
+    }
+}
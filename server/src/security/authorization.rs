@@ -6,10 +6,16 @@ use actix_web::{Error, HttpMessage};
 use actix_web::dev::{ServiceRequest, ServiceResponse, Service, Transform};
 
 use crate::security::SecurityContext;
+use crate::security::credentials::AuthError;
+
+// group for access to api
+pub const BASE_ACCESS: &str = "BASE_ACCESS";
+// group for access to management endpoints
+pub const DEVELOPER: &str = "DEVELOPER";
 
 pub struct AuthorizationMiddleware<S> {
     service: S,
-    only_developer: bool
+    group: Option<&'static str>, // only authentication is required without group
 }
 
 impl<S,B> Service for AuthorizationMiddleware<S>
@@ -32,27 +38,31 @@ impl<S,B> Service for AuthorizationMiddleware<S>
             {
                 let extensions = &req.extensions();
                 let context= extensions.get::<SecurityContext>();
-                match context {
-                    Some(ctx) => {
-                        if self.only_developer {
-                            ctx.groups.contains("DEVELOPER")
+                match (context, self.group) {
+                    (Some(ctx), Some(group)) => {
+                        if ctx.is_member(group) {
+                            Ok(())
                         } else {
-                            ctx.groups.contains("BASE_ACCESS")
+                            Err(AuthError::InsufficientScope(group.to_string()))
                         }
                     },
-                    None => false
+                    (Some(_), None) => Ok(()),
+                    (None, _) => Err(AuthError::Unauthenticated)
                 }
             };
 
-        if authorized {
-            let fut = self.service.call(req);
+        match authorized {
+            Ok(_) => {
+                let fut = self.service.call(req);
 
-            Box::pin(async move {
-                let res = fut.await?;
-                Ok(res)
-            })
-        } else {
-            Box::pin(async { Err(actix_web::error::ErrorUnauthorized("You are not authenticated"))})
+                Box::pin(async move {
+                    let res = fut.await?;
+                    Ok(res)
+                })
+            },
+            Err(err) => {
+                Box::pin(async { Err(err.into())})
+            }
         }
     }
 
@@ -60,15 +70,19 @@ impl<S,B> Service for AuthorizationMiddleware<S>
 
 #[derive(Clone)]
 pub struct Authorized {
-    only_developer: bool
+    group: Option<&'static str>
 }
 
 impl Authorized {
     pub fn all() -> Self {
-        Self { only_developer: false}
+        Self { group: Some(BASE_ACCESS) }
     }
     pub fn developers() -> Self {
-        Self { only_developer: true}
+        Self { group: Some(DEVELOPER) }
+    }
+    /// any authenticated user, endpoint filters its response by groups of user
+    pub fn authenticated() -> Self {
+        Self { group: None }
     }
 }
 
@@ -86,6 +100,6 @@ impl <S,B> Transform<S> for Authorized
     type Future = Ready<Result<Self::Transform, Self::InitError>>;
 
     fn new_transform(&self, service: S) -> Self::Future {
-        ready(Ok(AuthorizationMiddleware { service, only_developer: self.only_developer }))
+        ready(Ok(AuthorizationMiddleware { service, group: self.group }))
     }
 }
@@ -0,0 +1,269 @@
+use std::future::{Future, Ready, ready};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Poll, Context};
+
+use actix_web::{Error, HttpMessage};
+use actix_web::dev::{ServiceRequest, ServiceResponse, Service, Transform};
+use openssl::nid::Nid;
+use openssl::ssl::SslRef;
+use openssl::x509::X509;
+
+use crate::security::SecurityContext;
+use crate::server::config::{ClientAuthConfig, ClientCertUser};
+
+/// user mapped from client certificate in TLS handshake, connection data cloned into every request
+#[derive(Clone)]
+pub struct ClientCertificate(Option<SecurityContext>);
+
+struct Inner {
+    users: Vec<ClientCertUser>,
+}
+
+impl Inner {
+    /// map certificate subject common name or subject alternative names to user
+    fn map_certificate(&self, cert: &X509) -> Option<SecurityContext> {
+        let common_name = cert
+            .subject_name()
+            .entries_by_nid(Nid::COMMONNAME)
+            .next()
+            .and_then(|e| e.data().as_utf8().ok())
+            .map(|s| s.to_string());
+
+        let alt_names: Vec<String> = cert
+            .subject_alt_names()
+            .map(|names| names
+                .iter()
+                .filter_map(|n| n.dnsname().or_else(|| n.email()).or_else(|| n.uri()).map(|s| s.to_string()))
+                .collect())
+            .unwrap_or_default();
+
+        self.users
+            .iter()
+            .find(|u| {
+                let cn_matches = match (&u.common_name, &common_name) {
+                    (Some(expected), Some(actual)) => expected == actual,
+                    _ => false
+                };
+                let san_matches = match &u.san {
+                    Some(expected) => alt_names.iter().any(|n| n.eq_ignore_ascii_case(expected)),
+                    None => false
+                };
+                cn_matches || san_matches
+            })
+            .map(|u| SecurityContext::new(u.user_id, u.groups.clone()))
+    }
+}
+
+pub struct ClientCertMiddleware<S> {
+    service: S,
+}
+
+impl<S,B> Service for ClientCertMiddleware<S>
+    where
+        S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+        S::Future: 'static,
+        B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, ctx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(ctx)
+    }
+
+    fn call(&mut self, req: Self::Request) -> Self::Future {
+        // jwt and api keys have priority over client certificate
+        let certificate = req.extensions_mut().remove::<ClientCertificate>();
+        if let Some(ClientCertificate(Some(context))) = certificate {
+            if req.extensions().get::<SecurityContext>().is_none() {
+                req.extensions_mut().insert(context);
+            }
+        }
+
+        Box::pin(self.service.call(req))
+    }
+
+}
+
+/// Authenticator for internal systems with client certificates issued by our CA:
+/// certificate verified in TLS handshake (see `setup_tls`), subject or SAN mapped to user through config
+#[derive(Clone)]
+pub struct ClientCertService {
+    inner: Arc<Inner>,
+}
+
+impl ClientCertService {
+    pub fn new(config: &Option<ClientAuthConfig>) -> Result<Self, String> {
+        let users = config.as_ref().map(|c| c.users.clone()).unwrap_or_default();
+        let names = users.iter().map(|u| u.common_name.as_deref().or(u.san.as_deref()).unwrap_or("-"));
+        super::check_user_ids("client certificates", names.zip(users.iter().map(|u| u.user_id)))?;
+        let inner = Arc::new(Inner { users });
+        Ok(Self { inner })
+    }
+
+    /// data of connection for every its request, see `https_server`
+    pub fn connection_data(&self, ssl: &SslRef) -> ClientCertificate {
+        if self.inner.users.is_empty() {
+            return ClientCertificate(None);
+        }
+        ClientCertificate(ssl.peer_certificate().and_then(|cert| self.inner.map_certificate(&cert)))
+    }
+}
+
+impl <S,B> Transform<S> for ClientCertService
+    where
+        S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+        S::Future: 'static,
+        B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = ClientCertMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ClientCertMiddleware { service }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::net::TcpListener;
+
+    use actix_web::{web, App, HttpRequest, HttpResponse};
+    use actix_web::http::Version;
+    use actix_web::client::{Client, Connector};
+    use openssl::asn1::Asn1Time;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::hash::MessageDigest;
+    use openssl::nid::Nid;
+    use openssl::pkey::{PKey, Private};
+    use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
+    use openssl::x509::{X509, X509NameBuilder};
+    use openssl::x509::extension::BasicConstraints;
+
+    use super::*;
+    use crate::server::config::{HttpListener, SocketAddress};
+
+    fn key() -> PKey<Private> {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap()
+    }
+
+    /// certificate with common name, self-signed without issuer
+    fn certificate(common_name: &str, key: &PKey<Private>, issuer: Option<(&X509, &PKey<Private>)>) -> X509 {
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_nid(Nid::COMMONNAME, common_name).unwrap();
+        let name = name.build();
+
+        let mut builder = X509::builder().unwrap();
+        builder.set_version(2).unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder.set_pubkey(key).unwrap();
+        builder.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        builder.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+        match issuer {
+            Some((cert, issuer_key)) => {
+                builder.set_issuer_name(cert.subject_name()).unwrap();
+                builder.sign(issuer_key, MessageDigest::sha256()).unwrap();
+            },
+            None => {
+                builder.set_issuer_name(&name).unwrap();
+                builder.append_extension(BasicConstraints::new().critical().ca().build().unwrap()).unwrap();
+                builder.sign(key, MessageDigest::sha256()).unwrap();
+            }
+        }
+        builder.build()
+    }
+
+    async fn whoami(req: HttpRequest) -> HttpResponse {
+        let peer = req.peer_addr().map(|addr| addr.port()).unwrap_or_default();
+        match req.extensions().get::<SecurityContext>() {
+            Some(ctx) => HttpResponse::Ok().body(format!("{} {}", ctx.user_id(), peer)),
+            None => HttpResponse::Unauthorized().finish()
+        }
+    }
+
+    #[actix_rt::test]
+    async fn identity_is_kept_for_all_requests_of_connection() {
+        let (ca_key, server_key, client_key) = (key(), key(), key());
+        let ca = certificate("test-ca", &ca_key, None);
+        let server_cert = certificate("localhost", &server_key, Some((&ca, &ca_key)));
+        let client_cert = certificate("service-a", &client_key, Some((&ca, &ca_key)));
+
+        let dir = std::env::temp_dir().join(format!("foundation-clientcert-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, pem: Vec<u8>| {
+            let path = dir.join(name);
+            std::fs::write(&path, pem).unwrap();
+            path.to_string_lossy().to_string()
+        };
+        let settings = HttpListener {
+            listen: SocketAddress { domain: "127.0.0.1".to_string(), port: 0 },
+            tls_key: write("server.key", server_key.private_key_to_pem_pkcs8().unwrap()),
+            tls_cert: write("server.pem", server_cert.to_pem().unwrap()),
+            client_auth: Some(ClientAuthConfig {
+                ca: write("ca.pem", ca.to_pem().unwrap()),
+                users: vec![ClientCertUser {
+                    common_name: Some("service-a".to_string()), san: None, user_id: 7, groups: HashSet::new()
+                }],
+            }),
+        };
+
+        let clientcert = ClientCertService::new(&settings.client_auth).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app_clientcert = clientcert.clone();
+        let server = crate::server::https_server(listener, crate::server::setup_tls(&settings).unwrap(), clientcert, 75, move || {
+            App::new()
+                .wrap(app_clientcert.clone())
+                .route("/", web::get().to(whoami))
+        }).unwrap();
+
+        for (alpn, version) in [(&b"\x02h2"[..], Version::HTTP_2), (&b"\x08http/1.1"[..], Version::HTTP_11)] {
+            let mut ssl = SslConnector::builder(SslMethod::tls()).unwrap();
+            ssl.set_verify(SslVerifyMode::NONE);
+            ssl.set_certificate(&client_cert).unwrap();
+            ssl.set_private_key(&client_key).unwrap();
+            ssl.set_alpn_protos(alpn).unwrap();
+            let client = Client::builder().connector(Connector::new().ssl(ssl.build()).finish()).finish();
+
+            let mut bodies = Vec::new();
+            for _ in 0..2 {
+                let mut resp = client.get(format!("https://{}/", addr)).send().await.unwrap();
+                assert_eq!((resp.status().as_u16(), resp.version()), (200, version));
+                bodies.push(resp.body().await.unwrap());
+            }
+            // same peer port: second request was sent on same connection
+            assert!(bodies[0].starts_with(b"7 "));
+            assert_eq!(bodies[0], bodies[1]);
+        }
+
+        server.stop(false).await;
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn users_must_have_own_ids() {
+        let config = |ids: &[u32]| Some(ClientAuthConfig {
+            ca: "ca.pem".to_string(),
+            users: ids.iter().enumerate().map(|(i, id)| ClientCertUser {
+                common_name: Some(format!("service-{}", i)), san: None, user_id: *id, groups: HashSet::new()
+            }).collect(),
+        });
+
+        assert!(ClientCertService::new(&config(&[7, 8])).is_ok());
+        assert!(ClientCertService::new(&None).is_ok());
+        assert_eq!(ClientCertService::new(&config(&[7, 0])).err().unwrap(), "User service-1 of client certificates has no user-id");
+        assert_eq!(
+            ClientCertService::new(&config(&[7, 7])).err().unwrap(),
+            "Users service-0 and service-1 of client certificates have same user-id 7"
+        );
+    }
+}
@@ -0,0 +1,220 @@
+use std::fmt;
+
+use actix_web::{HttpMessage, HttpResponse, ResponseError};
+use actix_web::dev::ServiceRequest;
+use actix_web::http::{header, HeaderValue, StatusCode};
+
+use crate::server::Problem;
+
+// see: https://tools.ietf.org/html/rfc6750
+
+const REALM: &str = "foundation";
+const BEARER: &str = "Bearer";
+
+/// Errors of bearer token authentication (RFC 6750, section 3.1)
+#[derive(Debug)]
+pub enum AuthError {
+    /// request does not contain any bearer credentials, maybe credentials of other scheme
+    Unauthenticated,
+    /// authorization header is malformed
+    InvalidRequest(String),
+    /// token is expired, revoked, malformed or invalid for other reasons
+    InvalidToken(String),
+    /// token is valid, but has not required privileges
+    InsufficientScope(String),
+}
+
+impl AuthError {
+    fn code(&self) -> &'static str {
+        match self {
+            AuthError::Unauthenticated      => "unauthorized",
+            AuthError::InvalidRequest(_)    => "invalid_request",
+            AuthError::InvalidToken(_)      => "invalid_token",
+            AuthError::InsufficientScope(_) => "insufficient_scope",
+        }
+    }
+
+    fn description(&self) -> String {
+        match self {
+            AuthError::Unauthenticated             => "You are not authenticated".to_string(),
+            AuthError::InvalidRequest(descr)       => descr.clone(),
+            AuthError::InvalidToken(descr)         => descr.clone(),
+            AuthError::InsufficientScope(group)    => format!("Access requires membership in group {}", group),
+        }
+    }
+
+    /// value of WWW-Authenticate header;
+    /// error code omitted if request does not contain any credentials
+    fn challenge(&self) -> String {
+        let mut challenge = format!("{} realm=\"{}\"", BEARER, REALM);
+        if let AuthError::Unauthenticated = self {
+            return challenge;
+        }
+
+        challenge.push_str(&format!(", error=\"{}\", error_description=\"{}\"", self.code(), quoted(&self.description())));
+        if let AuthError::InsufficientScope(group) = self {
+            challenge.push_str(&format!(", scope=\"{}\"", quoted(group)));
+        }
+        challenge
+    }
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code(), self.description())
+    }
+}
+
+impl ResponseError for AuthError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AuthError::InvalidRequest(_)    => StatusCode::BAD_REQUEST,
+            AuthError::InsufficientScope(_) => StatusCode::FORBIDDEN,
+            _                               => StatusCode::UNAUTHORIZED,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut builder = HttpResponse::build(self.status_code());
+        builder.header(header::WWW_AUTHENTICATE, self.challenge());
+        Problem::new(self.status_code(), self.code(), self.description()).respond(builder)
+    }
+}
+
+/// Extract bearer token from `Authorization` header
+/// or, if cookie name is configured, from the cookie (for browser applications).
+/// Returns `None` if request does not contain any credentials.
+pub fn extract_token(req: &ServiceRequest, cookie: Option<&str>) -> Result<Option<String>, AuthError> {
+    if let Some(value) = req.headers().get(header::AUTHORIZATION) {
+        return parse_authorization(value).map(|token| Some(token.to_string()));
+    }
+
+    if let Some(name) = cookie {
+        if let Some(cookie) = req.cookie(name) {
+            let token = cookie.value();
+            return if is_token68(token) {
+                Ok(Some(token.to_string()))
+            } else {
+                Err(AuthError::InvalidToken(format!("Malformed token in cookie {}", name)))
+            };
+        }
+    }
+
+    Ok(None)
+}
+
+/// Parse `Authorization: Bearer <token>` header value
+pub fn parse_authorization(value: &HeaderValue) -> Result<&str, AuthError> {
+    let value = value
+        .to_str()
+        .map_err(|_| AuthError::InvalidRequest("Authorization header contains non-ASCII characters".to_string()))?
+        .trim();
+
+    let mut parts = value.splitn(2, ' ');
+    let scheme = parts.next().unwrap_or_default();
+    // credentials of other schemes are no bearer credentials at all: bare challenge without error code
+    if !scheme.eq_ignore_ascii_case(BEARER) {
+        return Err(AuthError::Unauthenticated);
+    }
+
+    let token = parts.next().unwrap_or_default().trim_start();
+    if token.is_empty() {
+        return Err(AuthError::InvalidRequest("Bearer token is missing".to_string()));
+    }
+    if !is_token68(token) {
+        return Err(AuthError::InvalidRequest("Bearer token is malformed".to_string()));
+    }
+
+    Ok(token)
+}
+
+/// b64token syntax: 1*( ALPHA / DIGIT / "-" / "." / "_" / "~" / "+" / "/" ) *"="
+fn is_token68(token: &str) -> bool {
+    let body = token.trim_end_matches('=');
+    !body.is_empty() && body.chars().all(|c| c.is_ascii_alphanumeric() || "-._~+/".contains(c))
+}
+
+/// escape value for quoted-string in header
+fn quoted(value: &str) -> String {
+    value.chars().filter(|c| c.is_ascii() && !c.is_ascii_control() && *c != '"' && *c != '\\').collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+    use actix_web::http::Cookie;
+
+    use super::*;
+
+    fn parse(value: &str) -> Result<String, AuthError> {
+        parse_authorization(&HeaderValue::from_str(value).unwrap()).map(str::to_string)
+    }
+
+    fn challenge(result: Result<String, AuthError>) -> String {
+        result.err().unwrap().challenge()
+    }
+
+    #[test]
+    fn bearer_token_is_parsed() {
+        assert_eq!(parse("Bearer abc.DEF-123_~+/").unwrap(), "abc.DEF-123_~+/");
+        assert_eq!(parse("bearer   abc").unwrap(), "abc");
+        assert_eq!(parse(" Bearer abc ").unwrap(), "abc");
+        // padding only at end
+        assert_eq!(parse("Bearer YWJj==").unwrap(), "YWJj==");
+        assert!(matches!(parse("Bearer YW=Jj"), Err(AuthError::InvalidRequest(_))));
+        assert!(matches!(parse("Bearer =="), Err(AuthError::InvalidRequest(_))));
+    }
+
+    #[test]
+    fn missing_or_malformed_token_is_invalid_request() {
+        assert!(matches!(parse("Bearer"), Err(AuthError::InvalidRequest(_))));
+        assert!(matches!(parse("Bearer   "), Err(AuthError::InvalidRequest(_))));
+        assert!(matches!(parse("Bearer abc def"), Err(AuthError::InvalidRequest(_))));
+        assert!(matches!(parse("Bearer a\"b"), Err(AuthError::InvalidRequest(_))));
+
+        let non_ascii = HeaderValue::from_bytes("Bearer äbc".as_bytes()).unwrap();
+        let err = parse_authorization(&non_ascii).err().unwrap();
+        assert!(matches!(err, AuthError::InvalidRequest(_)));
+        assert_eq!(
+            err.challenge(),
+            "Bearer realm=\"foundation\", error=\"invalid_request\", error_description=\"Authorization header contains non-ASCII characters\""
+        );
+    }
+
+    #[test]
+    fn other_schemes_get_bare_challenge() {
+        assert!(matches!(parse("Basic dXNlcjpwdw=="), Err(AuthError::Unauthenticated)));
+        assert!(matches!(parse("Bearerabc"), Err(AuthError::Unauthenticated)));
+        assert_eq!(challenge(parse("Digest username=x")), "Bearer realm=\"foundation\"");
+        assert_eq!(AuthError::Unauthenticated.status_code(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn token_from_header_or_cookie() {
+        let req = TestRequest::default().to_srv_request();
+        assert!(extract_token(&req, Some("token")).unwrap().is_none());
+
+        let req = TestRequest::default().cookie(Cookie::new("token", "abc")).to_srv_request();
+        assert_eq!(extract_token(&req, Some("token")).unwrap().as_deref(), Some("abc"));
+        // cookie is used only if configured
+        assert!(extract_token(&req, None).unwrap().is_none());
+
+        // header is preferred over cookie
+        let req = TestRequest::default()
+            .header(header::AUTHORIZATION, "Bearer def")
+            .cookie(Cookie::new("token", "abc"))
+            .to_srv_request();
+        assert_eq!(extract_token(&req, Some("token")).unwrap().as_deref(), Some("def"));
+
+        for value in &["", "a b", "=="] {
+            let req = TestRequest::default().cookie(Cookie::new("token", *value)).to_srv_request();
+            assert!(matches!(extract_token(&req, Some("token")), Err(AuthError::InvalidToken(_))), "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn description_is_quoted() {
+        let err = AuthError::InvalidToken("bad \"token\" \\ ünicode".to_string());
+        assert_eq!(err.challenge(), "Bearer realm=\"foundation\", error=\"invalid_token\", error_description=\"bad token  nicode\"");
+    }
+}
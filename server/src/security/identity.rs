@@ -10,21 +10,29 @@ use std::task::{Poll, Context};
 use actix_web::{Error, HttpMessage};
 use actix_web::dev::{ServiceRequest, ServiceResponse, Service, Transform};
 use serde::{Serialize, Deserialize};
+use slog::{debug, Logger};
 
 use jsonwebtoken::{Validation, Algorithm};
+use jsonwebtoken::errors::ErrorKind;
 
 use crate::security::SecurityContext;
+use crate::security::credentials::{self, AuthError};
+use crate::metrics::Metrics;
+use crate::telemetry::Trace;
 
 // use maplit::hashset;
 
 struct Inner {
     _source: Box<Vec<u8>>,   // source of jwt public key
     key:    jsonwebtoken::DecodingKey<'static>, // key reference to source
-    validation: Validation
+    validation: Validation,
+    cookie: Option<String>, // name of cookie with token for browser applications
+    metrics: Arc<Metrics>,
+    log: Logger,
 }
 
 impl Inner {
-    pub fn new(issuer: String, key_file: PathBuf) -> Self {
+    pub fn new(issuer: String, key_file: PathBuf, cookie: Option<String>, metrics: Arc<Metrics>, log: Logger) -> Self {
         let mut file = File::open(key_file).map_err(|err| format!("Can not open key-file : {}", err)).unwrap();
         let mut _source = Vec::with_capacity(1024);
         file.read_to_end(&mut _source).unwrap();
@@ -41,7 +49,7 @@ impl Inner {
         validation.iss = Some(issuer);
         validation.validate_exp = true;
 
-        Self { _source, key, validation}
+        Self { _source, key, validation, cookie, metrics, log }
     }
 }
 
@@ -65,26 +73,25 @@ impl <S,B> IdentityMiddleware<S>
         S::Future: 'static,
         B: 'static,
 {
-    fn construct_context(&mut self, req: &ServiceRequest) -> Result<(), String> {
-        let auth_header = req.headers().get("Authorization");
-        match auth_header {
-            Some(auth_header) => {
-                let _split: Vec<&str> = auth_header.to_str().unwrap().split("Bearer").collect();
-                let token = _split[1].trim();
-
-                let decode_result = jsonwebtoken::decode::<Claims>(token, &self.inner.key, &self.inner.validation);
+    fn construct_context(&mut self, req: &ServiceRequest) -> Result<(), AuthError> {
+        let token = credentials::extract_token(req, self.inner.cookie.as_deref())
+            .inspect_err(|_| self.inner.metrics.record_auth_failure("invalid_request"))?;
+        match token {
+            Some(token) => {
+                let decode_result = jsonwebtoken::decode::<Claims>(&token, &self.inner.key, &self.inner.validation);
                 match decode_result {
                     Ok(result) => {
                         let claims = result.claims;
 
-                        println!("iss: {}, sub: {}", &claims.iss, &claims.sub);
-                        
+                        debug!(self.inner.log, "token accepted"; "iss" => &claims.iss, "sub" => &claims.sub);
+
                         let user_id: u32 = claims.sub.parse().unwrap_or(0);
                         req.extensions_mut().insert(SecurityContext::new(user_id, claims.groups));
                         Ok(())
                     },
                     Err(err) => {
-                        Err(format!("Can not decode authorization token: {}", err))
+                        self.inner.metrics.record_auth_failure(failure_reason(err.kind()));
+                        Err(AuthError::InvalidToken(format!("Can not decode authorization token: {}", err)))
                     }
                 }
             },
@@ -94,6 +101,18 @@ impl <S,B> IdentityMiddleware<S>
 
 }
 
+/// reason of rejected token for metrics
+fn failure_reason(kind: &ErrorKind) -> &'static str {
+    match kind {
+        ErrorKind::ExpiredSignature => "expired",
+        ErrorKind::ImmatureSignature => "not_yet_valid",
+        ErrorKind::InvalidIssuer => "invalid_issuer",
+        ErrorKind::InvalidSignature => "invalid_signature",
+        ErrorKind::InvalidAlgorithm => "invalid_algorithm",
+        _ => "malformed",
+    }
+}
+
 impl<S,B> Service for IdentityMiddleware<S>
     where
         S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
@@ -110,7 +129,14 @@ impl<S,B> Service for IdentityMiddleware<S>
     }
 
     fn call(&mut self, req: Self::Request) -> Self::Future {
-        match self.construct_context(&req) {
+        let mut span = Trace::of(&req).span("authenticate");
+        let result = self.construct_context(&req);
+        if let Err(err) = &result {
+            span.error(err);
+        }
+        drop(span);
+
+        match result {
             Ok(_) => {
                 let fut = self.service.call(req);
 
@@ -120,7 +146,7 @@ impl<S,B> Service for IdentityMiddleware<S>
                 })
             },
             Err(err) => {
-                Box::pin(async { Err(actix_web::error::ErrorBadRequest(err))})
+                Box::pin(async { Err(err.into())})
             }
         }
     }
@@ -133,8 +159,8 @@ pub struct IdentityService {
 }
 
 impl IdentityService {
-    pub fn new(issuer: String, key_file: PathBuf) -> Self {
-        let inner = Arc::new(Inner::new(issuer, key_file));
+    pub fn new(issuer: String, key_file: PathBuf, cookie: Option<String>, metrics: Arc<Metrics>, log: Logger) -> Self {
+        let inner = Arc::new(Inner::new(issuer, key_file, cookie, metrics, log));
         Self { inner }
     }
 }
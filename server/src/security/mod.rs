@@ -1,8 +1,13 @@
 mod identity;
 mod authorization;
+mod apikey;
+mod clientcert;
+mod credentials;
+mod ratelimit;
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
+#[derive(Clone)]
 pub struct SecurityContext {
     user_id: u32,    // this is ID of user
     groups:  HashSet<String>,
@@ -12,7 +17,33 @@ impl SecurityContext {
     pub fn new(user_id: u32, groups:  HashSet<String>) -> Self {
         Self { user_id, groups }
     }
+
+    pub fn user_id(&self) -> u32 {
+        self.user_id
+    }
+
+    pub fn is_member(&self, group: &str) -> bool {
+        self.groups.contains(group)
+    }
+}
+
+/// ids of configured users are keys of quotas, statement timeouts and audit,
+/// so every user must have own id; 0 is not valid
+fn check_user_ids<'a>(source: &str, users: impl Iterator<Item = (&'a str, u32)>) -> Result<(), String> {
+    let mut names = HashMap::new();
+    for (name, user_id) in users {
+        if user_id == 0 {
+            return Err(format!("User {} of {} has no user-id", name, source));
+        }
+        if let Some(other) = names.insert(user_id, name) {
+            return Err(format!("Users {} and {} of {} have same user-id {}", other, name, source, user_id));
+        }
+    }
+    Ok(())
 }
 
 pub use identity::IdentityService;
-pub use authorization::Authorized;
+pub use apikey::ApiKeyService;
+pub use clientcert::ClientCertService;
+pub use authorization::{Authorized, BASE_ACCESS, DEVELOPER};
+pub use ratelimit::RateLimit;
@@ -0,0 +1,385 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::future::{Future, Ready, ready};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Poll, Context};
+use std::time::{Duration, Instant};
+
+use actix_web::{Error, HttpMessage, HttpResponse, ResponseError};
+use actix_web::dev::{ServiceRequest, ServiceResponse, Service, Transform};
+use actix_web::http::{header, StatusCode};
+
+use crate::security::SecurityContext;
+use crate::server::Problem;
+use crate::server::config::{Limit, ScopeLimits};
+
+// buckets of users, who are idle and have full quota again, are removed at most once per interval
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Request was rejected because user or group exceeded its quota
+#[derive(Debug)]
+pub struct QuotaExceeded {
+    subject: String,
+    reason: &'static str,
+    retry_after: u64, // seconds
+}
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Too many {} for {}", self.reason, self.subject)
+    }
+}
+
+impl ResponseError for QuotaExceeded {
+    fn status_code(&self) -> StatusCode {
+        StatusCode::TOO_MANY_REQUESTS
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut builder = HttpResponse::build(self.status_code());
+        builder.header(header::RETRY_AFTER, self.retry_after.to_string());
+        Problem::new(self.status_code(), "too_many_requests", self.to_string()).respond(builder)
+    }
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+enum Subject {
+    User(u32),
+    Group(String),
+}
+
+impl fmt::Display for Subject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Subject::User(id) => write!(f, "user {}", id),
+            Subject::Group(name) => write!(f, "group {}", name),
+        }
+    }
+}
+
+/// token bucket for requests per second and count of active requests
+struct Bucket {
+    tokens:  f64,
+    updated: Instant,
+    active:  u32,
+}
+
+impl Bucket {
+    /// add tokens for time since last update, up to capacity
+    fn refill(&mut self, limit: &Limit, now: Instant) {
+        if let Some(rate) = limit.requests_per_second {
+            let elapsed = now.duration_since(self.updated).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * rate).min(limit.capacity());
+            self.updated = now;
+        }
+    }
+
+    /// bucket without active requests and with full quota is same as new bucket
+    fn is_idle(&self, limit: &Limit) -> bool {
+        self.active == 0 && self.tokens >= limit.capacity()
+    }
+}
+
+struct Buckets {
+    buckets: HashMap<Subject, Bucket>,
+    swept:   Instant,
+}
+
+struct Inner {
+    user:   Limit,
+    groups: HashMap<String, Limit>,
+    buckets: Mutex<Buckets>,
+}
+
+impl Inner {
+    fn limit(&self, subject: &Subject) -> Option<&Limit> {
+        match subject {
+            Subject::User(_) => Some(&self.user),
+            Subject::Group(group) => self.groups.get(group),
+        }
+    }
+
+    fn limits<'a>(&'a self, ctx: &SecurityContext) -> Vec<(Subject, &'a Limit)> {
+        let mut limits = Vec::with_capacity(1 + ctx.groups.len());
+        limits.push((Subject::User(ctx.user_id), &self.user));
+        for group in ctx.groups.iter() {
+            if let Some(limit) = self.groups.get(group) {
+                limits.push((Subject::Group(group.clone()), limit));
+            }
+        }
+        limits
+    }
+
+    /// take one request from all buckets of user and his groups, or nothing if any quota is exceeded
+    fn acquire(self: &Arc<Self>, ctx: &SecurityContext) -> Result<Permit, QuotaExceeded> {
+        self.acquire_at(ctx, Instant::now())
+    }
+
+    fn acquire_at(self: &Arc<Self>, ctx: &SecurityContext, now: Instant) -> Result<Permit, QuotaExceeded> {
+        let limits = self.limits(ctx);
+
+        let mut guard = self.buckets.lock().unwrap();
+        self.sweep(&mut guard, now);
+        let buckets = &mut guard.buckets;
+
+        for (subject, limit) in limits.iter() {
+            let bucket = buckets.entry(subject.clone()).or_insert_with(|| Bucket {
+                tokens: limit.capacity(),
+                updated: now,
+                active: 0
+            });
+
+            if let Some(rate) = limit.requests_per_second {
+                bucket.refill(limit, now);
+
+                if bucket.tokens < 1.0 {
+                    let retry_after = ((1.0 - bucket.tokens) / rate).ceil().max(1.0) as u64;
+                    return Err(QuotaExceeded { subject: subject.to_string(), reason: "requests", retry_after });
+                }
+            }
+
+            if let Some(concurrent) = limit.concurrent {
+                if bucket.active >= concurrent {
+                    return Err(QuotaExceeded { subject: subject.to_string(), reason: "concurrent requests", retry_after: 1 });
+                }
+            }
+        }
+
+        let mut subjects = Vec::with_capacity(limits.len());
+        for (subject, limit) in limits {
+            let bucket = buckets.get_mut(&subject).unwrap();
+            if limit.requests_per_second.is_some() {
+                bucket.tokens -= 1.0;
+            }
+            bucket.active += 1;
+            subjects.push(subject);
+        }
+
+        Ok(Permit { inner: self.clone(), subjects })
+    }
+
+    /// remove buckets of idle users, otherwise every user since start keeps its bucket
+    fn sweep(&self, buckets: &mut Buckets, now: Instant) {
+        if now.duration_since(buckets.swept) < SWEEP_INTERVAL {
+            return;
+        }
+        buckets.swept = now;
+        buckets.buckets.retain(|subject, bucket| match self.limit(subject) {
+            Some(limit) => {
+                bucket.refill(limit, now);
+                !bucket.is_idle(limit)
+            },
+            None => bucket.active > 0
+        });
+    }
+}
+
+/// Active request of user; released when request completes or client goes away
+struct Permit {
+    inner: Arc<Inner>,
+    subjects: Vec<Subject>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let mut buckets = self.inner.buckets.lock().unwrap();
+        for subject in self.subjects.iter() {
+            if let Some(bucket) = buckets.buckets.get_mut(subject) {
+                bucket.active = bucket.active.saturating_sub(1);
+            }
+        }
+    }
+}
+
+impl Limit {
+    fn capacity(&self) -> f64 {
+        match (self.burst, self.requests_per_second) {
+            (Some(burst), _) => burst as f64,
+            (None, Some(rate)) => rate.max(1.0),
+            (None, None) => 0.0
+        }
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: S,
+    inner: Option<Arc<Inner>>,
+}
+
+impl<S,B> Service for RateLimitMiddleware<S>
+    where
+        S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+        S::Future: 'static,
+        B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, ctx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(ctx)
+    }
+
+    fn call(&mut self, req: Self::Request) -> Self::Future {
+        let permit = match &self.inner {
+            Some(inner) => {
+                let extensions = req.extensions();
+                match extensions.get::<SecurityContext>() {
+                    Some(ctx) => inner.acquire(ctx).map(Some),
+                    None => Ok(None)
+                }
+            },
+            None => Ok(None)
+        };
+
+        match permit {
+            Ok(permit) => {
+                let fut = self.service.call(req);
+
+                Box::pin(async move {
+                    let res = fut.await?;
+                    drop(permit);
+                    Ok(res)
+                })
+            },
+            Err(err) => {
+                Box::pin(async { Err(err.into())})
+            }
+        }
+    }
+
+}
+
+/// Limits requests per second and concurrent requests per user and per group in scope,
+/// so one user can not exhaust the shared connection pool
+#[derive(Clone)]
+pub struct RateLimit {
+    inner: Option<Arc<Inner>>,
+}
+
+impl RateLimit {
+    pub fn new(limits: Option<&ScopeLimits>) -> Self {
+        let inner = limits.map(|limits| {
+            let groups = limits.groups
+                .iter()
+                .map(|g| (g.group.clone(), g.limit.clone()))
+                .collect();
+            let buckets = Buckets { buckets: HashMap::new(), swept: Instant::now() };
+            Arc::new(Inner { user: limits.user.clone(), groups, buckets: Mutex::new(buckets) })
+        });
+        Self { inner }
+    }
+}
+
+impl <S,B> Transform<S> for RateLimit
+    where
+        S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+        S::Future: 'static,
+        B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RateLimitMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware { service, inner: self.inner.clone() }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::server::config::GroupLimit;
+
+    fn limit(requests_per_second: Option<f64>, burst: Option<u32>, concurrent: Option<u32>) -> Limit {
+        Limit { requests_per_second, burst, concurrent }
+    }
+
+    fn inner(user: Limit, groups: Vec<GroupLimit>) -> Arc<Inner> {
+        let limits = ScopeLimits { user, groups };
+        RateLimit::new(Some(&limits)).inner.unwrap()
+    }
+
+    fn user(id: u32, groups: &[&str]) -> SecurityContext {
+        SecurityContext::new(id, groups.iter().map(|g| g.to_string()).collect::<HashSet<_>>())
+    }
+
+    fn buckets(inner: &Inner) -> usize {
+        inner.buckets.lock().unwrap().buckets.len()
+    }
+
+    #[test]
+    fn burst_is_taken_at_once_and_refilled_by_rate() {
+        let inner = inner(limit(Some(2.0), Some(3), None), vec![]);
+        let (ctx, start) = (user(1, &[]), Instant::now());
+
+        for _ in 0..3 {
+            assert!(inner.acquire_at(&ctx, start).is_ok());
+        }
+        let err = inner.acquire_at(&ctx, start).err().unwrap();
+        assert_eq!((err.reason, err.retry_after), ("requests", 1));
+
+        // one token after half of second, not more than burst after long pause
+        assert!(inner.acquire_at(&ctx, start + Duration::from_millis(500)).is_ok());
+        assert!(inner.acquire_at(&ctx, start + Duration::from_millis(500)).is_err());
+        let later = start + Duration::from_secs(30);
+        for _ in 0..3 {
+            assert!(inner.acquire_at(&ctx, later).is_ok());
+        }
+        assert!(inner.acquire_at(&ctx, later).is_err());
+
+        // other users have own buckets
+        assert!(inner.acquire_at(&user(2, &[]), later).is_ok());
+    }
+
+    #[test]
+    fn concurrent_requests_are_released_with_permit() {
+        let group = GroupLimit { group: "REPORTS".to_string(), limit: limit(None, None, Some(2)) };
+        let inner = inner(limit(None, None, Some(1)), vec![group]);
+        let now = Instant::now();
+
+        let first = inner.acquire_at(&user(1, &["REPORTS"]), now).unwrap();
+        let err = inner.acquire_at(&user(1, &["REPORTS"]), now).err().unwrap();
+        assert_eq!((err.subject.as_str(), err.reason), ("user 1", "concurrent requests"));
+
+        // limit of group is shared by its users
+        let second = inner.acquire_at(&user(2, &["REPORTS"]), now).unwrap();
+        let err = inner.acquire_at(&user(3, &["REPORTS"]), now).err().unwrap();
+        assert_eq!(err.subject, "group REPORTS");
+
+        drop(first);
+        assert!(inner.acquire_at(&user(1, &["REPORTS"]), now).is_ok());
+        drop(second);
+        assert!(inner.acquire_at(&user(3, &["REPORTS"]), now).is_ok());
+    }
+
+    #[test]
+    fn idle_full_buckets_are_removed() {
+        // one token in 100 seconds
+        let inner = inner(limit(Some(0.01), Some(5), Some(10)), vec![]);
+        let now = Instant::now();
+
+        let active = inner.acquire_at(&user(1, &[]), now).unwrap();
+        drop(inner.acquire_at(&user(2, &[]), now).unwrap());
+        assert_eq!(buckets(&inner), 2);
+
+        // user 1 is active, quota of user 2 is not refilled yet
+        drop(inner.acquire_at(&user(3, &[]), now + SWEEP_INTERVAL).unwrap());
+        assert_eq!(buckets(&inner), 3);
+
+        // quota of user 2 is refilled
+        drop(inner.acquire_at(&user(4, &[]), now + SWEEP_INTERVAL * 2).unwrap());
+        assert_eq!(buckets(&inner), 3);
+
+        drop(active);
+        drop(inner.acquire_at(&user(4, &[]), now + SWEEP_INTERVAL * 20).unwrap());
+        assert_eq!(buckets(&inner), 1);
+    }
+}
@@ -1,8 +1,11 @@
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::path::Path;
 use config::{Config, ConfigError};
 use serde::Deserialize;
 
+use crate::audit::Sensitivity;
+
 // https://serde.rs/derive.html
 // https://github.com/mehcode/config-rs/blob/master/examples/hierarchical-env/config/default.toml
 // https://github.com/mehcode/config-rs/blob/master/examples/hierarchical-env/src/settings.rs
@@ -10,8 +13,19 @@ use serde::Deserialize;
 #[derive(Debug, Deserialize)]
 pub struct ServerConfig {
     pub connection: DbConnection,
+    #[serde(default)]
+    pub datasources: HashMap<String, DbConnection>, // other databases by name of datasource
     pub http: HttpListener,
+    #[serde(default)]
+    pub logging: LoggingConfig,
     pub jwt: JwtConfig,
+    pub apikeys: Option<ApiKeysConfig>,
+    pub audit: Option<AuditConfig>,
+    pub limits: Option<LimitsConfig>,
+    pub metrics: Option<MetricsConfig>,
+    pub tracing: Option<TracingConfig>,
+    #[serde(default)]
+    pub timeouts: TimeoutsConfig,
     pub others: Option<OthersConfig>,
 }
 
@@ -19,6 +33,37 @@ pub struct ServerConfig {
 pub struct DbConnection {
     pub url: String,
     pub credentials: DbCredentials,
+    #[serde(default)]
+    pub pool: PoolConfig,
+    #[serde(default)]
+    pub schemas: Vec<String>, // schemas of named datasource
+}
+
+/// tuning of connection pool, timeouts in seconds
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct PoolConfig {
+    pub max_size: u32,
+    pub min_idle: Option<u32>,
+    pub connection_timeout: u64,
+    pub idle_timeout: Option<u64>,
+    pub max_lifetime: Option<u64>,
+    pub statement_cache_size: Option<u32>,
+    pub test_on_checkout: bool,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig {
+            max_size: 15,
+            min_idle: None,
+            connection_timeout: 30,
+            idle_timeout: Some(600),
+            max_lifetime: Some(1800),
+            statement_cache_size: None,
+            test_on_checkout: true,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -32,7 +77,26 @@ pub struct DbCredentials {
 pub struct HttpListener {
     pub listen: SocketAddress,
     pub tls_key: String,
-    pub tls_cert: String
+    pub tls_cert: String,
+    pub client_auth: Option<ClientAuthConfig>,
+}
+
+/// client-certificate authentication
+#[derive(Debug, Deserialize)]
+pub struct ClientAuthConfig {
+    pub ca: String, // bundle of CA certificates, which issue client certificates
+    #[serde(default)]
+    pub users: Vec<ClientCertUser>,
+}
+
+/// mapping of certificate subject common name or subject alternative name to user
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ClientCertUser {
+    pub common_name: Option<String>,
+    pub san: Option<String>,
+    pub user_id: u32, // unique, not 0
+    pub groups: HashSet<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -41,16 +105,179 @@ pub struct SocketAddress {
     pub port: u16,
 }
 
+/// log records of level and above, to stdout if file is not set
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    pub level:  LogLevel,
+    pub format: LogFormat,
+    pub file:   Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Critical,
+    Error,
+    Warning,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json, // one JSON object per line
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct JwtConfig {
     pub public_key: String,
     pub issuer:     String,
+    pub cookie:     Option<String>, // accept token from cookie, for browser applications
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApiKeysConfig {
+    pub file: String, // keyring with hashed api keys
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct AuditConfig {
+    #[serde(default)]
+    pub min_level:     Sensitivity,
+    #[serde(default)]
+    pub default_level: Sensitivity,
+    #[serde(default)]
+    pub levels: Vec<AuditLevel>,
+    pub file:   Option<AuditFileConfig>,
+    pub table:  Option<AuditTableConfig>,
+}
+
+/// sensitivity of schema or, if table is specified, of table
+#[derive(Debug, Deserialize)]
+pub struct AuditLevel {
+    pub schema: String,
+    pub table:  Option<String>,
+    pub level:  Sensitivity,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct AuditFileConfig {
+    pub dir: String,
+    pub retention_days: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct AuditTableConfig {
+    pub name: String, // e.g. FOUNDATION.AUDIT_LOG
+    pub retention_days: Option<u32>,
 }
 
+/// quotas per scope of endpoints
 #[derive(Debug, Deserialize)]
+pub struct LimitsConfig {
+    pub api:  Option<ScopeLimits>,
+    pub mgmt: Option<ScopeLimits>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScopeLimits {
+    pub user: Limit, // limit for every user
+    #[serde(default)]
+    pub groups: Vec<GroupLimit>, // limit for all users of group together
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct GroupLimit {
+    pub group: String,
+    #[serde(flatten)]
+    pub limit: Limit,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Limit {
+    pub requests_per_second: Option<f64>,
+    pub burst: Option<u32>, // max requests at once, default is requests per second
+    pub concurrent: Option<u32>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct MetricsConfig {
+    #[serde(default)]
+    pub table_labels: Vec<String>, // schemas with schema and table as labels of metrics
+}
+
+/// limits of execution time of statements in seconds: by user id, by route or default
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct TimeoutsConfig {
+    pub statement: f64,
+    pub routes: HashMap<String, f64>, // patterns of routes, e.g. `/api/v1/{schema}/{table}/`
+    pub users: HashMap<String, f64>, // user ids, subject of token
+}
+
+impl Default for TimeoutsConfig {
+    fn default() -> Self {
+        TimeoutsConfig {
+            statement: 30.0,
+            routes: HashMap::new(),
+            users: HashMap::new(),
+        }
+    }
+}
+
+/// export of spans: OTLP/HTTP collector and/or local file
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct TracingConfig {
+    pub otlp_endpoint: Option<String>, // e.g. http://localhost:4318/v1/traces
+    pub file: Option<String>, // OTLP JSON, one batch per line
+    pub service_name: String,
+}
+
+impl Default for TracingConfig {
+    fn default() -> Self {
+        TracingConfig {
+            otlp_endpoint: None,
+            file: None,
+            service_name: "foundation".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub struct OthersConfig {
-    pub excludes: Vec<String>,
+    pub excludes: Vec<String>, // exact names of excluded schemas, case sensitive
+    pub expose: Option<ExposeRules>,
+    pub refresh_interval: Option<u64>, // seconds between periodic reloads of metainfo
+    pub snapshot: Option<String>, // file with snapshot of metainfo for fast startup
+    pub snapshots_dir: Option<String>, // directory with snapshots of other databases for diff
+    pub max_scan_rows: Option<u32>, // larger tables are not scanned without filter by indexed column
+}
+
+/// Glob (`*_BAK`, `TMP$*`) or regex (`/COPIE.*/`) patterns of exposed objects, case insensitive;
+/// tables are matched by `table` or `schema.table`, columns by `column`, `table.column` or `schema.table.column`
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct ExposeRules {
+    pub include_schemas: Vec<String>, // all schemas if empty
+    pub exclude_schemas: Vec<String>,
+    pub include_tables:  Vec<String>,
+    pub exclude_tables:  Vec<String>,
+    pub include_columns: Vec<String>,
+    pub exclude_columns: Vec<String>,
 }
 
 pub fn load_config() -> Result<ServerConfig, ConfigError> {
@@ -1,45 +1,185 @@
-use std::sync::RwLock;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
 
-use lazy_static::lazy_static;
-use r2d2::{Pool,PooledConnection};
+use r2d2::{CustomizeConnection, Pool, PooledConnection};
 use r2d2_oracle::OracleConnectionManager;
+use serde::Serialize;
+
+use super::config::{DbConnection, ServerConfig};
 
-pub type Datasource = Pool<OracleConnectionManager>;
 pub type Connection = PooledConnection<OracleConnectionManager>;
 
-type DatasourceHandler = RwLock<Option<Datasource>>;
+// datasource of `connection` section, with all schemas not mapped to other datasources
+pub const DEFAULT_DATASOURCE: &str = "default";
+
+// clients should retry soon, when connections are returned to exhausted pool
+const EXHAUSTED_RETRY_AFTER: u64 = 1;
+// database is not available, retry later
+const UNAVAILABLE_RETRY_AFTER: u64 = 30;
+
+struct Datasource {
+    pool:    Pool<OracleConnectionManager>,
+    schemas: Vec<String>, // owners in upper case, empty for all other schemas
+}
 
-lazy_static! {
-  static ref DATASOURCE: DatasourceHandler = RwLock::new(None);
+/// Connection pools of default datasource and of all named datasources
+pub struct Datasources {
+    sources: HashMap<String, Datasource>,
 }
 
-fn new_datasource(config: &super::config::DbConnection) -> Result<Datasource, String> {
+/// Counts of connections in pool
+#[derive(Debug, Serialize)]
+pub struct PoolState {
+    pub connections: u32, // established connections, in use or idle
+    pub idle:        u32,
+    pub max_size:    u32,
+}
+
+/// Connection can not be acquired from pool
+#[derive(Debug)]
+pub enum DatasourceError {
+    /// datasource with this name is not configured
+    NotConfigured(String),
+    /// all connections of datasource are in use until connection timeout
+    Exhausted(String),
+    /// database of datasource is not available
+    Unavailable(String, String),
+}
+
+/// size of statement cache for every new connection
+#[derive(Debug)]
+struct StatementCache(u32);
+
+impl CustomizeConnection<oracle::Connection, oracle::Error> for StatementCache {
+    fn on_acquire(&self, conn: &mut oracle::Connection) -> Result<(), oracle::Error> {
+        conn.set_stmt_cache_size(self.0)
+    }
+}
+
+fn new_datasource(config: &DbConnection) -> Datasource {
     let user = &config.credentials.user;
     let pw = &config.credentials.pw;
     let manager = OracleConnectionManager::new(user, pw, &config.url);
-    let pool = r2d2::Pool::builder()
-            .max_size(15)
-            .build(manager)
-            .map_err(|err|format!("Build db {:?} connection pool err: {:?}", &config.url, err))?;
 
-    Ok(pool)
+    let pool = &config.pool;
+    let mut builder = Pool::builder()
+            .max_size(pool.max_size)
+            .min_idle(pool.min_idle)
+            .connection_timeout(Duration::from_secs(pool.connection_timeout))
+            .idle_timeout(pool.idle_timeout.map(Duration::from_secs))
+            .max_lifetime(pool.max_lifetime.map(Duration::from_secs))
+            .test_on_check_out(pool.test_on_checkout);
+    if let Some(size) = pool.statement_cache_size {
+        builder = builder.connection_customizer(Box::new(StatementCache(size)));
+    }
+    // connections are established lazily, server can start from metainfo snapshot while database is unavailable
+    let pool = builder.build_unchecked(manager);
+
+    let schemas = config.schemas.iter().map(|s| s.to_uppercase()).collect();
+    Datasource { pool, schemas }
+}
+
+impl Datasource {
+    /// pool is exhausted, when all connections are in use
+    fn error(&self, name: &str, err: r2d2::Error) -> DatasourceError {
+        let state = self.pool.state();
+        if state.connections == self.pool.max_size() && state.idle_connections == 0 {
+            DatasourceError::Exhausted(name.to_string())
+        } else {
+            DatasourceError::Unavailable(name.to_string(), err.to_string())
+        }
+    }
+}
+
+impl PoolState {
+    pub fn is_exhausted(&self) -> bool {
+        self.connections == self.max_size && self.idle == 0
+    }
 }
 
-pub fn create_datasource(config:&super::config::DbConnection) -> Result<(), String> {
-    let mut ds = (*DATASOURCE).write()
-        .map_err(|_err| format!("Can not get lock for datasource creation"))?;
+impl Datasources {
+    pub fn new(config: &ServerConfig) -> Result<Datasources, String> {
+        let mut sources = HashMap::with_capacity(config.datasources.len() + 1);
+        sources.insert(DEFAULT_DATASOURCE.to_string(), new_datasource(&config.connection));
+
+        for (name, datasource) in config.datasources.iter() {
+            if name == DEFAULT_DATASOURCE {
+                return Err(format!("Datasource name {} is reserved for connection section", name));
+            }
+            if datasource.schemas.is_empty() {
+                return Err(format!("Datasource {} has no schemas", name));
+            }
+            sources.insert(name.clone(), new_datasource(datasource));
+        }
+
+        Ok(Datasources { sources })
+    }
+
+    /// connection of default datasource
+    pub fn get_connection(&self) -> Result<Connection, DatasourceError> {
+        self.get_named_connection(DEFAULT_DATASOURCE)
+    }
+
+    /// connection from pool of datasource, waits until connection timeout of pool
+    pub fn get_named_connection(&self, name: &str) -> Result<Connection, DatasourceError> {
+        let datasource = self.find(name)?;
+        datasource.pool.get().map_err(|err| datasource.error(name, err))
+    }
 
-    if let None = *ds {
-        let datasource = new_datasource(config)?;
-        *ds = Some(datasource);
-    };
+    /// connection from pool of datasource, waits only until timeout
+    pub fn get_connection_timeout(&self, name: &str, timeout: Duration) -> Result<Connection, DatasourceError> {
+        let datasource = self.find(name)?;
+        datasource.pool.get_timeout(timeout).map_err(|err| datasource.error(name, err))
+    }
+
+    /// longest wait for connection of pool
+    pub fn connection_timeout(&self, name: &str) -> Result<Duration, DatasourceError> {
+        Ok(self.find(name)?.pool.connection_timeout())
+    }
+
+    /// current counts of connections in pool of datasource
+    pub fn state(&self, name: &str) -> Result<PoolState, DatasourceError> {
+        let pool = &self.find(name)?.pool;
+        let state = pool.state();
+        Ok(PoolState { connections: state.connections, idle: state.idle_connections, max_size: pool.max_size() })
+    }
+
+    /// names of all datasources, default first
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.sources.keys().filter(|n| *n != DEFAULT_DATASOURCE).cloned().collect();
+        names.sort();
+        names.insert(0, DEFAULT_DATASOURCE.to_string());
+        names
+    }
+
+    fn find(&self, name: &str) -> Result<&Datasource, DatasourceError> {
+        self.sources.get(name).ok_or_else(|| DatasourceError::NotConfigured(name.to_string()))
+    }
+
+    /// names of datasources with their schemas in upper case, empty for default datasource
+    pub fn schemas(&self) -> Vec<(String, Vec<String>)> {
+        self.sources.iter().map(|(name, datasource)| (name.clone(), datasource.schemas.clone())).collect()
+    }
+}
 
-    Ok(())
+impl DatasourceError {
+    /// seconds until client can retry request
+    pub fn retry_after(&self) -> Option<u64> {
+        match self {
+            DatasourceError::NotConfigured(_)   => None,
+            DatasourceError::Exhausted(_)       => Some(EXHAUSTED_RETRY_AFTER),
+            DatasourceError::Unavailable(_, _)  => Some(UNAVAILABLE_RETRY_AFTER),
+        }
+    }
 }
 
-pub fn get_connection() -> Result<Connection, String> {
-    let ds = (*DATASOURCE).read().unwrap();
-    let cc = ds.as_ref().unwrap();
-    // oracle::connect(&cc.url, &cc.user, &cc.pw)
-    cc.get().map_err(|err|format!("Connect to db err: {:?}", err))
+impl fmt::Display for DatasourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DatasourceError::NotConfigured(name) => write!(f, "Datasource {} is not configured", name),
+            DatasourceError::Exhausted(name) => write!(f, "All connections of datasource {} are in use", name),
+            DatasourceError::Unavailable(name, err) => write!(f, "Connect to db {} err: {}", name, err),
+        }
+    }
 }
@@ -0,0 +1,224 @@
+use std::fmt;
+
+use actix_web::{HttpRequest, HttpResponse, ResponseError};
+use actix_web::dev::HttpResponseBuilder;
+use actix_web::error::BlockingError;
+use actix_web::http::{header, StatusCode};
+use serde::Serialize;
+
+use crate::backend::BackendError;
+use crate::security::{SecurityContext, DEVELOPER};
+use crate::telemetry::Trace;
+use super::DatasourceError;
+
+// see: https://tools.ietf.org/html/rfc7807
+const PROBLEM_JSON: &str = "application/problem+json";
+
+/// JSON body of all errors: problem details with stable code of error
+#[derive(Serialize)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub problem_type: &'static str,
+    pub title:        &'static str,
+    pub status:       u16,
+    pub code:         &'static str,
+    pub detail:       String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id:   Option<String>,
+}
+
+impl Problem {
+    pub fn new(status: StatusCode, code: &'static str, detail: String) -> Problem {
+        Problem {
+            problem_type: "about:blank",
+            title: status.canonical_reason().unwrap_or("Error"),
+            status: status.as_u16(),
+            code,
+            detail,
+            request_id: None,
+        }
+    }
+
+    /// body of response with status and headers from builder
+    pub fn respond(&self, mut builder: HttpResponseBuilder) -> HttpResponse {
+        builder
+            .content_type(PROBLEM_JSON)
+            .body(serde_json::to_string(self).unwrap_or_default())
+    }
+}
+
+/// Error of request, rendered as problem details with status and code by kind of error
+#[derive(Debug)]
+pub enum ApiError {
+    /// parameters, filter, order or paging can not be used for query
+    BadRequest(String),
+    /// schema, table or other resource is not exposed
+    NotFound(String),
+    /// query returned no rows (ORA-01403)
+    NoData(String),
+    /// constraint is violated (ORA-00001, ORA-02291, ORA-02292)
+    Conflict { code: &'static str, detail: String },
+    /// statement was interrupted by timeout or cancellation (ORA-01013)
+    Timeout { code: &'static str, detail: String },
+    /// connection of datasource is not available
+    Datasource(DatasourceError),
+    /// access to data can not be recorded in audit log, data is not returned
+    AuditUnavailable(String),
+    /// other error of database, message is shown only to developers
+    Database { ora: i32, message: String },
+    /// error of server, message is shown only to developers
+    Internal(String),
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_)   => StatusCode::BAD_REQUEST,
+            ApiError::NotFound(_)     => StatusCode::NOT_FOUND,
+            ApiError::NoData(_)       => StatusCode::NOT_FOUND,
+            ApiError::Conflict { .. } => StatusCode::CONFLICT,
+            ApiError::Timeout { .. }  => StatusCode::GATEWAY_TIMEOUT,
+            ApiError::Datasource(DatasourceError::NotConfigured(_)) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::Datasource(_)   => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::AuditUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Database { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::Internal(_)     => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// stable code for clients, unlike detail
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_)        => "invalid_request",
+            ApiError::NotFound(_)          => "not_found",
+            ApiError::NoData(_)            => "no_data_found",
+            ApiError::Conflict { code, .. } => code,
+            ApiError::Timeout { code, .. } => code,
+            ApiError::Datasource(DatasourceError::NotConfigured(_))  => "datasource_not_configured",
+            ApiError::Datasource(DatasourceError::Exhausted(_))      => "pool_exhausted",
+            ApiError::Datasource(DatasourceError::Unavailable(_, _)) => "database_unavailable",
+            ApiError::AuditUnavailable(_)  => "audit_unavailable",
+            ApiError::Database { .. }      => "database_error",
+            ApiError::Internal(_)          => "internal_error",
+        }
+    }
+
+    /// description of error; messages of database and server can contain sql and are shown only to developers
+    pub fn detail(&self, developer: bool) -> String {
+        match self {
+            ApiError::BadRequest(detail) | ApiError::NotFound(detail) | ApiError::NoData(detail) => detail.clone(),
+            ApiError::Conflict { detail, .. } | ApiError::Timeout { detail, .. } => detail.clone(),
+            ApiError::Datasource(DatasourceError::Unavailable(name, _)) if !developer =>
+                format!("Database of datasource {} is not available", name),
+            ApiError::Datasource(err) => err.to_string(),
+            ApiError::AuditUnavailable(message) if developer => message.clone(),
+            ApiError::AuditUnavailable(_) => "Access can not be recorded in audit log".to_string(),
+            ApiError::Database { message, .. } if developer => message.clone(),
+            ApiError::Database { ora, .. } => format!("Database error ORA-{:05}", ora),
+            ApiError::Internal(message) if developer => message.clone(),
+            ApiError::Internal(_) => "Internal server error".to_string(),
+        }
+    }
+
+    /// problem details for user of request, with id of request for support
+    pub fn response(&self, req: &HttpRequest) -> HttpResponse {
+        let developer = req.extensions().get::<SecurityContext>().map(|ctx| ctx.is_member(DEVELOPER)).unwrap_or(false);
+        let trace = Trace::of(req);
+        let mut problem = Problem::new(self.status(), self.code(), self.detail(developer));
+        if !trace.request_id().is_empty() {
+            problem.request_id = Some(trace.request_id().to_string());
+        }
+        problem.respond(self.builder())
+    }
+
+    fn builder(&self) -> HttpResponseBuilder {
+        let mut builder = HttpResponse::build(self.status());
+        if let ApiError::Datasource(err) = self {
+            if let Some(seconds) = err.retry_after() {
+                builder.header(header::RETRY_AFTER, seconds.to_string());
+            }
+        }
+        builder
+    }
+
+    /// ORA errors with meaning for clients, other errors of database are errors of server
+    fn from_ora(ora: i32, message: String) -> ApiError {
+        let conflict = |code, detail: &str| ApiError::Conflict { code, detail: detail.to_string() };
+        match ora {
+            1    => conflict("unique_violation", "Row with same unique key already exists"),
+            2291 => conflict("parent_key_not_found", "Parent row of foreign key does not exist"),
+            2292 => conflict("child_record_found", "Row is referenced by rows of other table"),
+            1403 => ApiError::NoData("No data found".to_string()),
+            1013 => ApiError::Timeout { code: "statement_cancelled", detail: "Statement was cancelled".to_string() },
+            _    => ApiError::Database { ora, message },
+        }
+    }
+}
+
+impl From<BackendError> for ApiError {
+    fn from(err: BackendError) -> Self {
+        match err {
+            BackendError::Datasource(err) => ApiError::Datasource(err),
+            BackendError::Query(message) => ApiError::Internal(message),
+            BackendError::Database { code, message } => ApiError::from_ora(code, message),
+            err @ BackendError::Timeout { .. } => ApiError::Timeout { code: "statement_timeout", detail: err.to_string() },
+            BackendError::Cancelled => ApiError::Timeout { code: "statement_cancelled", detail: "Statement was cancelled".to_string() },
+        }
+    }
+}
+
+/// error of blocking work, e.g. query in thread pool
+impl From<BlockingError<ApiError>> for ApiError {
+    fn from(err: BlockingError<ApiError>) -> Self {
+        match err {
+            BlockingError::Error(err) => err,
+            BlockingError::Canceled => ApiError::Internal("Blocking work was canceled".to_string()),
+        }
+    }
+}
+
+/// description, which can be shown to all users
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.detail(false))
+    }
+}
+
+/// response without request, e.g. in middlewares
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.status()
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        Problem::new(self.status(), self.code(), self.detail(false)).respond(self.builder())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ora_errors_are_mapped_to_status_without_message() {
+        let database = |code, message: &str| ApiError::from(BackendError::Database { code, message: message.to_string() });
+
+        let err = database(1, "ORA-00001: unique constraint (HR.EMP_PK) violated");
+        assert_eq!((err.status(), err.code()), (StatusCode::CONFLICT, "unique_violation"));
+        assert!(!err.detail(false).contains("EMP_PK"));
+
+        let err = database(2292, "ORA-02292: integrity constraint (HR.EMP_DEPT_FK) violated - child record found");
+        assert_eq!((err.status(), err.code()), (StatusCode::CONFLICT, "child_record_found"));
+
+        let err = database(1403, "ORA-01403: no data found");
+        assert_eq!((err.status(), err.code()), (StatusCode::NOT_FOUND, "no_data_found"));
+
+        let err = database(1013, "ORA-01013: user requested cancel of current operation");
+        assert_eq!((err.status(), err.code()), (StatusCode::GATEWAY_TIMEOUT, "statement_cancelled"));
+
+        let err = database(904, "ORA-00904: \"SALARY\": invalid identifier in SELECT SALARY FROM HR.EMPLOYEES");
+        assert_eq!((err.status(), err.code()), (StatusCode::INTERNAL_SERVER_ERROR, "database_error"));
+        assert_eq!(err.detail(false), "Database error ORA-00904");
+        assert!(err.detail(true).contains("SELECT SALARY"));
+    }
+}
@@ -0,0 +1,170 @@
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+use chrono::Local;
+use serde_json::{Map, Value};
+use slog::{o, Drain, Key, Level, OwnedKVList, Record, Serializer, KV};
+
+use super::config::{LogFormat, LogLevel, LoggingConfig};
+use super::SimpleResult;
+
+/// Records as JSON objects, one per line
+struct JsonDrain {
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+/// key-values of record and of logger as JSON fields
+struct JsonFields(Map<String, Value>);
+
+impl Serializer for JsonFields {
+    fn emit_arguments(&mut self, key: Key, val: &fmt::Arguments) -> slog::Result {
+        self.0.insert(key.to_string(), Value::String(val.to_string()));
+        Ok(())
+    }
+
+    fn emit_usize(&mut self, key: Key, val: usize) -> slog::Result {
+        self.0.insert(key.to_string(), Value::from(val));
+        Ok(())
+    }
+
+    fn emit_u32(&mut self, key: Key, val: u32) -> slog::Result {
+        self.0.insert(key.to_string(), Value::from(val));
+        Ok(())
+    }
+
+    fn emit_u64(&mut self, key: Key, val: u64) -> slog::Result {
+        self.0.insert(key.to_string(), Value::from(val));
+        Ok(())
+    }
+
+    fn emit_i64(&mut self, key: Key, val: i64) -> slog::Result {
+        self.0.insert(key.to_string(), Value::from(val));
+        Ok(())
+    }
+
+    fn emit_bool(&mut self, key: Key, val: bool) -> slog::Result {
+        self.0.insert(key.to_string(), Value::from(val));
+        Ok(())
+    }
+
+    fn emit_none(&mut self, key: Key) -> slog::Result {
+        self.0.insert(key.to_string(), Value::Null);
+        Ok(())
+    }
+}
+
+impl Drain for JsonDrain {
+    type Ok = ();
+    type Err = io::Error;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> io::Result<()> {
+        let mut fields = JsonFields(Map::new());
+        values.serialize(record, &mut fields)?;
+        record.kv().serialize(record, &mut fields)?;
+
+        let mut fields = fields.0;
+        fields.insert("ts".to_string(), Value::String(Local::now().to_rfc3339()));
+        fields.insert("level".to_string(), Value::String(record.level().as_str().to_lowercase()));
+        fields.insert("module".to_string(), Value::String(record.module().to_string()));
+        fields.insert("msg".to_string(), Value::String(record.msg().to_string()));
+
+        let mut out = self.out.lock().unwrap();
+        serde_json::to_writer(&mut *out, &fields)?;
+        writeln!(out)?;
+        out.flush()
+    }
+}
+
+fn level(level: LogLevel) -> Level {
+    match level {
+        LogLevel::Critical => Level::Critical,
+        LogLevel::Error    => Level::Error,
+        LogLevel::Warning  => Level::Warning,
+        LogLevel::Info     => Level::Info,
+        LogLevel::Debug    => Level::Debug,
+        LogLevel::Trace    => Level::Trace,
+    }
+}
+
+/// logger by config: text or JSON, to stdout or appended to file
+pub fn setup_logging(config: &LoggingConfig) -> SimpleResult<slog::Logger> {
+    let file = match &config.file {
+        Some(path) => {
+            let path = Path::new(path);
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)
+                    .map_err(|err| format!("Can not create log directory {:?}: {}", dir, err))?;
+            }
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|err| format!("Can not open log file {:?}: {}", path, err))?;
+            Some(file)
+        },
+        None => None
+    };
+
+    let drain = match (config.format, file) {
+        (LogFormat::Json, Some(file)) => slog_async::Async::new(JsonDrain { out: Mutex::new(Box::new(file)) }.fuse()).build(),
+        (LogFormat::Json, None) => slog_async::Async::new(JsonDrain { out: Mutex::new(Box::new(io::stdout())) }.fuse()).build(),
+        (LogFormat::Text, Some(file)) => {
+            let decorator = slog_term::PlainDecorator::new(file);
+            slog_async::Async::new(slog_term::FullFormat::new(decorator).build().fuse()).build()
+        },
+        (LogFormat::Text, None) => {
+            let decorator = slog_term::TermDecorator::new().build();
+            slog_async::Async::new(slog_term::CompactFormat::new(decorator).build().fuse()).build()
+        }
+    };
+
+    let drain = drain.filter_level(level(config.level)).fuse();
+    Ok(slog::Logger::root(drain, o!()))
+}
+
+/// logger without output, for disabled components and tests
+pub fn discard_logging() -> slog::Logger {
+    slog::Logger::root(slog::Discard, o!())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use slog::info;
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn records_are_json_lines() {
+        let buffer = Buffer::default();
+        let drain = JsonDrain { out: Mutex::new(Box::new(buffer.clone())) };
+        let log = slog::Logger::root(drain.fuse(), o!("component" => "backend"));
+
+        info!(log, "select"; "sql" => "SELECT 1 FROM DUAL", "binds" => 2usize);
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let record: Value = serde_json::from_str(output.lines().next().unwrap()).unwrap();
+        assert_eq!(record["level"], "info");
+        assert_eq!(record["msg"], "select");
+        assert_eq!(record["component"], "backend");
+        assert_eq!(record["sql"], "SELECT 1 FROM DUAL");
+        assert_eq!(record["binds"], 2);
+    }
+}
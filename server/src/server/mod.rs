@@ -1,16 +1,24 @@
 pub mod config;
 mod datasource;
+mod error;
+mod logging;
 mod setup;
 
-pub use setup::setup_logging;
+pub use error::{ApiError, Problem};
+pub use logging::{setup_logging, discard_logging};
 pub use setup::setup_tls;
+pub use setup::https_server;
 pub use setup::setup_identity;
+pub use setup::setup_apikeys;
+pub use setup::setup_clientcert;
 pub use self::config::load_config;
 
 pub use datasource::{
-    create_datasource,
-    get_connection,
-    Connection
+    Connection,
+    Datasources,
+    DatasourceError,
+    PoolState,
+    DEFAULT_DATASOURCE
 };
 
 pub type SimpleResult<T> = Result<T, String>;
\ No newline at end of file
@@ -1,46 +1,116 @@
+use std::fmt;
+use std::io;
+use std::net::TcpListener;
 use std::path::Path;
+use std::sync::Arc;
 
-use openssl::ssl::{SslAcceptorBuilder, SslAcceptor, SslFiletype, SslMethod};
-
-use slog_async;
-use slog_term;
-use slog::{Drain,o};
-
-use super::config;
-
-/// setup logging
-// TODO: use logger everywhere
-// TODO: async logger:  https://github.com/zupzup/rust-web-example/blob/main/src/logging/mod.rs
-// TODO: actix example: https://www.zupzup.org/rust-webapp/index.html
-// TODO: see also:      https://github.com/zupzup/rust-web-example/blob/main/src/handlers/mod.rs
-// TODO: see also:      https://rust.graystorm.com/2019/07/20/better-logging-for-the-web-application/
-pub fn setup_logging() -> slog::Logger {
-    let decorator = slog_term::TermDecorator::new().build();
-    let drain = slog_term::CompactFormat::new(decorator).build().fuse();
-    let drain = slog_async::Async::new(drain).build().fuse();
-    slog::Logger::root(drain, o!())
-}
+use actix_http::{Error, HttpService, Request, Response};
+use actix_service::{map_config, IntoServiceFactory, Service, ServiceFactory};
+use actix_web::dev::{AppConfig, MessageBody, Server};
+use actix_web::rt::net::TcpStream;
+use actix_tls::openssl::SslStream;
+use openssl::ssl::{AlpnError, SslAcceptor, SslFiletype, SslMethod, SslVerifyMode};
+use openssl::x509::X509Name;
+
+use super::{config, SimpleResult};
 
-/// load ssl keys
+/// load ssl keys, HTTP/2 and HTTP/1.1 are negotiated by ALPN
 // to create a self-signed temporary cert for testing:
 // `openssl req -x509 -newkey rsa:4096 -nodes -keyout key.pem -out cert.pem -days 365 -subj '/CN=localhost'`
-pub fn setup_tls(settings: &config::HttpListener) -> SslAcceptorBuilder {
+pub fn setup_tls(settings: &config::HttpListener) -> SimpleResult<SslAcceptor> {
     let certfilepath = Path::new(&settings.tls_cert);
     let keyfilepath = Path::new(&settings.tls_key);
+    let err = |context: &str, path: &Path, err: openssl::error::ErrorStack| format!("{} {}: {}", context, path.display(), err);
 
-    let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls()).unwrap();
+    let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls())
+        .map_err(|err| format!("Can not create TLS acceptor: {}", err))?;
 
     builder
         .set_private_key_file(keyfilepath, SslFiletype::PEM)
-        .unwrap();
-    builder.set_certificate_chain_file(certfilepath).unwrap();
-    builder
+        .map_err(|e| err("Can not load TLS key", keyfilepath, e))?;
+    builder.set_certificate_chain_file(certfilepath)
+        .map_err(|e| err("Can not load TLS certificate", certfilepath, e))?;
+
+    // optional client certificates, clients without certificate use jwt or api keys
+    if let Some(client_auth) = &settings.client_auth {
+        let cafilepath = Path::new(&client_auth.ca);
+        builder.set_ca_file(cafilepath)
+            .map_err(|e| err("Can not load CA of client certificates", cafilepath, e))?;
+        let ca_names = X509Name::load_client_ca_file(cafilepath)
+            .map_err(|e| err("Can not load CA of client certificates", cafilepath, e))?;
+        builder.set_client_ca_list(ca_names);
+        builder.set_verify(SslVerifyMode::PEER);
+        builder.set_session_id_context(b"foundation")
+            .map_err(|err| format!("Can not set TLS session context: {}", err))?;
+    }
+
+    builder.set_alpn_select_callback(|_, protocols| {
+        const H2: &[u8] = b"\x02h2";
+        const H11: &[u8] = b"\x08http/1.1";
+        if protocols.windows(3).any(|window| window == H2) {
+            Ok(b"h2")
+        } else if protocols.windows(9).any(|window| window == H11) {
+            Ok(b"http/1.1")
+        } else {
+            Err(AlpnError::NOACK)
+        }
+    });
+    builder.set_alpn_protos(b"\x08http/1.1\x02h2")
+        .map_err(|err| format!("Can not set ALPN protocols: {}", err))?;
+
+    Ok(builder.build())
+}
+
+/// https server of application; identity of client certificate is mapped once per connection
+/// and cloned into every request of connection, for HTTP/1.1 keep-alive and all streams of HTTP/2
+pub fn https_server<F, I, S, B>(
+    listener:   TcpListener,
+    acceptor:   SslAcceptor,
+    clientcert: crate::security::ClientCertService,
+    keep_alive: usize,
+    factory:    F,
+) -> io::Result<Server>
+    where
+        F: Fn() -> I + Send + Clone + 'static,
+        I: IntoServiceFactory<S>,
+        S: ServiceFactory<Config = AppConfig, Request = Request>,
+        S::Error: Into<Error> + 'static,
+        S::InitError: fmt::Debug,
+        S::Response: Into<Response<B>> + 'static,
+        <S::Service as Service>::Future: 'static,
+        B: MessageBody + 'static,
+{
+    Server::build()
+        .listen("foundation", listener, move || {
+            let clientcert = clientcert.clone();
+            // application does not generate urls, config of host and scheme is not used
+            HttpService::build()
+                .keep_alive(keep_alive)
+                .on_connect(move |stream: &SslStream<TcpStream>| clientcert.connection_data(stream.ssl()))
+                .finish(map_config(factory(), |_| AppConfig::default()))
+                .openssl(acceptor.clone())
+        })
+        .map(|server| server.run())
 }
 
 
-pub fn setup_identity(settings: &config::JwtConfig) -> crate::security::IdentityService {
+pub fn setup_identity(settings: &config::JwtConfig, metrics: Arc<crate::metrics::Metrics>, log: slog::Logger) -> crate::security::IdentityService {
     crate::security::IdentityService::new(
         settings.issuer.to_string(), 
-        Path::new(&settings.public_key).to_path_buf()
+        Path::new(&settings.public_key).to_path_buf(),
+        settings.cookie.clone(),
+        metrics,
+        log
     )
+}
+
+pub fn setup_clientcert(settings: &config::HttpListener) -> Result<crate::security::ClientCertService, String> {
+    crate::security::ClientCertService::new(&settings.client_auth)
+}
+
+pub fn setup_apikeys(settings: &Option<config::ApiKeysConfig>) -> Result<crate::security::ApiKeyService, String> {
+    match settings {
+        Some(settings) => crate::security::ApiKeyService::load(&settings.file),
+        None => Ok(crate::security::ApiKeyService::empty())
+    }
 }
\ No newline at end of file
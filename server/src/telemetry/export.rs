@@ -0,0 +1,199 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+use slog::{error, Logger};
+
+use super::{SpanData, SpanKind};
+use crate::server::config::TracingConfig;
+use crate::server::SimpleResult;
+
+const BATCH_SIZE: usize = 512;
+// port of OTLP/HTTP receiver
+const OTLP_PORT: u16 = 4318;
+const OTLP_PATH: &str = "/v1/traces";
+const OTLP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Receiver of batches of spans in OTLP JSON encoding
+pub enum Exporter {
+    /// OTLP/HTTP collector, e.g. sidecar of opentelemetry collector
+    Otlp { address: String, host: String, path: String },
+    /// local file with one export request per line, for offline use
+    File(File),
+}
+
+/// exporters by config; only plain http endpoints are supported
+pub fn exporters(config: &TracingConfig) -> SimpleResult<Vec<Exporter>> {
+    let mut exporters = Vec::new();
+
+    if let Some(endpoint) = &config.otlp_endpoint {
+        let rest = endpoint.strip_prefix("http://")
+            .ok_or_else(|| format!("Only http endpoints are supported for OTLP: {}", endpoint))?;
+        let (host, path) = match rest.find('/') {
+            Some(idx) => (&rest[..idx], &rest[idx..]),
+            None => (rest, OTLP_PATH)
+        };
+        let address = if host.contains(':') { host.to_string() } else { format!("{}:{}", host, OTLP_PORT) };
+        exporters.push(Exporter::Otlp { address, host: host.to_string(), path: path.to_string() });
+    }
+
+    if let Some(file) = &config.file {
+        let path = Path::new(file);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|err| format!("Can not create directory of spans {:?}: {}", dir, err))?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|err| format!("Can not open file of spans {:?}: {}", path, err))?;
+        exporters.push(Exporter::File(file));
+    }
+
+    Ok(exporters)
+}
+
+impl Exporter {
+    fn export(&mut self, body: &str) -> SimpleResult<()> {
+        match self {
+            Exporter::Otlp { address, host, path } => post(address, host, path, body),
+            Exporter::File(file) => writeln!(file, "{}", body)
+                .map_err(|err| format!("can not write spans: {}", err)),
+        }
+    }
+}
+
+/// minimal HTTP/1.1 request, connection is closed after response
+fn post(address: &str, host: &str, path: &str, body: &str) -> SimpleResult<()> {
+    let err = |err: std::io::Error| format!("can not export spans to {}: {}", address, err);
+
+    let addr = address.to_socket_addrs()
+        .map_err(err)?
+        .next()
+        .ok_or_else(|| format!("can not resolve {}", address))?;
+    let mut stream = TcpStream::connect_timeout(&addr, OTLP_TIMEOUT).map_err(err)?;
+    stream.set_read_timeout(Some(OTLP_TIMEOUT)).map_err(err)?;
+    stream.set_write_timeout(Some(OTLP_TIMEOUT)).map_err(err)?;
+
+    write!(
+        stream,
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        path, host, body.len(), body
+    ).map_err(err)?;
+
+    let mut status = String::new();
+    BufReader::new(stream).read_line(&mut status).map_err(err)?;
+    match status.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => Err(format!("can not export spans to {}: {}", address, status.trim()))
+    }
+}
+
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0).to_string()
+}
+
+/// `ExportTraceServiceRequest` in OTLP JSON encoding
+pub fn otlp_json(spans: &[SpanData], service_name: &str) -> Value {
+    let spans: Vec<Value> = spans.iter().map(|span| {
+        let attributes: Vec<Value> = span.attributes.iter()
+            .map(|(key, value)| json!({ "key": key, "value": { "stringValue": value } }))
+            .collect();
+        let kind = match span.kind {
+            SpanKind::Internal => 1,
+            SpanKind::Server => 2,
+            SpanKind::Client => 3,
+        };
+        let status = match &span.error {
+            Some(message) => json!({ "code": 2, "message": message }),
+            None => json!({ "code": 1 }),
+        };
+        let mut value = json!({
+            "traceId": span.trace_id.to_string(),
+            "spanId": span.span_id.to_string(),
+            "name": span.name,
+            "kind": kind,
+            "startTimeUnixNano": unix_nanos(span.start),
+            "endTimeUnixNano": unix_nanos(span.end),
+            "attributes": attributes,
+            "status": status,
+        });
+        if let Some(parent) = span.parent_id {
+            value["parentSpanId"] = Value::String(parent.to_string());
+        }
+        value
+    }).collect();
+
+    json!({
+        "resourceSpans": [{
+            "resource": { "attributes": [{ "key": "service.name", "value": { "stringValue": service_name } }] },
+            "scopeSpans": [{ "scope": { "name": "foundation" }, "spans": spans }]
+        }]
+    })
+}
+
+/// loop of background exporter: collect spans in batches and pass them to all exporters
+pub fn export_spans(receiver: Receiver<SpanData>, mut exporters: Vec<Exporter>, service_name: String, log: Logger) {
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    loop {
+        match receiver.recv_timeout(Duration::from_secs(1)) {
+            Ok(span) => {
+                batch.push(span);
+                while batch.len() < BATCH_SIZE {
+                    match receiver.try_recv() {
+                        Ok(span) => batch.push(span),
+                        Err(_) => break
+                    }
+                }
+            },
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => return
+        }
+
+        let body = otlp_json(&batch, &service_name).to_string();
+        for exporter in exporters.iter_mut() {
+            if let Err(err) = exporter.export(&body) {
+                error!(log, "can not export spans"; "spans" => batch.len(), "error" => err);
+            }
+        }
+        batch.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::telemetry::{SpanId, TraceId};
+
+    #[test]
+    fn spans_are_encoded_as_otlp_json() {
+        let start = UNIX_EPOCH + Duration::from_millis(1500);
+        let span = SpanData {
+            trace_id: TraceId([1; 16]),
+            span_id: SpanId([2; 8]),
+            parent_id: None,
+            name: "db.execute".to_string(),
+            kind: SpanKind::Client,
+            start,
+            end: start + Duration::from_millis(20),
+            attributes: vec![("db.system", "oracle".to_string())],
+            error: Some("ORA-01013".to_string()),
+        };
+
+        let request = otlp_json(&[span], "foundation");
+        let span = &request["resourceSpans"][0]["scopeSpans"][0]["spans"][0];
+        assert_eq!(span["traceId"], "01010101010101010101010101010101");
+        assert_eq!(span["spanId"], "0202020202020202");
+        assert!(span.get("parentSpanId").is_none());
+        assert_eq!(span["kind"], 3);
+        assert_eq!(span["startTimeUnixNano"], "1500000000");
+        assert_eq!(span["endTimeUnixNano"], "1520000000");
+        assert_eq!(span["attributes"][0], json!({ "key": "db.system", "value": { "stringValue": "oracle" } }));
+        assert_eq!(span["status"], json!({ "code": 2, "message": "ORA-01013" }));
+    }
+}
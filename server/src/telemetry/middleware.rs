@@ -0,0 +1,116 @@
+use std::future::{Future, Ready, ready};
+use std::pin::Pin;
+use std::task::{Poll, Context};
+
+use actix_web::{Error, HttpMessage};
+use actix_web::dev::{ServiceRequest, ServiceResponse, Service, Transform};
+use actix_web::http::{HeaderName, HeaderValue};
+
+use super::{TraceId, TraceParent, Tracer};
+
+pub const REQUEST_ID: &str = "x-request-id";
+pub const TRACEPARENT: &str = "traceparent";
+
+// ids of clients are accepted only if they are short and printable
+const MAX_REQUEST_ID: usize = 128;
+
+pub struct RequestTracingMiddleware<S> {
+    service: S,
+    tracer:  Tracer,
+}
+
+/// request id from header or new one
+fn request_id(req: &ServiceRequest) -> String {
+    req.headers()
+        .get(REQUEST_ID)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty() && v.len() <= MAX_REQUEST_ID && v.bytes().all(|b| b.is_ascii_graphic()))
+        .map(str::to_string)
+        .unwrap_or_else(|| TraceId::random().to_string())
+}
+
+impl<S,B> Service for RequestTracingMiddleware<S>
+    where
+        S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+        S::Future: 'static,
+        B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, ctx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(ctx)
+    }
+
+    fn call(&mut self, req: Self::Request) -> Self::Future {
+        let request_id = request_id(&req);
+        let parent = req.headers()
+            .get(TRACEPARENT)
+            .and_then(|v| v.to_str().ok())
+            .and_then(TraceParent::parse);
+        let route = req.match_pattern().unwrap_or_else(|| "unmatched".to_string());
+
+        let mut span = self.tracer.server_span(parent, request_id.clone(), &format!("{} {}", req.method(), route));
+        span.attribute("http.method", req.method());
+        span.attribute("http.target", req.path());
+        span.attribute("http.request_id", &request_id);
+        let trace = span.trace();
+        req.extensions_mut().insert(trace.clone());
+
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            // errors of middlewares are rendered by server, without headers of trace
+            let mut res = fut.await.inspect_err(|err| {
+                let status = err.as_response_error().status_code();
+                span.attribute("http.status_code", status.as_u16());
+            })?;
+
+            let status = res.status();
+            span.attribute("http.status_code", status.as_u16());
+            if status.is_server_error() {
+                span.error(status);
+            }
+
+            let headers = res.headers_mut();
+            if let Ok(value) = HeaderValue::from_str(&request_id) {
+                headers.insert(HeaderName::from_static(REQUEST_ID), value);
+            }
+            if let Ok(value) = HeaderValue::from_str(&trace.traceparent().to_string()) {
+                headers.insert(HeaderName::from_static(TRACEPARENT), value);
+            }
+            Ok(res)
+        })
+    }
+}
+
+/// Request id and W3C trace context of requests, with server span of every request
+#[derive(Clone)]
+pub struct RequestTracing {
+    tracer: Tracer,
+}
+
+impl RequestTracing {
+    pub fn new(tracer: Tracer) -> Self {
+        Self { tracer }
+    }
+}
+
+impl <S,B> Transform<S> for RequestTracing
+    where
+        S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+        S::Future: 'static,
+        B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RequestTracingMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestTracingMiddleware { service, tracer: self.tracer.clone() }))
+    }
+}
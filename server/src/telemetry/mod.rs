@@ -0,0 +1,310 @@
+mod export;
+mod middleware;
+
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, SyncSender};
+use std::thread;
+use std::time::SystemTime;
+
+use actix_web::HttpMessage;
+use slog::{warn, Logger};
+
+use crate::server::config::TracingConfig;
+use crate::server::SimpleResult;
+
+pub use middleware::RequestTracing;
+
+const QUEUE_SIZE: usize = 10000;
+
+/// W3C trace id: 16 random bytes, never all zero
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TraceId([u8; 16]);
+
+/// W3C span id: 8 random bytes, never all zero
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SpanId([u8; 8]);
+
+fn random<const N: usize>() -> [u8; N] {
+    let mut bytes = [0u8; N];
+    while bytes.iter().all(|b| *b == 0) {
+        openssl::rand::rand_bytes(&mut bytes).expect("random bytes");
+    }
+    bytes
+}
+
+fn parse_hex<const N: usize>(hex: &str) -> Option<[u8; N]> {
+    if hex.len() != N * 2 || !hex.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b)) {
+        return None;
+    }
+    let mut bytes = [0u8; N];
+    for (idx, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[idx * 2..idx * 2 + 2], 16).ok()?;
+    }
+    if bytes.iter().all(|b| *b == 0) { None } else { Some(bytes) }
+}
+
+fn write_hex(f: &mut fmt::Formatter<'_>, bytes: &[u8]) -> fmt::Result {
+    bytes.iter().try_for_each(|b| write!(f, "{:02x}", b))
+}
+
+impl TraceId {
+    pub fn random() -> Self {
+        TraceId(random())
+    }
+}
+
+impl SpanId {
+    pub fn random() -> Self {
+        SpanId(random())
+    }
+}
+
+impl fmt::Display for TraceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_hex(f, &self.0)
+    }
+}
+
+impl fmt::Display for SpanId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_hex(f, &self.0)
+    }
+}
+
+/// Context of caller from `traceparent` header: `00-<trace id>-<parent span id>-<flags>`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TraceParent {
+    pub trace_id: TraceId,
+    pub span_id:  SpanId,
+    pub sampled:  bool,
+}
+
+impl TraceParent {
+    /// header of unknown version is read by format of version 00, version ff is invalid
+    pub fn parse(header: &str) -> Option<TraceParent> {
+        let parts: Vec<&str> = header.trim().split('-').collect();
+        if parts.len() < 4 || parts[0].len() != 2 || parts[0] == "ff" || (parts[0] == "00" && parts.len() != 4) {
+            return None;
+        }
+        let trace_id = TraceId(parse_hex(parts[1])?);
+        let span_id = SpanId(parse_hex(parts[2])?);
+        if parts[3].len() != 2 {
+            return None;
+        }
+        let flags = u8::from_str_radix(parts[3], 16).ok()?;
+        Some(TraceParent { trace_id, span_id, sampled: flags & 1 == 1 })
+    }
+}
+
+impl fmt::Display for TraceParent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "00-{}-{}-{:02x}", self.trace_id, self.span_id, self.sampled as u8)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SpanKind {
+    Internal,
+    Server,
+    Client,
+}
+
+/// Finished span for export
+#[derive(Debug)]
+pub struct SpanData {
+    pub trace_id:   TraceId,
+    pub span_id:    SpanId,
+    pub parent_id:  Option<SpanId>,
+    pub name:       String,
+    pub kind:       SpanKind,
+    pub start:      SystemTime,
+    pub end:        SystemTime,
+    pub attributes: Vec<(&'static str, String)>,
+    pub error:      Option<String>,
+}
+
+struct Inner {
+    sender:  Option<SyncSender<SpanData>>,
+    dropped: AtomicUsize,
+    log:     Logger,
+}
+
+/// Exporter of spans; spans are written asynchronously by background exporter
+#[derive(Clone)]
+pub struct Tracer {
+    inner: Arc<Inner>,
+}
+
+impl Tracer {
+    pub fn start(config: &Option<TracingConfig>, log: Logger) -> SimpleResult<Tracer> {
+        let config = match config {
+            Some(config) => config,
+            None => return Ok(Tracer::disabled())
+        };
+
+        let exporters = export::exporters(config)?;
+        if exporters.is_empty() {
+            return Ok(Tracer::disabled());
+        }
+
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_SIZE);
+        let service_name = config.service_name.clone();
+        let exporter_log = log.clone();
+        thread::Builder::new()
+            .name("span-exporter".to_string())
+            .spawn(move || export::export_spans(receiver, exporters, service_name, exporter_log))
+            .map_err(|err| format!("Can not start span exporter: {}", err))?;
+
+        Ok(Tracer { inner: Arc::new(Inner { sender: Some(sender), dropped: AtomicUsize::new(0), log }) })
+    }
+
+    /// ids are propagated, but spans are not exported
+    pub fn disabled() -> Tracer {
+        Tracer { inner: Arc::new(Inner { sender: None, dropped: AtomicUsize::new(0), log: crate::server::discard_logging() }) }
+    }
+
+    /// span of request in server: child of span of caller or root of new trace
+    pub fn server_span(&self, parent: Option<TraceParent>, request_id: String, name: &str) -> Span {
+        let trace = Trace {
+            tracer: self.clone(),
+            trace_id: parent.map(|p| p.trace_id).unwrap_or_else(TraceId::random),
+            span_id: parent.map(|p| p.span_id).unwrap_or_else(SpanId::random),
+            sampled: parent.map(|p| p.sampled).unwrap_or(true),
+            request_id,
+        };
+        let mut span = trace.span_of_kind(name, SpanKind::Server);
+        if let (Some(data), None) = (&mut span.data, parent) {
+            data.parent_id = None;
+        }
+        span
+    }
+
+    /// enqueue span for export; never blocks request processing
+    fn export(&self, span: SpanData) {
+        if let Some(sender) = &self.inner.sender {
+            if sender.try_send(span).is_err() {
+                let dropped = self.inner.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                warn!(self.inner.log, "span queue is not available, span dropped"; "dropped" => dropped);
+            }
+        }
+    }
+}
+
+/// Trace of request: ids for correlation and parent of new spans
+#[derive(Clone)]
+pub struct Trace {
+    tracer:     Tracer,
+    trace_id:   TraceId,
+    span_id:    SpanId, // current span, parent of new spans
+    sampled:    bool,
+    request_id: String,
+}
+
+impl Trace {
+    /// trace without export, for tests and background work
+    pub fn disabled() -> Trace {
+        Trace { tracer: Tracer::disabled(), trace_id: TraceId::random(), span_id: SpanId::random(), sampled: false, request_id: String::new() }
+    }
+
+    /// trace of request, created by `RequestTracing`
+    pub fn of(req: &impl HttpMessage) -> Trace {
+        req.extensions().get::<Trace>().cloned().unwrap_or_else(Trace::disabled)
+    }
+
+    pub fn trace_id(&self) -> TraceId {
+        self.trace_id
+    }
+
+    pub fn request_id(&self) -> &str {
+        &self.request_id
+    }
+
+    /// header value for calls from current span
+    pub fn traceparent(&self) -> TraceParent {
+        TraceParent { trace_id: self.trace_id, span_id: self.span_id, sampled: self.sampled }
+    }
+
+    /// start child span of current span, finished when dropped
+    pub fn span(&self, name: &str) -> Span {
+        self.span_of_kind(name, SpanKind::Internal)
+    }
+
+    pub fn span_of_kind(&self, name: &str, kind: SpanKind) -> Span {
+        let data = SpanData {
+            trace_id: self.trace_id,
+            span_id: SpanId::random(),
+            parent_id: Some(self.span_id),
+            name: name.to_string(),
+            kind,
+            start: SystemTime::now(),
+            end: SystemTime::now(),
+            attributes: Vec::new(),
+            error: None,
+        };
+        Span { trace: self.clone(), data: Some(data) }
+    }
+}
+
+/// Span in progress, exported when dropped
+pub struct Span {
+    trace: Trace,
+    data:  Option<SpanData>,
+}
+
+impl Span {
+    pub fn attribute(&mut self, key: &'static str, value: impl ToString) {
+        if let Some(data) = &mut self.data {
+            data.attributes.push((key, value.to_string()));
+        }
+    }
+
+    pub fn error(&mut self, message: impl ToString) {
+        if let Some(data) = &mut self.data {
+            data.error = Some(message.to_string());
+        }
+    }
+
+    /// trace with this span as parent of new spans
+    pub fn trace(&self) -> Trace {
+        let mut trace = self.trace.clone();
+        if let Some(data) = &self.data {
+            trace.span_id = data.span_id;
+        }
+        trace
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if let Some(mut data) = self.data.take() {
+            if self.trace.sampled {
+                data.end = SystemTime::now();
+                self.trace.tracer.export(data);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn traceparent_is_parsed_and_formatted() {
+        let header = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let parent = TraceParent::parse(header).unwrap();
+        assert_eq!(parent.trace_id.to_string(), "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(parent.span_id.to_string(), "00f067aa0ba902b7");
+        assert!(parent.sampled);
+        assert_eq!(parent.to_string(), header);
+
+        assert!(!TraceParent::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00").unwrap().sampled);
+        assert!(TraceParent::parse("00-00000000000000000000000000000000-00f067aa0ba902b7-01").is_none());
+        assert!(TraceParent::parse("00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01").is_none());
+        assert!(TraceParent::parse("ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").is_none());
+        assert!(TraceParent::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra").is_none());
+        assert!(TraceParent::parse("01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra").is_some());
+    }
+}
@@ -0,0 +1,343 @@
+use actix_web::test;
+use actix_web::http::{header, StatusCode};
+use serde_json::json;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::Harness;
+use crate::backend::MemoryBackend;
+use crate::server::config::TimeoutsConfig;
+
+const API: &[&str] = &["BASE_ACCESS"];
+const DEVELOPER: &[&str] = &["DEVELOPER"];
+
+// `{"department_id":"10"}`
+const DEPARTMENT_10: &str = "%7B%22department_id%22%3A%2210%22%7D";
+// `{}`
+const EMPTY_FILTER: &str = "%7B%7D";
+
+fn ids(rows: &serde_json::Value) -> Vec<i64> {
+    rows.as_array().unwrap().iter().filter_map(|r| r["id"].as_i64()).collect()
+}
+
+#[actix_rt::test]
+async fn health_is_public() {
+    let harness = Harness::new();
+    let resp = harness.get("/mgmt/health", None).await;
+    assert_eq!(resp.status, StatusCode::OK);
+    assert_eq!(resp.text(), "OK");
+}
+
+#[actix_rt::test]
+async fn readiness_checks_backend_and_metainfo() {
+    let resp = Harness::new().get("/mgmt/health/live", None).await;
+    assert_eq!(resp.status, StatusCode::OK);
+
+    let resp = Harness::new().get("/mgmt/health/ready", None).await;
+    assert_eq!(resp.status, StatusCode::OK);
+    let readiness = resp.json();
+    assert_eq!(readiness["status"], json!("ready"));
+    let checks: Vec<&str> = readiness["checks"].as_array().unwrap().iter().map(|c| c["name"].as_str().unwrap()).collect();
+    assert_eq!(checks, vec!["pool:default", "database:default", "metainfo"]);
+
+    let resp = Harness::with_backend(Arc::new(MemoryBackend::exhausted())).get("/mgmt/health/ready", None).await;
+    assert_eq!(resp.status, StatusCode::SERVICE_UNAVAILABLE);
+    let readiness = resp.json();
+    assert_eq!(readiness["status"], json!("degraded"));
+    assert_eq!(readiness["checks"][0]["ok"], json!(false));
+    assert_eq!(readiness["checks"][2]["ok"], json!(true));
+}
+
+#[actix_rt::test]
+async fn schemas_metainfo() {
+    let harness = Harness::new();
+    let token = harness.token(DEVELOPER);
+
+    let resp = harness.get("/mgmt/schemas/", Some(&token)).await;
+    assert_eq!(resp.status, StatusCode::OK);
+    assert_eq!(resp.json(), json!({ "schemas": ["hr"] }));
+
+    let resp = harness.get("/mgmt/schemas/hr", Some(&token)).await;
+    let tables: Vec<String> = resp.json()["tables"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["name"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(tables, vec!["departments", "employees", "employees_v", "events"]);
+
+    let resp = harness.get("/mgmt/schemas/hr/employees", Some(&token)).await;
+    assert_eq!(resp.status, StatusCode::OK);
+    let table = resp.json();
+    assert_eq!(table["has_pk"], json!(true));
+    assert_eq!(table["num_rows"], json!(4));
+    assert_eq!(table["columns"][0], json!({ "name": "id", "col_type": "Integer", "is_pk": true, "nullable": false }));
+
+    let resp = harness.get("/mgmt/schemas/hr/unknown", Some(&token)).await;
+    assert_eq!(resp.status, StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn query_by_pk() {
+    let harness = Harness::new();
+    let token = harness.token(API);
+
+    let resp = harness.get("/api/v1/hr/employees/2", Some(&token)).await;
+    assert_eq!(resp.status, StatusCode::OK);
+    let row = resp.json();
+    assert_eq!(row["name"], json!("Blake"));
+    assert_eq!(row["salary"], json!(2850.5));
+
+    let resp = harness.get("/api/v1/hr/employees/1,2", Some(&token)).await;
+    assert_eq!(resp.status, StatusCode::BAD_REQUEST);
+
+    let resp = harness.get("/api/v1/hr/employees_v/1", Some(&token)).await;
+    assert_eq!(resp.status, StatusCode::BAD_REQUEST);
+
+    let resp = harness.get("/api/v1/hr/unknown/1", Some(&token)).await;
+    assert_eq!(resp.status, StatusCode::NOT_FOUND);
+}
+
+#[actix_rt::test]
+async fn list_by_params() {
+    let harness = Harness::new();
+    let token = harness.token(API);
+
+    let resp = harness.get(&format!("/api/v1/hr/employees/?q={}", DEPARTMENT_10), Some(&token)).await;
+    assert_eq!(resp.status, StatusCode::OK);
+    let mut found = ids(&resp.json());
+    found.sort_unstable();
+    assert_eq!(found, vec![1, 3]);
+
+    let resp = harness.get("/api/v1/hr/employees/?q=%7B%22unknown%22%3A%221%22%7D", Some(&token)).await;
+    assert_eq!(resp.status, StatusCode::BAD_REQUEST);
+
+    let resp = harness.get("/api/v1/hr/employees/?q=not-json", Some(&token)).await;
+    assert_eq!(resp.status, StatusCode::BAD_REQUEST);
+
+    // large table without filter by indexed column
+    let resp = harness.get(&format!("/api/v1/hr/events/?q={}", EMPTY_FILTER), Some(&token)).await;
+    assert_eq!(resp.status, StatusCode::BAD_REQUEST);
+}
+
+#[actix_rt::test]
+async fn list_with_order_and_pagination() {
+    let harness = Harness::new();
+    let token = harness.token(API);
+
+    let resp = harness.get(&format!("/api/v1/hr/employees/?q={}&order=salary", EMPTY_FILTER), Some(&token)).await;
+    assert_eq!(resp.status, StatusCode::OK);
+    // nulls are last
+    assert_eq!(ids(&resp.json()), vec![2, 4, 1, 3]);
+
+    let page = |offset: u32| format!("/api/v1/hr/employees/?q={}&order=id&limit=2&offset={}", EMPTY_FILTER, offset);
+    let resp = harness.get(&page(2), Some(&token)).await;
+    assert_eq!(ids(&resp.json()), vec![3, 4]);
+    let resp = harness.get(&page(4), Some(&token)).await;
+    assert_eq!(ids(&resp.json()), Vec::<i64>::new());
+
+    // offset must be a multiple of limit
+    let resp = harness.get(&page(3), Some(&token)).await;
+    assert_eq!(resp.status, StatusCode::BAD_REQUEST);
+
+    let resp = harness.get(&format!("/api/v1/hr/employees/?q={}&limit=101", EMPTY_FILTER), Some(&token)).await;
+    assert_eq!(resp.status, StatusCode::BAD_REQUEST);
+
+    let resp = harness.get(&format!("/api/v1/hr/employees/?q={}&order=unknown", EMPTY_FILTER), Some(&token)).await;
+    assert_eq!(resp.status, StatusCode::BAD_REQUEST);
+}
+
+#[actix_rt::test]
+async fn unauthenticated_requests_are_rejected() {
+    let harness = Harness::new();
+
+    for uri in &["/api/v1/hr/employees/1", "/mgmt/schemas/"] {
+        let resp = harness.get(uri, None).await;
+        assert_eq!(resp.status, StatusCode::UNAUTHORIZED);
+        assert!(resp.headers.contains_key(header::WWW_AUTHENTICATE));
+        assert_eq!(resp.json()["code"], json!("unauthorized"));
+    }
+}
+
+#[actix_rt::test]
+async fn invalid_tokens_are_rejected() {
+    let harness = Harness::new();
+
+    let expired = harness.expired_token(API);
+    let resp = harness.get("/api/v1/hr/employees/1", Some(&expired)).await;
+    assert_eq!(resp.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(resp.json()["code"], json!("invalid_token"));
+
+    // token signed by other key
+    let foreign = Harness::new().token(API);
+    let resp = harness.get("/api/v1/hr/employees/1", Some(&foreign)).await;
+    assert_eq!(resp.status, StatusCode::UNAUTHORIZED);
+
+    let req = actix_web::test::TestRequest::get()
+        .uri("/api/v1/hr/employees/1")
+        .header(header::AUTHORIZATION, "Basic dXNlcjpwdw==");
+    let resp = harness.call(req).await;
+    assert_eq!(resp.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(resp.headers.get(header::WWW_AUTHENTICATE).unwrap(), "Bearer realm=\"foundation\"");
+}
+
+#[actix_rt::test]
+async fn groups_are_required() {
+    let harness = Harness::new();
+
+    // api requires BASE_ACCESS, management requires DEVELOPER
+    let developer = harness.token(DEVELOPER);
+    let resp = harness.get("/api/v1/hr/employees/1", Some(&developer)).await;
+    assert_eq!(resp.status, StatusCode::FORBIDDEN);
+    assert_eq!(resp.json()["code"], json!("insufficient_scope"));
+
+    let api = harness.token(API);
+    let resp = harness.get("/mgmt/schemas/", Some(&api)).await;
+    assert_eq!(resp.status, StatusCode::FORBIDDEN);
+
+    let resp = harness.get("/mgmt/search?q=employee", Some(&api)).await;
+    assert_eq!(resp.status, StatusCode::FORBIDDEN);
+}
+
+#[actix_rt::test]
+async fn exhausted_pool_is_unavailable() {
+    let harness = Harness::with_backend(Arc::new(MemoryBackend::exhausted()));
+    let token = harness.token(API);
+
+    for uri in &["/api/v1/hr/employees/1".to_string(), format!("/api/v1/hr/employees/?q={}", DEPARTMENT_10)] {
+        let resp = harness.get(uri, Some(&token)).await;
+        assert_eq!(resp.status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(resp.headers.get(header::RETRY_AFTER).unwrap(), "1");
+        assert_eq!(resp.json()["code"], json!("pool_exhausted"));
+    }
+
+    // other instances in same process have own backends
+    let other = Harness::new();
+    let resp = other.get("/api/v1/hr/employees/1", Some(&other.token(API))).await;
+    assert_eq!(resp.status, StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn metrics_of_requests_and_authentication() {
+    let harness = Harness::new();
+    harness.get("/api/v1/hr/employees/1", Some(&harness.token(API))).await;
+    harness.get("/api/v1/hr/employees/1", Some(&harness.expired_token(API))).await;
+
+    let resp = harness.get("/mgmt/metrics", Some(&harness.token(API))).await;
+    assert_eq!(resp.status, StatusCode::FORBIDDEN);
+
+    let resp = harness.get("/mgmt/metrics", Some(&harness.token(DEVELOPER))).await;
+    assert_eq!(resp.status, StatusCode::OK);
+    let text = resp.text();
+    assert!(text.contains("foundation_http_requests_total{method=\"GET\",route=\"/api/v1/{schema}/{table}/{pk}\",status=\"200\",schema=\"\",table=\"\"} 1\n"));
+    assert!(text.contains("foundation_http_requests_total{method=\"GET\",route=\"/api/v1/{schema}/{table}/{pk}\",status=\"401\",schema=\"\",table=\"\"} 1\n"));
+    assert!(text.contains("foundation_rows_returned_total{schema=\"\",table=\"\"} 1\n"));
+    assert!(text.contains("foundation_auth_failures_total{reason=\"expired\"} 1\n"));
+    assert!(text.contains("foundation_pool_connections_idle{datasource=\"default\"} 1\n"));
+}
+
+#[actix_rt::test]
+async fn request_id_and_trace_context_are_propagated() {
+    let harness = Harness::new();
+    let token = harness.token(API);
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/hr/employees/1")
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .header("x-request-id", "order-4711")
+        .header("traceparent", "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01");
+    let resp = harness.call(req).await;
+    assert_eq!(resp.status, StatusCode::OK);
+    assert_eq!(resp.headers.get("x-request-id").unwrap(), "order-4711");
+    let traceparent = resp.headers.get("traceparent").unwrap().to_str().unwrap();
+    assert!(traceparent.starts_with("00-4bf92f3577b34da6a3ce929d0e0e4736-"));
+    assert!(traceparent.ends_with("-01"));
+    assert!(!traceparent.contains("00f067aa0ba902b7"));
+
+    // ids are generated for requests without headers, invalid ids are replaced
+    let req = test::TestRequest::get()
+        .uri("/mgmt/health")
+        .header("x-request-id", "a".repeat(200));
+    let resp = harness.call(req).await;
+    let request_id = resp.headers.get("x-request-id").unwrap().to_str().unwrap();
+    assert_eq!(request_id.len(), 32);
+    assert!(resp.headers.get("traceparent").is_some());
+}
+
+#[actix_rt::test]
+async fn statement_timeouts_by_route_and_user() {
+    let slow = || Arc::new(MemoryBackend::slow(Duration::from_millis(300)));
+    let routes: HashMap<String, f64> = [("/api/v1/{schema}/{table}/{pk}".to_string(), 0.05)].iter().cloned().collect();
+
+    let harness = Harness::with_timeouts(slow(), TimeoutsConfig { routes: routes.clone(), ..Default::default() });
+    let resp = harness.get("/api/v1/hr/employees/1", Some(&harness.token(API))).await;
+    assert_eq!(resp.status, StatusCode::GATEWAY_TIMEOUT);
+    let problem = resp.json();
+    assert_eq!(problem["code"], json!("statement_timeout"));
+    let detail = problem["detail"].as_str().unwrap();
+    assert!(detail.starts_with("statement timed out after ") && detail.ends_with("timeout is 50 ms"), "{}", detail);
+
+    // other routes have default timeout
+    let resp = harness.get(&format!("/api/v1/hr/employees/?q={}", DEPARTMENT_10), Some(&harness.token(API))).await;
+    assert_eq!(resp.status, StatusCode::OK);
+
+    // timeout of user overrides timeout of route
+    let users = [("42".to_string(), 5.0)].iter().cloned().collect();
+    let harness = Harness::with_timeouts(slow(), TimeoutsConfig { routes, users, ..Default::default() });
+    let resp = harness.get("/api/v1/hr/employees/1", Some(&harness.token(API))).await;
+    assert_eq!(resp.status, StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn errors_are_problem_details() {
+    let harness = Harness::new();
+    let token = harness.token(API);
+
+    let req = test::TestRequest::get()
+        .uri("/api/v1/hr/unknown/1")
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .header("x-request-id", "order-4711");
+    let resp = harness.call(req).await;
+    assert_eq!(resp.status, StatusCode::NOT_FOUND);
+    assert_eq!(resp.headers.get(header::CONTENT_TYPE).unwrap(), "application/problem+json");
+    assert_eq!(resp.json(), json!({
+        "type": "about:blank",
+        "title": "Not Found",
+        "status": 404,
+        "code": "not_found",
+        "detail": "Table hr.unknown is not found",
+        "request_id": "order-4711"
+    }));
+
+    let resp = harness.get("/api/v1/hr/employees/99", Some(&token)).await;
+    assert_eq!(resp.status, StatusCode::NOT_FOUND);
+    assert_eq!(resp.json()["code"], json!("no_data_found"));
+
+    let resp = harness.get("/api/v1/hr/employees/?q=not-json", Some(&token)).await;
+    assert_eq!(resp.status, StatusCode::BAD_REQUEST);
+    assert_eq!(resp.json()["code"], json!("invalid_request"));
+}
+
+#[actix_rt::test]
+async fn graphql_relations_are_loaded_in_batches() {
+    let backend = Arc::new(MemoryBackend::fixture());
+    let harness = Harness::with_backend(backend.clone());
+    let token = harness.token(API);
+
+    let query = "{ hr_departments(order: [\"id\"]) { id employees(order: [\"id\"]) { id departments { name } } } }";
+    let req = test::TestRequest::post()
+        .uri("/api/graphql")
+        .header(header::AUTHORIZATION, format!("Bearer {}", token))
+        .set_json(&json!({ "query": query }));
+    let resp = harness.call(req).await;
+    assert_eq!(resp.status, StatusCode::OK);
+    let departments = &resp.json()["data"]["hr_departments"];
+    assert_eq!(ids(departments), vec![10, 20, 30]);
+    assert_eq!(ids(&departments[0]["employees"]), vec![1, 3]);
+    assert_eq!(departments[2]["employees"][0]["departments"], json!({ "name": "Sales" }));
+
+    // one select for list and one for every relation, not one per row
+    assert_eq!(backend.selects(), 3);
+}
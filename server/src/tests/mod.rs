@@ -0,0 +1,142 @@
+//! In-process tests of api: stand-in database and tokens signed with throwaway RSA key
+
+mod api;
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use actix_web::{test, App, HttpResponse};
+use actix_web::dev::{Body, ResponseBody, Service};
+use actix_web::http::{header, HeaderMap, StatusCode};
+use actix_web::web::Bytes;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use openssl::rsa::Rsa;
+use serde_json::json;
+
+use crate::application::{self, ApplicationState};
+use crate::backend::{Backend, MemoryBackend};
+use crate::metrics::RequestMetrics;
+use crate::security::{IdentityService, RateLimit};
+use crate::server::config::TimeoutsConfig;
+use crate::telemetry::{RequestTracing, Tracer};
+
+const ISSUER: &str = "foundation-tests";
+
+// every harness has own key file
+static KEY_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// Application with fixture database, identity checked by public key of harness
+pub struct Harness {
+    state:    Arc<ApplicationState>,
+    key_file: PathBuf,
+    key:      EncodingKey,
+}
+
+/// Response of application, also for errors of middlewares
+pub struct Response {
+    pub status:  StatusCode,
+    pub headers: HeaderMap,
+    pub body:    Bytes,
+}
+
+impl Response {
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_slice(&self.body).expect("JSON body")
+    }
+
+    pub fn text(&self) -> &str {
+        std::str::from_utf8(&self.body).expect("UTF-8 body")
+    }
+}
+
+impl Harness {
+    pub fn new() -> Harness {
+        Harness::with_backend(Arc::new(MemoryBackend::fixture()))
+    }
+
+    pub fn with_backend(backend: Arc<dyn Backend>) -> Harness {
+        Harness::with_timeouts(backend, TimeoutsConfig::default())
+    }
+
+    pub fn with_timeouts(backend: Arc<dyn Backend>, timeouts: TimeoutsConfig) -> Harness {
+        let rsa = Rsa::generate(2048).unwrap();
+        let key_file = std::env::temp_dir().join(format!(
+            "foundation-test-{}-{}.pem",
+            std::process::id(),
+            KEY_FILES.fetch_add(1, Ordering::SeqCst)
+        ));
+        std::fs::write(&key_file, rsa.public_key_to_pem().unwrap()).unwrap();
+        let key = EncodingKey::from_rsa_pem(&rsa.private_key_to_pem().unwrap()).unwrap();
+
+        let state = ApplicationState::with_backend(backend, None, timeouts).unwrap();
+        Harness { state, key_file, key }
+    }
+
+    /// valid token of user with groups
+    pub fn token(&self, groups: &[&str]) -> String {
+        self.sign(groups, 3600)
+    }
+
+    pub fn expired_token(&self, groups: &[&str]) -> String {
+        self.sign(groups, -3600)
+    }
+
+    fn sign(&self, groups: &[&str], expires_in: i64) -> String {
+        let now = chrono::Utc::now().timestamp();
+        let claims = json!({
+            "exp": now + expires_in,
+            "iat": now,
+            "iss": ISSUER,
+            "sub": "42",
+            "groups": groups
+        });
+        jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &self.key).unwrap()
+    }
+
+    /// GET request with bearer token, if any
+    pub async fn get(&self, uri: &str, token: Option<&str>) -> Response {
+        let mut req = test::TestRequest::get().uri(uri);
+        if let Some(token) = token {
+            req = req.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        self.call(req).await
+    }
+
+    pub async fn call(&self, req: test::TestRequest) -> Response {
+        let identity = IdentityService::new(ISSUER.to_string(), self.key_file.clone(), None, self.state.metrics(), crate::server::discard_logging());
+        let mut app = test::init_service(
+            App::new()
+                .data(self.state.clone())
+                .wrap(identity)
+                .wrap(RequestMetrics::new(self.state.metrics()))
+                .wrap(RequestTracing::new(Tracer::disabled()))
+                .service(application::base_scope(RateLimit::new(None)))
+                .service(application::v1_api_scope(RateLimit::new(None)))
+        ).await;
+
+        match app.call(req.to_request()).await {
+            Ok(resp) => {
+                let status = resp.status();
+                let headers = resp.headers().clone();
+                let body = test::read_body(resp).await;
+                Response { status, headers, body }
+            },
+            // errors of middlewares are rendered by server
+            Err(err) => {
+                let resp: HttpResponse = err.into();
+                let body = match resp.body() {
+                    ResponseBody::Body(Body::Bytes(bytes)) | ResponseBody::Other(Body::Bytes(bytes)) => bytes.clone(),
+                    _ => Bytes::new()
+                };
+                Response { status: resp.status(), headers: resp.headers().clone(), body }
+            }
+        }
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.key_file);
+    }
+}